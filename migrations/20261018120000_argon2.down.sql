@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+-- Argon2 hashes do not fit the old column, the affected accounts are locked with a value
+-- no legacy hash can match and need a password reset after the rollback
+UPDATE Users SET Password = repeat('!', 64) WHERE length(Password) > 64;
+
+ALTER TABLE Users ALTER COLUMN Password TYPE CHAR(64);
@@ -0,0 +1,3 @@
+-- Add up migration script here
+
+ALTER TABLE Users ALTER COLUMN Password TYPE TEXT;
\ No newline at end of file
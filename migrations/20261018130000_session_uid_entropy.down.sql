@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DELETE FROM Sessions WHERE LENGTH(Uid) > 11;
+ALTER TABLE Sessions ALTER COLUMN Uid TYPE CHAR(11);
\ No newline at end of file
@@ -0,0 +1,3 @@
+-- Add up migration script here
+
+ALTER TABLE Sessions ALTER COLUMN Uid TYPE VARCHAR(64);
\ No newline at end of file
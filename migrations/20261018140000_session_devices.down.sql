@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+ALTER TABLE Sessions DROP COLUMN LastSeenAt;
+ALTER TABLE Sessions DROP COLUMN CreatedAt;
+ALTER TABLE Sessions DROP COLUMN Ip;
+ALTER TABLE Sessions DROP COLUMN UserAgent;
+ALTER TABLE Sessions DROP COLUMN Id;
\ No newline at end of file
@@ -0,0 +1,7 @@
+-- Add up migration script here
+
+ALTER TABLE Sessions ADD COLUMN Id SERIAL NOT NULL UNIQUE;
+ALTER TABLE Sessions ADD COLUMN UserAgent TEXT;
+ALTER TABLE Sessions ADD COLUMN Ip TEXT;
+ALTER TABLE Sessions ADD COLUMN CreatedAt TIMESTAMPTZ NOT NULL DEFAULT NOW();
+ALTER TABLE Sessions ADD COLUMN LastSeenAt TIMESTAMPTZ NOT NULL DEFAULT NOW();
\ No newline at end of file
@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+DROP TABLE ApiTokens;
\ No newline at end of file
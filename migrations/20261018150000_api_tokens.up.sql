@@ -0,0 +1,15 @@
+-- Add up migration script here
+
+CREATE TABLE ApiTokens (
+    Id SERIAL PRIMARY KEY,
+    UserId INTEGER NOT NULL,
+    Name VARCHAR(50) NOT NULL,
+    TokenHash CHAR(64) NOT NULL UNIQUE,
+    Scopes TEXT[] NOT NULL,
+    CreatedAt TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    LastUsedAt TIMESTAMPTZ,
+    ExpiresAt TIMESTAMPTZ,
+    FOREIGN KEY (UserId) REFERENCES Users(Id) ON DELETE CASCADE
+);
+
+CREATE INDEX IdxApiTokensUserId ON ApiTokens(UserId);
\ No newline at end of file
@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+DROP TABLE Bots;
+DELETE FROM Users WHERE IsBot;
+ALTER TABLE Messages DROP COLUMN IsBot;
+ALTER TABLE Users DROP COLUMN IsBot;
@@ -0,0 +1,15 @@
+-- Add up migration script here
+
+ALTER TABLE Users ADD COLUMN IsBot BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE Messages ADD COLUMN IsBot BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE Bots (
+    UserId INTEGER PRIMARY KEY,
+    OwnerId INTEGER NOT NULL,
+    WebhookUrl TEXT,
+    WebhookSecret CHAR(64) NOT NULL,
+    FOREIGN KEY (UserId) REFERENCES Users(Id) ON DELETE CASCADE,
+    FOREIGN KEY (OwnerId) REFERENCES Users(Id) ON DELETE CASCADE
+);
+
+CREATE INDEX IdxBotsOwnerId ON Bots(OwnerId);
@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+ALTER TABLE ChatMembers DROP CONSTRAINT chatmembers_userid_fkey;
+ALTER TABLE ChatMembers ADD CONSTRAINT chatmembers_userid_fkey
+    FOREIGN KEY (UserId) REFERENCES Users(Id) ON DELETE CASCADE;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DROP TABLE PendingLogins;
+DROP TABLE RecoveryCodes;
+DROP TABLE UserTotp;
@@ -0,0 +1,28 @@
+-- Add up migration script here
+
+CREATE TABLE UserTotp (
+    UserId INTEGER PRIMARY KEY,
+    Secret VARCHAR(64) NOT NULL,
+    Enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    LastStep BIGINT NOT NULL DEFAULT 0,
+    CreatedAt TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    FOREIGN KEY (UserId) REFERENCES Users(Id) ON DELETE CASCADE
+);
+
+CREATE TABLE RecoveryCodes (
+    Id SERIAL PRIMARY KEY,
+    UserId INTEGER NOT NULL,
+    CodeHash CHAR(64) NOT NULL,
+    UsedAt TIMESTAMPTZ,
+    FOREIGN KEY (UserId) REFERENCES Users(Id) ON DELETE CASCADE
+);
+
+CREATE TABLE PendingLogins (
+    Uid VARCHAR(64) PRIMARY KEY,
+    UserId INTEGER NOT NULL,
+    Attempts INTEGER NOT NULL DEFAULT 0,
+    ExpiresAt TIMESTAMPTZ NOT NULL,
+    FOREIGN KEY (UserId) REFERENCES Users(Id) ON DELETE CASCADE
+);
+
+CREATE INDEX IdxRecoveryCodesUserId ON RecoveryCodes(UserId);
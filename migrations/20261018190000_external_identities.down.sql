@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE AuthRequests;
+DROP TABLE ExternalIdentities;
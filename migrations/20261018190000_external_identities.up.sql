@@ -0,0 +1,20 @@
+-- Add up migration script here
+
+CREATE TABLE ExternalIdentities (
+    Provider VARCHAR(50) NOT NULL,
+    Subject VARCHAR(255) NOT NULL,
+    UserId INTEGER NOT NULL,
+    CreatedAt TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (Provider, Subject),
+    FOREIGN KEY (UserId) REFERENCES Users(Id) ON DELETE CASCADE
+);
+
+CREATE TABLE AuthRequests (
+    State VARCHAR(64) PRIMARY KEY,
+    Provider VARCHAR(50) NOT NULL,
+    Nonce VARCHAR(64) NOT NULL,
+    CodeVerifier VARCHAR(128) NOT NULL,
+    ExpiresAt TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IdxExternalIdentitiesUserId ON ExternalIdentities(UserId);
@@ -0,0 +1,14 @@
+-- Add up migration script here
+
+CREATE TABLE Profiles (
+    UserId INTEGER PRIMARY KEY,
+    DisplayName VARCHAR(64),
+    Bio VARCHAR(500),
+    StatusText VARCHAR(100),
+    StatusExpiresAt TIMESTAMPTZ,
+    AvatarType VARCHAR(32),
+    Avatar BYTEA,
+    AvatarUpdatedAt TIMESTAMPTZ,
+    UpdatedAt TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    FOREIGN KEY (UserId) REFERENCES Users(Id) ON DELETE CASCADE
+);
@@ -0,0 +1,8 @@
+-- Add up migration script here
+
+CREATE TABLE Presence (
+    UserId INTEGER PRIMARY KEY,
+    LastSeenAt TIMESTAMPTZ,
+    Hidden BOOLEAN NOT NULL DEFAULT FALSE,
+    FOREIGN KEY (UserId) REFERENCES Users(Id) ON DELETE CASCADE
+);
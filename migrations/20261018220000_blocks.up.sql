@@ -0,0 +1,12 @@
+-- Add up migration script here
+
+CREATE TABLE Blocks (
+    BlockerId INTEGER NOT NULL,
+    BlockedId INTEGER NOT NULL,
+    CreatedAt TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (BlockerId, BlockedId),
+    FOREIGN KEY (BlockerId) REFERENCES Users(Id) ON DELETE CASCADE,
+    FOREIGN KEY (BlockedId) REFERENCES Users(Id) ON DELETE CASCADE
+);
+
+CREATE INDEX IdxBlocksBlockedId ON Blocks(BlockedId);
@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+DROP TABLE AdminActions;
+
+ALTER TABLE Users
+    DROP COLUMN IsAdmin,
+    DROP COLUMN SuspendedAt;
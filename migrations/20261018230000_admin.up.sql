@@ -0,0 +1,17 @@
+-- Add up migration script here
+
+ALTER TABLE Users
+    ADD COLUMN IsAdmin BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN SuspendedAt TIMESTAMPTZ;
+
+CREATE TABLE AdminActions (
+    Id SERIAL PRIMARY KEY,
+    AdminId INTEGER,
+    Action VARCHAR(32) NOT NULL,
+    TargetId INTEGER NOT NULL,
+    TargetName VARCHAR(30) NOT NULL,
+    CreatedAt TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    FOREIGN KEY (AdminId) REFERENCES Users(Id) ON DELETE SET NULL
+);
+
+CREATE INDEX IdxAdminActionsTargetId ON AdminActions(TargetId);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP INDEX IdxChatMembersOwner;
+ALTER TABLE ChatMembers DROP COLUMN Role, DROP COLUMN JoinedAt;
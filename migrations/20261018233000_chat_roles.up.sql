@@ -0,0 +1,13 @@
+-- Add up migration script here
+
+ALTER TABLE ChatMembers
+    ADD COLUMN Role VARCHAR(16) NOT NULL DEFAULT 'member',
+    ADD COLUMN JoinedAt TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+-- chats created before roles existed get an owner picked like a successor of a deleted owner,
+-- existing members all joined at migration time, so it is the one with the lowest user id
+UPDATE ChatMembers cm SET Role = 'owner'
+FROM (SELECT DISTINCT ON (ChatId) ChatId, UserId FROM ChatMembers ORDER BY ChatId, JoinedAt, UserId) first
+WHERE cm.ChatId = first.ChatId AND cm.UserId = first.UserId;
+
+CREATE UNIQUE INDEX IdxChatMembersOwner ON ChatMembers(ChatId) WHERE Role = 'owner';
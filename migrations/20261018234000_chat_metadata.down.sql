@@ -0,0 +1,8 @@
+-- Add down migration script here
+
+ALTER TABLE Chats
+    DROP COLUMN Description,
+    DROP COLUMN ImageType,
+    DROP COLUMN Image,
+    DROP COLUMN ImageUpdatedAt,
+    DROP COLUMN Version;
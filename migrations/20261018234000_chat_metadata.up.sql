@@ -0,0 +1,8 @@
+-- Add up migration script here
+
+ALTER TABLE Chats
+    ADD COLUMN Description VARCHAR(500),
+    ADD COLUMN ImageType VARCHAR(32),
+    ADD COLUMN Image BYTEA,
+    ADD COLUMN ImageUpdatedAt TIMESTAMPTZ,
+    ADD COLUMN Version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE DirectChats;
+ALTER TABLE Chats DROP COLUMN Kind;
@@ -0,0 +1,13 @@
+-- Add up migration script here
+
+ALTER TABLE Chats ADD COLUMN Kind VARCHAR(16) NOT NULL DEFAULT 'group';
+
+-- one direct chat per pair of users, the smaller id first
+CREATE TABLE DirectChats (
+    ChatId INTEGER PRIMARY KEY,
+    FirstUserId INTEGER NOT NULL,
+    SecondUserId INTEGER NOT NULL,
+    UNIQUE (FirstUserId, SecondUserId),
+    CHECK (FirstUserId < SecondUserId),
+    FOREIGN KEY (ChatId) REFERENCES Chats(Id) ON DELETE CASCADE
+);
@@ -0,0 +1,15 @@
+-- Add up migration script here
+
+CREATE TABLE ChatInvites (
+    Code VARCHAR(32) PRIMARY KEY,
+    ChatId INTEGER NOT NULL,
+    CreatedBy INTEGER,
+    MaxUses INTEGER,
+    Uses INTEGER NOT NULL DEFAULT 0,
+    ExpiresAt TIMESTAMPTZ,
+    CreatedAt TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    FOREIGN KEY (ChatId) REFERENCES Chats(Id) ON DELETE CASCADE,
+    FOREIGN KEY (CreatedBy) REFERENCES Users(Id) ON DELETE SET NULL
+);
+
+CREATE INDEX IdxChatInvitesChatId ON ChatInvites(ChatId);
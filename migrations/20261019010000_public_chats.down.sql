@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+DROP INDEX IdxChatsDescriptionTrgm;
+DROP INDEX IdxChatsTitleTrgm;
+DROP INDEX IdxChatsPublic;
+ALTER TABLE Chats DROP COLUMN Visibility;
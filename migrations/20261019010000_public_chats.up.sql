@@ -0,0 +1,7 @@
+-- Add up migration script here
+
+ALTER TABLE Chats ADD COLUMN Visibility VARCHAR(16) NOT NULL DEFAULT 'private';
+
+CREATE INDEX IdxChatsPublic ON Chats(Id) WHERE Visibility = 'public';
+CREATE INDEX IdxChatsTitleTrgm ON Chats USING gin (Title gin_trgm_ops) WHERE Visibility = 'public';
+CREATE INDEX IdxChatsDescriptionTrgm ON Chats USING gin (Description gin_trgm_ops) WHERE Visibility = 'public';
@@ -0,0 +1,9 @@
+-- Add down migration script here
+
+DROP INDEX IdxChatsLastActivity;
+DROP INDEX IdxMessagesChatIdId;
+CREATE INDEX IdxMessagesChatId ON Messages(ChatId);
+
+ALTER TABLE ChatMembers DROP COLUMN LastReadMessageId;
+ALTER TABLE Chats DROP COLUMN LastActivityAt;
+ALTER TABLE Chats DROP COLUMN LastMessageId;
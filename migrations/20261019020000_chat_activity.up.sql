@@ -0,0 +1,20 @@
+-- Add up migration script here
+
+ALTER TABLE Chats ADD COLUMN LastMessageId BIGINT REFERENCES Messages(Id) ON DELETE SET NULL;
+ALTER TABLE Chats ADD COLUMN LastActivityAt TIMESTAMPTZ NOT NULL DEFAULT NOW();
+ALTER TABLE ChatMembers ADD COLUMN LastReadMessageId BIGINT;
+
+UPDATE Chats SET LastActivityAt = CreatedAt WHERE CreatedAt IS NOT NULL;
+
+UPDATE Chats c SET LastMessageId = m.Id, LastActivityAt = m.CreatedAt
+FROM (
+    SELECT DISTINCT ON (ChatId) ChatId, Id, CreatedAt FROM Messages ORDER BY ChatId, Id DESC
+) m
+WHERE m.ChatId = c.Id;
+
+-- existing history counts as read, only new messages show up as unread
+UPDATE ChatMembers cm SET LastReadMessageId = c.LastMessageId FROM Chats c WHERE c.Id = cm.ChatId;
+
+DROP INDEX IdxMessagesChatId;
+CREATE INDEX IdxMessagesChatIdId ON Messages(ChatId, Id);
+CREATE INDEX IdxChatsLastActivity ON Chats(LastActivityAt DESC, Id DESC);
@@ -0,0 +1,3 @@
+-- Add up migration script here
+
+ALTER TABLE Users ADD COLUMN ReadReceipts BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+ALTER TABLE Sessions DROP COLUMN ExternalAuthAt;
\ No newline at end of file
@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+-- set when the session was created by an identity provider login, which stands in for
+-- the password on sensitive changes of accounts without one
+ALTER TABLE Sessions ADD COLUMN ExternalAuthAt TIMESTAMPTZ;
\ No newline at end of file
@@ -0,0 +1,424 @@
+use std::{collections::HashMap, sync::Arc};
+use axum::{
+    Json,
+    Extension,
+    extract::{Path, Query, State},
+};
+use crate::{
+    AppState,
+    error::{ApiError, RepositoryError},
+    controllers::{export, users::hash_password},
+    services::{auth::Auth, session, trace::TraceId},
+    models::{
+        users::UserId,
+        admin::{
+            AdminActionKind,
+            AdminActionResponse,
+            AdminUser,
+            GetAdminActionsQuery,
+            GetAdminActionsResponse,
+            GetAdminUsersQuery,
+            GetAdminUsersResponse,
+            ResetPasswordRequest,
+            MAX_ADMIN_PAGE_SIZE,
+        },
+    },
+};
+
+/// List users
+///
+/// Every account including bots and suspended ones, ordered by id.
+#[utoipa::path(
+    get,
+    path = "/admin/users",
+    tag = "admin",
+    params(
+        ("username" = Option<String>, Query, description = "Part of the username"),
+        ("after" = Option<UserId>, Query, description = "Last user id of the previous page"),
+        ("limit" = Option<i64>, Query, description = "Number of users to return, max 100")
+    ),
+    responses(
+        (status = OK, description = "Users", body = GetAdminUsersResponse),
+        (status = FORBIDDEN, description = "Not an administrator", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn get_users(
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<GetAdminUsersQuery>,
+) -> Result<GetAdminUsersResponse, ApiError> {
+    let limit = params.limit.clamp(1, MAX_ADMIN_PAGE_SIZE);
+    let username = params.username.filter(|username| !username.trim().is_empty());
+
+    let mut users = match state.admin.get_users(username, params.after, limit + 1).await {
+        Ok(users) => users,
+        Err(err) => {
+            tracing::error!("failed to get users: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    };
+
+    let has_more = users.len() > limit as usize;
+    users.truncate(limit as usize);
+
+    Ok(GetAdminUsersResponse { users, has_more })
+}
+
+/// Suspend user
+///
+/// Signs the user out everywhere and rejects their sessions and API tokens until unsuspended,
+/// bots owned by the user are suspended too.
+#[utoipa::path(
+    put,
+    path = "/admin/users/{id}/suspension",
+    tag = "admin",
+    params(
+        ("id" = UserId, Path, description = "User id")
+    ),
+    responses(
+        (status = NO_CONTENT, description = "User suspended", body = AdminActionResponse),
+        (status = BAD_REQUEST, description = "Suspending yourself", body = ApiError, example = json!({"type": "Validation", "fields": {"id": ["You cannot suspend yourself"]}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Not an administrator", body = ApiError),
+        (status = NOT_FOUND, description = "User not found", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn suspend_user(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(user_id): Path<UserId>,
+) -> Result<AdminActionResponse, ApiError> {
+    check_not_self(&auth, user_id, "You cannot suspend yourself", &trace_id)?;
+    let target = get_target(&state, user_id, &trace_id).await?;
+
+    if let Err(err) = state.admin.set_suspended(user_id, true).await {
+        tracing::error!("failed to suspend user: {err}");
+        return Err(ApiError::Unknown { trace_id });
+    }
+
+    if let Err(err) = state.sessions.remove_user_sessions(user_id).await {
+        tracing::error!("failed to revoke sessions: {err}");
+        return Err(ApiError::Unknown { trace_id });
+    }
+
+    // dropping the sender ends every open event stream of the user, token ones included
+    state.events.remove(&user_id);
+    record_action(&state, &auth, AdminActionKind::Suspend, &target, &trace_id).await?;
+    Ok(AdminActionResponse)
+}
+
+/// Unsuspend user
+#[utoipa::path(
+    delete,
+    path = "/admin/users/{id}/suspension",
+    tag = "admin",
+    params(
+        ("id" = UserId, Path, description = "User id")
+    ),
+    responses(
+        (status = NO_CONTENT, description = "User unsuspended", body = AdminActionResponse),
+        (status = FORBIDDEN, description = "Not an administrator", body = ApiError),
+        (status = NOT_FOUND, description = "User not found", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn unsuspend_user(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(user_id): Path<UserId>,
+) -> Result<AdminActionResponse, ApiError> {
+    let target = get_target(&state, user_id, &trace_id).await?;
+
+    if let Err(err) = state.admin.set_suspended(user_id, false).await {
+        tracing::error!("failed to unsuspend user: {err}");
+        return Err(ApiError::Unknown { trace_id });
+    }
+
+    record_action(&state, &auth, AdminActionKind::Unsuspend, &target, &trace_id).await?;
+    Ok(AdminActionResponse)
+}
+
+/// Force logout
+///
+/// Revokes every session of the user, API tokens keep working.
+#[utoipa::path(
+    delete,
+    path = "/admin/users/{id}/sessions",
+    tag = "admin",
+    params(
+        ("id" = UserId, Path, description = "User id")
+    ),
+    responses(
+        (status = NO_CONTENT, description = "Sessions revoked", body = AdminActionResponse),
+        (status = FORBIDDEN, description = "Not an administrator", body = ApiError),
+        (status = NOT_FOUND, description = "User not found", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn force_logout(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(user_id): Path<UserId>,
+) -> Result<AdminActionResponse, ApiError> {
+    let target = get_target(&state, user_id, &trace_id).await?;
+
+    match state.sessions.remove_user_sessions(user_id).await {
+        Ok(removed) => {
+            tracing::info!("{} sessions of user {user_id} revoked", removed.len());
+            session::close_streams(&state.events, user_id, removed);
+        }
+
+        Err(err) => {
+            tracing::error!("failed to revoke sessions: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    }
+
+    record_action(&state, &auth, AdminActionKind::ForceLogout, &target, &trace_id).await?;
+    Ok(AdminActionResponse)
+}
+
+/// Reset password
+///
+/// Sets a new password and revokes every session of the user.
+#[utoipa::path(
+    put,
+    path = "/admin/users/{id}/password",
+    tag = "admin",
+    params(
+        ("id" = UserId, Path, description = "User id")
+    ),
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = NO_CONTENT, description = "Password reset", body = AdminActionResponse),
+        (status = BAD_REQUEST, description = "Invalid password, or the user is a bot", body = ApiError),
+        (status = FORBIDDEN, description = "Not an administrator", body = ApiError),
+        (status = NOT_FOUND, description = "User not found", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn reset_password(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(user_id): Path<UserId>,
+    Json(req): Json<ResetPasswordRequest>,
+) -> Result<AdminActionResponse, ApiError> {
+    let target = get_target(&state, user_id, &trace_id).await?;
+
+    let mut errors = req.password.validate();
+    if target.is_bot {
+        errors.push("Bots can't sign in with a password".to_owned());
+    }
+
+    if !errors.is_empty() {
+        return Err(ApiError::Validation {
+            fields: HashMap::from([("password".to_owned(), errors)]),
+            trace_id,
+        });
+    }
+
+    let hash = hash_password(&state.random, &state.hasher, &req.password, &trace_id).await?;
+    if let Err(err) = state.users.update_password(user_id, hash).await {
+        tracing::error!("failed to reset password: {err}");
+        return Err(ApiError::Unknown { trace_id });
+    }
+
+    match state.sessions.remove_user_sessions(user_id).await {
+        Ok(removed) => session::close_streams(&state.events, user_id, removed),
+        Err(err) => {
+            tracing::error!("failed to revoke sessions: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    }
+
+    record_action(&state, &auth, AdminActionKind::ResetPassword, &target, &trace_id).await?;
+    Ok(AdminActionResponse)
+}
+
+/// Delete user
+///
+/// Deletes the account like the user would, bots owned by the user are deleted as well.
+#[utoipa::path(
+    delete,
+    path = "/admin/users/{id}",
+    tag = "admin",
+    params(
+        ("id" = UserId, Path, description = "User id")
+    ),
+    responses(
+        (status = NO_CONTENT, description = "User deleted", body = AdminActionResponse),
+        (status = BAD_REQUEST, description = "Deleting yourself", body = ApiError, example = json!({"type": "Validation", "fields": {"id": ["Delete your own account from the account settings"]}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Not an administrator", body = ApiError),
+        (status = NOT_FOUND, description = "User not found", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn delete_user(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(user_id): Path<UserId>,
+) -> Result<AdminActionResponse, ApiError> {
+    check_not_self(&auth, user_id, "Delete your own account from the account settings", &trace_id)?;
+    let target = get_target(&state, user_id, &trace_id).await?;
+
+    if let Err(err) = state.users.delete_user(user_id).await {
+        tracing::error!("failed to delete user: {err}");
+        return Err(ApiError::Unknown { trace_id });
+    }
+
+    state.events.remove(&user_id);
+    export::discard_export(&state, user_id).await;
+    record_action(&state, &auth, AdminActionKind::DeleteUser, &target, &trace_id).await?;
+    Ok(AdminActionResponse)
+}
+
+/// Get audit log
+#[utoipa::path(
+    get,
+    path = "/admin/actions",
+    tag = "admin",
+    params(
+        ("before" = Option<i32>, Query, description = "Id of the oldest action of the previous page"),
+        ("limit" = Option<i64>, Query, description = "Number of actions to return, max 100")
+    ),
+    responses(
+        (status = OK, description = "Administrator actions, most recent first", body = GetAdminActionsResponse),
+        (status = FORBIDDEN, description = "Not an administrator", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn get_actions(
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<GetAdminActionsQuery>,
+) -> Result<GetAdminActionsResponse, ApiError> {
+    let limit = params.limit.clamp(1, MAX_ADMIN_PAGE_SIZE);
+    let mut actions = match state.admin.get_actions(params.before, limit + 1).await {
+        Ok(actions) => actions,
+        Err(err) => {
+            tracing::error!("failed to get admin actions: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    };
+
+    let has_more = actions.len() > limit as usize;
+    actions.truncate(limit as usize);
+
+    Ok(GetAdminActionsResponse { actions, has_more })
+}
+
+/// Grants the administrator role to the comma separated usernames of `ADMIN_USERNAMES`.
+///
+/// Usernames that do not exist yet are skipped, so they get the role on the next start after signing up.
+pub async fn grant_configured_admins(state: &AppState) {
+    let Ok(usernames) = std::env::var("ADMIN_USERNAMES") else {
+        return;
+    };
+
+    for username in usernames.split(',').map(str::trim).filter(|username| !username.is_empty()) {
+        match state.admin.set_admin(username, true).await {
+            Ok(user_id) => tracing::info!("user {user_id} ({username}) is an administrator"),
+            Err(RepositoryError::NotFound) => tracing::warn!("administrator {username} not found"),
+            Err(err) => tracing::error!("failed to grant administrator role to {username}: {err}"),
+        }
+    }
+}
+
+fn check_not_self(auth: &Auth, user_id: UserId, message: &str, trace_id: &TraceId) -> Result<(), ApiError> {
+    if auth.user.id != user_id {
+        return Ok(());
+    }
+
+    Err(ApiError::Validation {
+        fields: HashMap::from([("id".to_owned(), vec![message.to_owned()])]),
+        trace_id: trace_id.clone(),
+    })
+}
+
+async fn get_target(state: &AppState, user_id: UserId, trace_id: &TraceId) -> Result<AdminUser, ApiError> {
+    match state.admin.get_user(user_id).await {
+        Ok(user) => Ok(user),
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("user {user_id} not found");
+            Err(ApiError::NotFound {
+                trace_id: trace_id.clone(),
+            })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to get user: {err}");
+            Err(ApiError::Unknown {
+                trace_id: trace_id.clone(),
+            })
+        }
+    }
+}
+
+async fn record_action(
+    state: &AppState,
+    auth: &Auth,
+    action: AdminActionKind,
+    target: &AdminUser,
+    trace_id: &TraceId,
+) -> Result<(), ApiError> {
+    tracing::info!("admin {} did {} on user {}", auth.user.id, action.as_ref(), target.id);
+    if let Err(err) = state
+        .admin
+        .record_action(auth.user.id, action, target.id, &target.username)
+        .await
+    {
+        tracing::error!("failed to record admin action: {err}");
+        return Err(ApiError::Unknown {
+            trace_id: trace_id.clone(),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::OffsetDateTime;
+    use crate::{models::{sessions::SessionId, users::User}, services::auth::Credentials};
+
+    #[test]
+    fn test_check_not_self() {
+        let auth = Auth {
+            user: User {
+                id: UserId::new(1),
+                username: "admin".to_owned(),
+                password: String::new(),
+                is_bot: false,
+                is_admin: true,
+                suspended_at: None,
+                created_at: OffsetDateTime::now_utc(),
+            },
+            credentials: Credentials::Session {
+                uid: "uid".to_owned(),
+                id: SessionId::new(1),
+                external_auth_at: None,
+            },
+        };
+        let trace_id = TraceId::new();
+
+        assert!(check_not_self(&auth, UserId::new(2), "", &trace_id).is_ok());
+        assert!(matches!(
+            check_not_self(&auth, UserId::new(1), "", &trace_id),
+            Err(ApiError::Validation { .. })
+        ));
+    }
+}
@@ -0,0 +1,192 @@
+use std::{collections::HashMap, sync::Arc};
+use axum::{
+    Extension,
+    extract::{Path, State},
+};
+use crate::{
+    AppState,
+    error::{ApiError, RepositoryError},
+    repositories::blocks::BlocksRepository,
+    services::{auth::Auth, trace::TraceId},
+    models::{
+        blocks::GetBlockedUsersResponse,
+        users::{UpdateAccountResponse, UserId},
+    },
+};
+
+/// Get blocked users
+#[utoipa::path(
+    get,
+    path = "/account/blocks",
+    tag = "blocks",
+    responses(
+        (status = OK, description = "Blocked users, most recent first", body = GetBlockedUsersResponse),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn get_blocked_users(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<GetBlockedUsersResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    match state.blocks.get_blocked_users(auth.user.id).await {
+        Ok(users) => Ok(GetBlockedUsersResponse(users)),
+        Err(err) => {
+            tracing::error!("failed to get blocked users: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Block user
+///
+/// Blocked users cannot add you to chats, their messages in shared chats are flagged,
+/// they are left out of your search results and you get no events about their messages.
+#[utoipa::path(
+    put,
+    path = "/account/blocks/{user_id}",
+    tag = "blocks",
+    params(
+        ("user_id" = UserId, Path, description = "User to block")
+    ),
+    responses(
+        (status = NO_CONTENT, description = "User blocked", body = UpdateAccountResponse),
+        (status = BAD_REQUEST, description = "Blocking yourself", body = ApiError, example = json!({"type": "Validation", "fields": {"user_id": ["You cannot block yourself"]}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError),
+        (status = NOT_FOUND, description = "User not found", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn block_user(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(user_id): Path<UserId>,
+) -> Result<UpdateAccountResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    if user_id == auth.user.id {
+        return Err(ApiError::Validation {
+            fields: HashMap::from([("user_id".to_owned(), vec!["You cannot block yourself".to_owned()])]),
+            trace_id,
+        });
+    }
+
+    match state.blocks.block_user(auth.user.id, user_id).await {
+        Ok(_) => {
+            tracing::info!("user {} blocked user {user_id}", auth.user.id);
+            Ok(UpdateAccountResponse)
+        }
+
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("user {user_id} not found");
+            Err(ApiError::NotFound { trace_id })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to block user: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Unblock user
+#[utoipa::path(
+    delete,
+    path = "/account/blocks/{user_id}",
+    tag = "blocks",
+    params(
+        ("user_id" = UserId, Path, description = "User to unblock")
+    ),
+    responses(
+        (status = NO_CONTENT, description = "User unblocked", body = UpdateAccountResponse),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError),
+        (status = NOT_FOUND, description = "User is not blocked", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn unblock_user(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(user_id): Path<UserId>,
+) -> Result<UpdateAccountResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    match state.blocks.unblock_user(auth.user.id, user_id).await {
+        Ok(_) => {
+            tracing::info!("user {} unblocked user {user_id}", auth.user.id);
+            Ok(UpdateAccountResponse)
+        }
+
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("user {user_id} is not blocked by {}", auth.user.id);
+            Err(ApiError::NotFound { trace_id })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to unblock user: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Fails when one of the users blocked the caller, so nobody is added to a chat against their will.
+pub(crate) async fn check_not_blocked(
+    blocks: &dyn BlocksRepository,
+    user_id: UserId,
+    users_ids: &[UserId],
+    trace_id: &TraceId,
+) -> Result<(), ApiError> {
+    match blocks.get_blockers(user_id, users_ids).await {
+        Ok(blockers) if blockers.is_empty() => Ok(()),
+        Ok(blockers) => {
+            tracing::warn!("user {user_id} is blocked by {blockers:?}");
+            Err(ApiError::Validation {
+                fields: HashMap::from([(
+                    "users_ids".to_owned(),
+                    vec!["Some users cannot be added to this chat".to_owned()],
+                )]),
+                trace_id: trace_id.clone(),
+            })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to get blockers: {err}");
+            Err(ApiError::Unknown {
+                trace_id: trace_id.clone(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::blocks::MockBlocksRepository;
+
+    #[tokio::test]
+    async fn test_check_not_blocked() {
+        let mut blocks = MockBlocksRepository::new();
+        blocks
+            .expect_get_blockers()
+            .withf(|blocked, users| *blocked == UserId::new(1) && users == [UserId::new(2), UserId::new(3)])
+            .returning(|_, _| Ok(vec![UserId::new(3)]));
+        blocks
+            .expect_get_blockers()
+            .returning(|_, _| Ok(Vec::new()));
+
+        let trace_id = TraceId::new();
+        let result = check_not_blocked(&blocks, UserId::new(1), &[UserId::new(2), UserId::new(3)], &trace_id).await;
+        assert!(matches!(result, Err(ApiError::Validation { .. })));
+
+        let result = check_not_blocked(&blocks, UserId::new(1), &[UserId::new(2)], &trace_id).await;
+        assert!(result.is_ok());
+    }
+}
@@ -0,0 +1,237 @@
+use std::{collections::HashMap, sync::Arc};
+use axum::{
+    Json,
+    Extension,
+    extract::{Path, State},
+};
+use crate::{
+    AppState,
+    error::{ApiError, RepositoryError},
+    services::{
+        auth::{Auth, hash_api_token},
+        trace::TraceId,
+    },
+    models::{
+        users::UserId,
+        tokens::API_TOKEN_PREFIX,
+        bots::{
+            NewBotRequest,
+            NewBotResponse,
+            GetBotsResponse,
+            RemoveBotResponse,
+        },
+    },
+};
+
+/// Get bots owned by the current user
+#[utoipa::path(
+    get,
+    path = "/bots",
+    tag = "bots",
+    responses(
+        (status = OK, description = "Owned bots", body = GetBotsResponse),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError, example = json!({"type": "Forbidden", "trace_id": "aa23dcd356c"})),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError, example = json!({"type": "Unknown", "trace_id": "aa23dcd356c"}))
+    ),
+    security(("auth" = []))
+)]
+pub async fn get_bots(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<GetBotsResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    match state.bots.get_owner_bots(auth.user.id).await {
+        Ok(bots) => Ok(GetBotsResponse(bots)),
+        Err(err) => {
+            tracing::error!("failed to get user bots: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Create bot
+///
+/// Bots can't log in with a password, they authenticate with the returned API token.
+/// When a webhook url is set, message and chat events of the bot chats are POSTed to it
+/// and signed with `X-Webhook-Signature: sha256=HMAC(webhook_secret, "{X-Webhook-Timestamp}.{body}")`.
+#[utoipa::path(
+    post,
+    path = "/bots",
+    tag = "bots",
+    request_body = NewBotRequest,
+    responses(
+        (status = CREATED, description = "Bot created", body = NewBotResponse),
+        (status = BAD_REQUEST, description = "Validation error", example = json!({"type": "Validation", "fields": {"webhook_url": ["Webhook url is invalid"]}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError, example = json!({"type": "Forbidden", "trace_id": "aa23dcd356c"})),
+        (status = CONFLICT, description = "Username is taken", body = ApiError, example = json!({"type": "Conflict", "trace_id": "aa23dcd356c"})),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError, example = json!({"type": "Unknown", "trace_id": "aa23dcd356c"}))
+    ),
+    security(("auth" = []))
+)]
+pub async fn new_bot(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<NewBotRequest>,
+) -> Result<NewBotResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    let errors = validate_bot(&req, state.webhooks.allows_insecure_hosts());
+    if !errors.is_empty() {
+        return Err(ApiError::Validation {
+            fields: errors,
+            trace_id,
+        });
+    }
+
+    let (token, webhook_secret) = {
+        let mut random = state.random.lock().await;
+        (
+            format!("{API_TOKEN_PREFIX}{}", random.get_api_token()),
+            random.get_webhook_secret(),
+        )
+    };
+
+    let result = state
+        .bots
+        .create_bot(
+            auth.user.id,
+            req.username.trim(),
+            req.webhook_url.map(|url| url.as_ref().to_owned()),
+            &webhook_secret,
+            &hash_api_token(&token),
+        )
+        .await;
+
+    match result {
+        Ok(bot) => {
+            tracing::info!("bot {} created by user {}", bot.id, auth.user.id);
+            Ok(NewBotResponse {
+                token,
+                webhook_secret,
+                bot,
+            })
+        }
+
+        Err(RepositoryError::Conflict) => {
+            tracing::warn!("user {} already exists", *req.username);
+            Err(ApiError::Conflict { trace_id })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to create bot: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Remove bot
+#[utoipa::path(
+    delete,
+    path = "/bots/{bot_id}",
+    tag = "bots",
+    params(
+        ("bot_id" = UserId, Path, description = "Bot user id")
+    ),
+    responses(
+        (status = NO_CONTENT, description = "Bot removed", body = RemoveBotResponse),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError, example = json!({"type": "Forbidden", "trace_id": "aa23dcd356c"})),
+        (status = NOT_FOUND, description = "Bot not found", body = ApiError, example = json!({"type": "NotFound", "trace_id": "aa23dcd356c"})),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError, example = json!({"type": "Unknown", "trace_id": "aa23dcd356c"}))
+    ),
+    security(("auth" = []))
+)]
+pub async fn remove_bot(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(bot_id): Path<UserId>,
+) -> Result<RemoveBotResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    match state.bots.remove_bot(auth.user.id, bot_id).await {
+        Ok(_) => {
+            tracing::info!("bot {bot_id} of user {} removed", auth.user.id);
+            Ok(RemoveBotResponse)
+        }
+
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("bot {bot_id} of user {} not found", auth.user.id);
+            Err(ApiError::NotFound { trace_id })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to remove bot: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+fn validate_bot(req: &NewBotRequest, allow_insecure_hosts: bool) -> HashMap<String, Vec<String>> {
+    let mut errors = HashMap::new();
+    let username_errors = req.username.validate();
+    if !username_errors.is_empty() {
+        errors.insert("username".to_owned(), username_errors);
+    }
+
+    if let Some(webhook_url) = &req.webhook_url {
+        let url_errors = webhook_url.validate(allow_insecure_hosts);
+        if !url_errors.is_empty() {
+            errors.insert("webhook_url".to_owned(), url_errors);
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{bots::WebhookUrl, users::Username};
+
+    #[test]
+    fn test_validate_bot_ok() {
+        let req = NewBotRequest {
+            username: Username::new("standup_bot"),
+            webhook_url: Some(WebhookUrl::new("https://example.com/hooks/standup")),
+        };
+
+        assert!(validate_bot(&req, false).is_empty());
+    }
+
+    #[test]
+    fn test_validate_bot_invalid_url() {
+        for url in ["example.com", "ftp://example.com/hook", "https://"] {
+            let req = NewBotRequest {
+                username: Username::new("standup_bot"),
+                webhook_url: Some(WebhookUrl::new(url)),
+            };
+
+            assert!(validate_bot(&req, false).contains_key("webhook_url"), "{url}");
+        }
+    }
+
+    #[test]
+    fn test_validate_bot_internal_url() {
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://localhost/hook",
+            "http://api.localhost./hook",
+            "http://10.0.0.5/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/hook",
+            "http://[::ffff:192.168.0.1]/hook",
+            "http://0x7f000001/hook",
+        ] {
+            let req = NewBotRequest {
+                username: Username::new("standup_bot"),
+                webhook_url: Some(WebhookUrl::new(url)),
+            };
+
+            assert!(validate_bot(&req, false).contains_key("webhook_url"), "{url}");
+            assert!(validate_bot(&req, true).is_empty(), "{url}");
+        }
+    }
+}
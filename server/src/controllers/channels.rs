@@ -0,0 +1,202 @@
+use std::{collections::HashMap, sync::Arc};
+use axum::{
+    Extension,
+    extract::{Path, Query, State},
+};
+use crate::{
+    AppState,
+    error::{ApiError, RepositoryError},
+    controllers::members::notify_members_changed,
+    services::{auth::Auth, trace::TraceId},
+    models::{
+        tokens::Scope,
+        chats::{
+            CHANNEL_QUERY_MAX_LENGTH,
+            MAX_CHANNELS_PAGE_SIZE,
+            Chat,
+            ChatId,
+            ChatVisibility,
+            GetChannelsQuery,
+            GetChannelsResponse,
+        },
+        invites::JoinChatResponse,
+        messages::{GetMessagesParams, GetMessagesResponse, MessageId},
+    },
+};
+
+/// Most recent messages shown to users who did not join the channel.
+const MAX_PREVIEW_MESSAGES: i64 = 50;
+
+/// Browse channels
+///
+/// Lists public chats, newest first, optionally filtered by title or description.
+#[utoipa::path(
+    get,
+    path = "/channels",
+    tag = "channels",
+    params(
+        ("query" = Option<String>, Query, description = "Part of the title or description"),
+        ("before" = Option<ChatId>, Query, description = "Last channel id of the previous page"),
+        ("limit" = Option<i64>, Query, description = "Page size, 20 by default and at most 100")
+    ),
+    responses(
+        (status = OK, description = "Channels", body = GetChannelsResponse),
+        (status = BAD_REQUEST, description = "Query too long", body = ApiError),
+        (status = FORBIDDEN, description = "Token lacks the read-messages scope", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["read-messages"]))
+)]
+pub async fn get_channels(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<GetChannelsQuery>,
+) -> Result<GetChannelsResponse, ApiError> {
+    auth.require_scope(Scope::ReadMessages, &trace_id)?;
+
+    let query = params
+        .query
+        .map(|query| query.trim().to_owned())
+        .filter(|query| !query.is_empty());
+
+    if query.as_ref().is_some_and(|query| query.chars().count() > CHANNEL_QUERY_MAX_LENGTH) {
+        return Err(ApiError::Validation {
+            fields: HashMap::from([(
+                "query".to_owned(),
+                vec![format!("Query must be at most {CHANNEL_QUERY_MAX_LENGTH} characters")],
+            )]),
+            trace_id,
+        });
+    }
+
+    let limit = params.limit.clamp(1, MAX_CHANNELS_PAGE_SIZE);
+    let mut channels = state
+        .chats
+        .get_public_chats(auth.user.id, query, params.before, limit + 1)
+        .await
+        .map_err(|err| {
+            tracing::error!("failed to get channels: {err}");
+            ApiError::Unknown { trace_id }
+        })?;
+
+    let has_more = channels.len() > limit as usize;
+    channels.truncate(limit as usize);
+
+    Ok(GetChannelsResponse { channels, has_more })
+}
+
+/// Join channel
+///
+/// Members are notified with a `ChatMembers` event, use `/chats/{chat_id}/leave` to leave.
+#[utoipa::path(
+    post,
+    path = "/channels/{chat_id}/join",
+    tag = "channels",
+    params(
+        ("chat_id" = ChatId, Path, description = "Chat id")
+    ),
+    responses(
+        (status = OK, description = "Joined channel, also when already a member", body = Chat),
+        (status = FORBIDDEN, description = "Token lacks the manage-chats scope", body = ApiError),
+        (status = NOT_FOUND, description = "No public chat with this id", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["manage-chats"]))
+)]
+pub async fn join_channel(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<ChatId>,
+) -> Result<JoinChatResponse, ApiError> {
+    auth.require_scope(Scope::ManageChats, &trace_id)?;
+
+    let joined = match state.chats.join_public_chat(chat_id, auth.user.id).await {
+        Ok(joined) => joined,
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("user {} tried to join chat {chat_id}, which is not public", auth.user.id);
+            return Err(ApiError::NotFound { trace_id });
+        }
+
+        Err(err) => {
+            tracing::error!("failed to join channel: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    };
+
+    let chat = get_chat(&state, chat_id, &auth, &trace_id).await?;
+    if joined {
+        tracing::info!("user {} joined channel {chat_id}", auth.user.id);
+        notify_members_changed(&state, chat_id, &chat.title, &chat.users_ids, vec![auth.user.id], Vec::new()).await;
+    }
+
+    Ok(JoinChatResponse(chat))
+}
+
+/// Preview channel messages
+///
+/// Read-only access to the recent messages of a public chat, without joining it.
+#[utoipa::path(
+    get,
+    path = "/channels/{chat_id}/messages",
+    tag = "channels",
+    params(
+        ("chat_id" = ChatId, Path, description = "Chat id"),
+        ("limit" = i64, Query, description = "Number of messages to return, max 50"),
+        ("last_message_id" = Option<MessageId>, Query, description = "Last message id to return")
+    ),
+    responses(
+        (status = OK, description = "Messages", body = GetMessagesResponse),
+        (status = FORBIDDEN, description = "Token lacks the read-messages scope", body = ApiError),
+        (status = NOT_FOUND, description = "No public chat with this id", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["read-messages"]))
+)]
+pub async fn get_channel_messages(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<ChatId>,
+    Query(params): Query<GetMessagesParams>,
+) -> Result<GetMessagesResponse, ApiError> {
+    auth.require_scope(Scope::ReadMessages, &trace_id)?;
+
+    match state.chats.get_chat(chat_id, auth.user.id).await {
+        Ok(chat) if chat.visibility == ChatVisibility::Public => {}
+        Ok(_) | Err(RepositoryError::NotFound) => {
+            tracing::warn!("user {} tried to preview chat {chat_id}, which is not public", auth.user.id);
+            return Err(ApiError::NotFound { trace_id });
+        }
+
+        Err(err) => {
+            tracing::error!("failed to get chat: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    }
+
+    let limit = params.limit.clamp(1, MAX_PREVIEW_MESSAGES);
+    let mut messages = state
+        .messages
+        .get_messages(chat_id, auth.user.id, limit + 1, params.last_message_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("failed to get messages: {err}");
+            ApiError::Unknown { trace_id }
+        })?;
+
+    let has_more = messages.len() > limit as usize;
+    messages.truncate(limit as usize);
+
+    Ok(GetMessagesResponse { messages, has_more })
+}
+
+async fn get_chat(state: &AppState, chat_id: ChatId, auth: &Auth, trace_id: &TraceId) -> Result<Chat, ApiError> {
+    state.chats.get_chat(chat_id, auth.user.id).await.map_err(|err| {
+        tracing::error!("failed to get chat: {err}");
+        ApiError::Unknown {
+            trace_id: trace_id.clone(),
+        }
+    })
+}
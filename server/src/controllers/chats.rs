@@ -5,53 +5,94 @@ use std::{
 use axum::{
     Json,
     Extension,
-    extract::{State, Path},
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{HeaderMap, header},
 };
 use crate::{
     AppState,
-    error::ApiError,
+    error::{ApiError, RepositoryError},
+    controllers::{blocks, members, profiles::validate_image},
+    repositories::chats::ChatsRepository,
     services::{
         auth::Auth,
         trace::TraceId,
     },
     models::{
         users::UserId,
+        tokens::Scope,
+        profiles::GetAvatarResponse,
         events::{
             SseEvent,
             ChatEvent,
             SseEventType,
+            ChatUpdatedEvent,
         },
         chats::{
+            MAX_CHATS_PAGE_SIZE,
+            Chat,
+            ChatCursor,
             ChatId,
+            ChatPermission,
+            ChatRole,
             ChatTitle,
+            DirectChatResponse,
             NewChatRequest,
             NewChatResponse,
+            GetChatsQuery,
             GetChatsResponse,
             RemoveChatResponse,
+            UpdateChatRequest,
+            UpdateChatResponse,
+            parse_if_match,
         },
     },
 };
 
 /// Get user chats
+///
+/// Chats with their latest message and unread count, most recently active first.
 #[utoipa::path(
     get,
     path = "/chats",
     tag = "chats",
+    params(
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+        ("limit" = Option<i64>, Query, description = "Page size, 50 by default and at most 100")
+    ),
     responses(
         (status = OK, description = "User chats", body = GetChatsResponse),
+        (status = BAD_REQUEST, description = "Invalid cursor", body = ApiError),
+        (status = FORBIDDEN, description = "Token lacks the read-messages scope", body = ApiError),
         (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError, example = json!({"type": "Internal", "trace_id": "aa23dcd356c"}))
     ),
-    security(("auth" = []))
+    security(("auth" = []), ("token" = ["read-messages"]))
 )]
 pub async fn get_chats(
     Extension(auth): Extension<Arc<Auth>>,
     Extension(trace_id): Extension<TraceId>,
     State(state): State<Arc<AppState>>,
+    Query(params): Query<GetChatsQuery>,
 ) -> Result<GetChatsResponse, ApiError> {
+    auth.require_scope(Scope::ReadMessages, &trace_id)?;
+
+    let cursor = match params.cursor.as_deref().map(str::parse::<ChatCursor>) {
+        Some(Ok(cursor)) => Some(cursor),
+        Some(Err(_)) => {
+            return Err(ApiError::Validation {
+                fields: HashMap::from([("cursor".to_owned(), vec!["Invalid cursor".to_owned()])]),
+                trace_id,
+            });
+        }
+
+        None => None,
+    };
+
     tracing::trace!("getting chats for user {}", auth.user.id);
-    let chats = match state.chats.get_user_chats(auth.user.id).await {
+    let limit = params.limit.clamp(1, MAX_CHATS_PAGE_SIZE);
+    let mut chats = match state.chats.get_chat_list(auth.user.id, cursor, limit + 1).await {
         Ok(chats) => {
-            tracing::trace!("user {} has {} chats", auth.user.id, chats.len());
+            tracing::trace!("user {} got {} chats", auth.user.id, chats.len());
             chats
         }
 
@@ -61,7 +102,14 @@ pub async fn get_chats(
         }
     };
 
-    Ok(GetChatsResponse(chats))
+    let next_cursor = if chats.len() > limit as usize {
+        chats.truncate(limit as usize);
+        chats.last().map(|chat| chat.cursor().to_string())
+    } else {
+        None
+    };
+
+    Ok(GetChatsResponse { chats, next_cursor })
 }
 
 /// Create new chat
@@ -72,10 +120,11 @@ pub async fn get_chats(
     request_body = NewChatRequest,
     responses(
         (status = OK, description = "Chat created", body = NewChatResponse),
-        (status = BAD_REQUEST, description = "Validation error", example = json!({"type": "Validation", "fields": {"title": "Chat title is required"}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Token lacks the manage-chats scope", body = ApiError),
+        (status = BAD_REQUEST, description = "Validation error, also when a member blocked you", example = json!({"type": "Validation", "fields": {"title": "Chat title is required"}, "trace_id": "aa23dcd356c"})),
         (status = INTERNAL_SERVER_ERROR, description = "Internal server error", example = json!({"type": "Internal", "trace_id": "aa23dcd356c"}))
     ),
-    security(("auth" = []))
+    security(("auth" = []), ("token" = ["manage-chats"]))
 )]
 pub async fn new_chat(
     Extension(auth): Extension<Arc<Auth>>,
@@ -83,6 +132,8 @@ pub async fn new_chat(
     State(state): State<Arc<AppState>>,
     Json(chat): Json<NewChatRequest>,
 ) -> Result<NewChatResponse, ApiError> {
+    auth.require_scope(Scope::ManageChats, &trace_id)?;
+
     let errors = validate_chat(&chat.title);
     if !errors.is_empty() {
         return Err(ApiError::Validation {
@@ -99,7 +150,9 @@ pub async fn new_chat(
         vec![auth.user.id]
     };
 
-    let chat_id = match state.chats.create_chat(&chat.title, &users_ids).await {
+    blocks::check_not_blocked(&*state.blocks, auth.user.id, &users_ids, &trace_id).await?;
+
+    let chat_id = match state.chats.create_chat(&chat.title, &users_ids, auth.user.id, chat.visibility).await {
         Ok(id) => {
             tracing::trace!("chat {id} created");
             id
@@ -111,28 +164,106 @@ pub async fn new_chat(
         }
     };
 
-    for member in &users_ids {
-        if *member == auth.user.id {
-            continue;
-        }
+    let event = SseEvent::new(
+        SseEventType::Chat,
+        ChatEvent {
+            title: chat.title.clone(),
+            users_ids: users_ids.clone(),
+            chat_id,
+        },
+    );
+
+    let recipients = users_ids
+        .iter()
+        .copied()
+        .filter(|member| *member != auth.user.id)
+        .collect::<Vec<_>>();
 
+    for member in &recipients {
         if let Some(member) = state.events.get(member)
-            && let Err(err) = member.send(SseEvent::new(
-                SseEventType::Chat,
-                ChatEvent {
-                    title: ChatTitle::new(chat.title.clone()),
-                    users_ids: users_ids.clone(),
-                    chat_id,
-                },
-            )) {
+            && let Err(err) = member.send(event.clone()) {
                 tracing::error!("failed to send event: {err}");
             }
     }
 
+    state.webhooks.notify_bots(&*state.bots, &recipients, &event).await;
+
     Ok(NewChatResponse::new(chat_id))
 }
 
+/// Open direct chat
+///
+/// Returns the one-to-one chat with the user, creating it on first use.
+/// The user is notified with a `Chat` event whenever they are added to it, including after leaving it.
+#[utoipa::path(
+    put,
+    path = "/chats/direct/{user_id}",
+    tag = "chats",
+    params(
+        ("user_id" = UserId, Path, description = "The other participant")
+    ),
+    responses(
+        (status = OK, description = "Existing direct chat", body = Chat),
+        (status = CREATED, description = "Direct chat created", body = Chat),
+        (status = BAD_REQUEST, description = "Chat with yourself, or the user blocked you", body = ApiError, example = json!({"type": "Validation", "fields": {"user_id": ["You cannot chat with yourself"]}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Token lacks the manage-chats scope", body = ApiError),
+        (status = NOT_FOUND, description = "User not found", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["manage-chats"]))
+)]
+pub async fn direct_chat(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(user_id): Path<UserId>,
+) -> Result<DirectChatResponse, ApiError> {
+    auth.require_scope(Scope::ManageChats, &trace_id)?;
+
+    if user_id == auth.user.id {
+        return Err(ApiError::Validation {
+            fields: HashMap::from([("user_id".to_owned(), vec!["You cannot chat with yourself".to_owned()])]),
+            trace_id,
+        });
+    }
+
+    blocks::check_not_blocked(&*state.blocks, auth.user.id, &[user_id], &trace_id).await?;
+
+    let (chat_id, added) = match state.chats.get_or_create_direct_chat(auth.user.id, user_id).await {
+        Ok(result) => result,
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("user {user_id} not found");
+            return Err(ApiError::NotFound { trace_id });
+        }
+
+        Err(err) => {
+            tracing::error!("failed to get direct chat: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    };
+
+    let chat = match state.chats.get_chat(chat_id, auth.user.id).await {
+        Ok(chat) => chat,
+        Err(err) => {
+            tracing::error!("failed to get chat: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    };
+
+    if added.contains(&user_id) {
+        tracing::info!("user {} opened direct chat {chat_id} with user {user_id}", auth.user.id);
+        notify_direct_chat(&state, chat_id, user_id).await;
+    }
+
+    Ok(DirectChatResponse {
+        created: added.len() == 2,
+        chat,
+    })
+}
+
 /// Remove chat
+///
+/// Only the owner can remove the chat, members are notified with a `ChatMembers` event.
 #[utoipa::path(
     delete,
     path = "/chats/{chat_id}",
@@ -142,19 +273,32 @@ pub async fn new_chat(
     ),
     responses(
         (status = NO_CONTENT, description = "Chat removed", body = RemoveChatResponse),
+        (status = FORBIDDEN, description = "Not the owner, or the token lacks the manage-chats scope", body = ApiError),
         (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError, example = json!({"type": "Internal", "trace_id": "aa23dcd356c"}))
     ),
-    security(("auth" = []))
+    security(("auth" = []), ("token" = ["manage-chats"]))
 )]
 pub async fn remove_chat(
-    Extension(_): Extension<Arc<Auth>>,
+    Extension(auth): Extension<Arc<Auth>>,
     Extension(trace_id): Extension<TraceId>,
     State(state): State<Arc<AppState>>,
     Path(chat_id): Path<ChatId>,
 ) -> Result<RemoveChatResponse, ApiError> {
+    auth.require_scope(Scope::ManageChats, &trace_id)?;
+    authorize(&*state.chats, auth.user.id, chat_id, ChatPermission::DeleteChat, &trace_id).await?;
+
+    let chat = match state.chats.get_chat(chat_id, auth.user.id).await {
+        Ok(chat) => chat,
+        Err(err) => {
+            tracing::error!("failed to get chat: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    };
+
     match state.chats.remove_chat(chat_id).await {
         Ok(_) => {
-            tracing::trace!("chat {} removed", chat_id);
+            tracing::info!("chat {chat_id} removed by user {}", auth.user.id);
+            members::notify_members_changed(&state, chat_id, &chat.title, &[], Vec::new(), chat.users_ids).await;
             Ok(RemoveChatResponse)
         }
         Err(err) => {
@@ -164,6 +308,309 @@ pub async fn remove_chat(
     }
 }
 
+/// Update chat
+///
+/// Changes the title, the description and the visibility, a `null` description clears it.
+/// Only the owner can make the chat public or private.
+/// Send the version from the `ETag` in `If-Match` to fail instead of overwriting someone else's change.
+#[utoipa::path(
+    patch,
+    path = "/chats/{chat_id}",
+    tag = "chats",
+    params(
+        ("chat_id" = ChatId, Path, description = "Chat id"),
+        ("If-Match" = Option<String>, Header, description = "Version the change is based on, as returned in the `ETag`")
+    ),
+    request_body = UpdateChatRequest,
+    responses(
+        (status = OK, description = "Chat updated", body = Chat),
+        (status = BAD_REQUEST, description = "Validation error", body = ApiError, example = json!({"type": "Validation", "fields": {"title": ["Title is too long"]}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Not allowed to rename the chat, or the token lacks the manage-chats scope", body = ApiError),
+        (status = PRECONDITION_FAILED, description = "The chat changed since the given version", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["manage-chats"]))
+)]
+pub async fn update_chat(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<ChatId>,
+    headers: HeaderMap,
+    Json(req): Json<UpdateChatRequest>,
+) -> Result<UpdateChatResponse, ApiError> {
+    auth.require_scope(Scope::ManageChats, &trace_id)?;
+    let role = authorize(&*state.chats, auth.user.id, chat_id, ChatPermission::Rename, &trace_id).await?;
+    if req.visibility.is_some() && !role.can(ChatPermission::ChangeVisibility) {
+        tracing::warn!("user {} is {} of chat {chat_id}, visibility change denied", auth.user.id, role.as_ref());
+        return Err(ApiError::Forbidden { trace_id });
+    }
+
+    let expected_version = expected_version(&headers, &trace_id)?;
+    let mut errors = req.title.as_ref().map(validate_chat).unwrap_or_default();
+    if let Some(Some(description)) = &req.description {
+        let description_errors = description.validate();
+        if !description_errors.is_empty() {
+            errors.insert("description".to_owned(), description_errors);
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(ApiError::Validation {
+            fields: errors,
+            trace_id,
+        });
+    }
+
+    let description = req
+        .description
+        .as_ref()
+        .map(|description| description.as_deref().filter(|description| !description.is_empty()));
+
+    let result = state
+        .chats
+        .update_chat(chat_id, req.title.as_ref(), description, req.visibility, expected_version)
+        .await;
+
+    let chat = updated_chat(result, chat_id, trace_id)?;
+    tracing::info!("chat {chat_id} updated by user {}", auth.user.id);
+    notify_chat_updated(&state, &chat).await;
+    Ok(UpdateChatResponse(chat))
+}
+
+/// Upload chat image
+///
+/// The request body is the raw PNG, JPEG, GIF or WebP image, up to 1 MiB.
+#[utoipa::path(
+    put,
+    path = "/chats/{chat_id}/image",
+    tag = "chats",
+    params(
+        ("chat_id" = ChatId, Path, description = "Chat id"),
+        ("If-Match" = Option<String>, Header, description = "Version the change is based on, as returned in the `ETag`")
+    ),
+    request_body(content = Vec<u8>, content_type = "image/*"),
+    responses(
+        (status = OK, description = "Image uploaded", body = Chat),
+        (status = BAD_REQUEST, description = "Unsupported or too large image", body = ApiError, example = json!({"type": "Validation", "fields": {"image": ["Unsupported image format"]}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Not allowed to edit the chat, or the token lacks the manage-chats scope", body = ApiError),
+        (status = PRECONDITION_FAILED, description = "The chat changed since the given version", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["manage-chats"]))
+)]
+pub async fn set_chat_image(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<ChatId>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<UpdateChatResponse, ApiError> {
+    auth.require_scope(Scope::ManageChats, &trace_id)?;
+    authorize(&*state.chats, auth.user.id, chat_id, ChatPermission::Rename, &trace_id).await?;
+
+    let expected_version = expected_version(&headers, &trace_id)?;
+    let format = validate_image(&body, "image", &trace_id)?;
+    let result = state
+        .chats
+        .set_chat_image(chat_id, format.content_type(), &body, expected_version)
+        .await;
+
+    let chat = updated_chat(result, chat_id, trace_id)?;
+    tracing::info!("image of chat {chat_id} uploaded by user {}", auth.user.id);
+    notify_chat_updated(&state, &chat).await;
+    Ok(UpdateChatResponse(chat))
+}
+
+/// Remove chat image
+#[utoipa::path(
+    delete,
+    path = "/chats/{chat_id}/image",
+    tag = "chats",
+    params(
+        ("chat_id" = ChatId, Path, description = "Chat id"),
+        ("If-Match" = Option<String>, Header, description = "Version the change is based on, as returned in the `ETag`")
+    ),
+    responses(
+        (status = OK, description = "Image removed", body = Chat),
+        (status = FORBIDDEN, description = "Not allowed to edit the chat, or the token lacks the manage-chats scope", body = ApiError),
+        (status = NOT_FOUND, description = "No image", body = ApiError),
+        (status = PRECONDITION_FAILED, description = "The chat changed since the given version", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["manage-chats"]))
+)]
+pub async fn remove_chat_image(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<ChatId>,
+    headers: HeaderMap,
+) -> Result<UpdateChatResponse, ApiError> {
+    auth.require_scope(Scope::ManageChats, &trace_id)?;
+    authorize(&*state.chats, auth.user.id, chat_id, ChatPermission::Rename, &trace_id).await?;
+
+    let expected_version = expected_version(&headers, &trace_id)?;
+    let result = state.chats.remove_chat_image(chat_id, expected_version).await;
+
+    let chat = updated_chat(result, chat_id, trace_id)?;
+    tracing::info!("image of chat {chat_id} removed by user {}", auth.user.id);
+    notify_chat_updated(&state, &chat).await;
+    Ok(UpdateChatResponse(chat))
+}
+
+/// Get chat image
+#[utoipa::path(
+    get,
+    path = "/chats/{chat_id}/image",
+    tag = "chats",
+    params(
+        ("chat_id" = ChatId, Path, description = "Chat id")
+    ),
+    responses(
+        (status = OK, description = "Chat image", content_type = "image/*"),
+        (status = FORBIDDEN, description = "Not a member of the chat", body = ApiError),
+        (status = NOT_FOUND, description = "No image", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["read-messages"]))
+)]
+pub async fn get_chat_image(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<ChatId>,
+) -> Result<GetAvatarResponse, ApiError> {
+    auth.require_scope(Scope::ReadMessages, &trace_id)?;
+    authorize(&*state.chats, auth.user.id, chat_id, ChatPermission::ReadMessages, &trace_id).await?;
+
+    match state.chats.get_chat_image(chat_id).await {
+        Ok(image) => Ok(GetAvatarResponse(image)),
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("chat {chat_id} has no image");
+            Err(ApiError::NotFound { trace_id })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to get chat image: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Sends the direct chat to the participant who was added to it, titled as they see it.
+async fn notify_direct_chat(state: &AppState, chat_id: ChatId, user_id: UserId) {
+    let chat = match state.chats.get_chat(chat_id, user_id).await {
+        Ok(chat) => chat,
+        Err(err) => {
+            tracing::error!("failed to get chat: {err}");
+            return;
+        }
+    };
+
+    let event = SseEvent::new(
+        SseEventType::Chat,
+        ChatEvent {
+            chat_id,
+            title: chat.title,
+            users_ids: chat.users_ids,
+        },
+    );
+
+    if let Some(member) = state.events.get(&user_id)
+        && let Err(err) = member.send(event.clone()) {
+            tracing::error!("failed to send event: {err}");
+        }
+
+    state.webhooks.notify_bots(&*state.bots, &[user_id], &event).await;
+}
+
+/// Sends the chat to its members after a change of its title, description or image.
+async fn notify_chat_updated(state: &AppState, chat: &Chat) {
+    let event = SseEvent::new(SseEventType::ChatUpdated, ChatUpdatedEvent { chat: chat.clone() });
+    for member in &chat.users_ids {
+        if let Some(member) = state.events.get(member)
+            && let Err(err) = member.send(event.clone()) {
+                tracing::error!("failed to send chat updated event: {err}");
+            }
+    }
+
+    state.webhooks.notify_bots(&*state.bots, &chat.users_ids, &event).await;
+}
+
+/// Version from the `If-Match` header, `None` when missing or `*`.
+fn expected_version(headers: &HeaderMap, trace_id: &TraceId) -> Result<Option<i32>, ApiError> {
+    let Some(value) = headers.get(header::IF_MATCH) else {
+        return Ok(None);
+    };
+
+    match value.to_str().ok().and_then(parse_if_match) {
+        Some(version) => Ok(version),
+        None => {
+            tracing::warn!("invalid If-Match header: {value:?}");
+            Err(ApiError::PreconditionFailed {
+                trace_id: trace_id.clone(),
+            })
+        }
+    }
+}
+
+fn updated_chat(
+    result: Result<Chat, RepositoryError>,
+    chat_id: ChatId,
+    trace_id: TraceId,
+) -> Result<Chat, ApiError> {
+    match result {
+        Ok(chat) => Ok(chat),
+        Err(RepositoryError::Conflict) => {
+            tracing::warn!("chat {chat_id} changed since the expected version");
+            Err(ApiError::PreconditionFailed { trace_id })
+        }
+
+        Err(RepositoryError::NotFound) => Err(ApiError::NotFound { trace_id }),
+        Err(err) => {
+            tracing::error!("failed to update chat {chat_id}: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Ensures the user is a member of the chat whose role grants `permission`, returns that role.
+///
+/// Non-members get `Forbidden` as well, so the existence of a chat is not revealed.
+pub(crate) async fn authorize(
+    chats: &dyn ChatsRepository,
+    user_id: UserId,
+    chat_id: ChatId,
+    permission: ChatPermission,
+    trace_id: &TraceId,
+) -> Result<ChatRole, ApiError> {
+    match chats.get_member_role(chat_id, user_id).await {
+        Ok(role) if role.can(permission) => Ok(role),
+        Ok(role) => {
+            tracing::warn!("user {user_id} is {} of chat {chat_id}, {} denied", role.as_ref(), permission.as_ref());
+            Err(ApiError::Forbidden {
+                trace_id: trace_id.clone(),
+            })
+        }
+
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("user {user_id} is not a member of chat {chat_id}");
+            Err(ApiError::Forbidden {
+                trace_id: trace_id.clone(),
+            })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to get member role: {err}");
+            Err(ApiError::Unknown {
+                trace_id: trace_id.clone(),
+            })
+        }
+    }
+}
+
 fn validate_chat(title: &ChatTitle) -> HashMap<String, Vec<String>> {
     let mut errors = HashMap::new();
     let title_errors = title.validate();
@@ -179,4 +626,53 @@ fn merge_ids(user_id: UserId, ids: Vec<UserId>) -> Vec<UserId> {
     users.push(user_id);
     users.extend(ids);
     users
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::chats::MockChatsRepository;
+
+    fn chats() -> MockChatsRepository {
+        let mut chats = MockChatsRepository::new();
+        chats
+            .expect_get_member_role()
+            .withf(|chat_id, user_id| *chat_id == ChatId::new(1) && *user_id == UserId::new(1))
+            .returning(|_, _| Ok(ChatRole::Owner));
+        chats
+            .expect_get_member_role()
+            .withf(|chat_id, user_id| *chat_id == ChatId::new(1) && *user_id == UserId::new(2))
+            .returning(|_, _| Ok(ChatRole::Member));
+        chats
+            .expect_get_member_role()
+            .returning(|_, _| Err(RepositoryError::NotFound));
+        chats
+    }
+
+    #[tokio::test]
+    async fn test_authorize_member() {
+        let chats = chats();
+        let trace_id = TraceId::new();
+
+        let role = authorize(&chats, UserId::new(2), ChatId::new(1), ChatPermission::SendMessages, &trace_id).await;
+        assert_eq!(role.ok(), Some(ChatRole::Member));
+
+        let role = authorize(&chats, UserId::new(2), ChatId::new(1), ChatPermission::DeleteChat, &trace_id).await;
+        assert!(matches!(role, Err(ApiError::Forbidden { .. })));
+
+        let role = authorize(&chats, UserId::new(1), ChatId::new(1), ChatPermission::DeleteChat, &trace_id).await;
+        assert_eq!(role.ok(), Some(ChatRole::Owner));
+    }
+
+    #[tokio::test]
+    async fn test_authorize_non_member() {
+        let chats = chats();
+        let trace_id = TraceId::new();
+
+        let role = authorize(&chats, UserId::new(3), ChatId::new(1), ChatPermission::ReadMessages, &trace_id).await;
+        assert!(matches!(role, Err(ApiError::Forbidden { .. })));
+
+        let role = authorize(&chats, UserId::new(1), ChatId::new(2), ChatPermission::ReadMessages, &trace_id).await;
+        assert!(matches!(role, Err(ApiError::Forbidden { .. })));
+    }
+}
@@ -1,14 +1,16 @@
 use crate::{
     AppState,
-    services::auth::Auth,
-    models::events::SseEvent,
+    error::ApiError,
+    controllers::presence::PresenceGuard,
+    services::{auth::Auth, trace::TraceId},
+    models::{events::SseEvent, tokens::Scope, users::UserId},
 };
 use axum::{
     Extension,
     extract::State,
     response::{Sse, sse::Event},
 };
-use std::sync::Arc;
+use std::{future::ready, sync::Arc};
 use tokio::sync::broadcast;
 use futures_util::{Stream, StreamExt};
 use tokio_stream::wrappers::BroadcastStream;
@@ -20,13 +22,17 @@ use tokio_stream::wrappers::BroadcastStream;
     tag = "events",
     responses(
         (status = 200, description = "OK", content_type = "text/event-stream"),
+        (status = FORBIDDEN, description = "Token lacks the read-messages scope", body = ApiError),
     ),
-    security(("auth" = []))
+    security(("auth" = []), ("token" = ["read-messages"]))
 )]
 pub async fn events(
     Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
     State(state): State<Arc<AppState>>,
-) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, ApiError> {
+    auth.require_scope(Scope::ReadMessages, &trace_id)?;
+
     let rx = state
         .events
         .entry(auth.user.id)
@@ -34,19 +40,61 @@ pub async fn events(
         .value()
         .subscribe();
 
-    let stream = BroadcastStream::new(rx).filter_map(|msg| async move {
-        match msg {
-            Ok(SseEvent { event_type, data }) => {
-                tracing::trace!("SSE event {} emitted", event_type.as_ref());
-                Some(Ok(Event::default().event(event_type.as_ref()).data(data)))
+    let session_id = auth.session_id();
+    let presence = PresenceGuard::connect(state.clone(), auth.user.id, session_id);
+    let stream = BroadcastStream::new(rx)
+        .take_while(move |msg| {
+            let revoked = matches!(
+                (msg, session_id),
+                (Ok(event), Some(id)) if event.closes.contains(&id)
+            );
+            if revoked {
+                tracing::trace!("session revoked, closing SSE stream");
             }
-            
-            Err(err) => {
-                tracing::error!("SSE error: {err}");
-                None
+
+            ready(!revoked)
+        })
+        .filter_map(|msg| async move {
+            match msg {
+                Ok(SseEvent { event_type, data, .. }) => {
+                    tracing::trace!("SSE event {} emitted", event_type.as_ref());
+                    Some(Ok(Event::default().event(event_type.as_ref()).data(data)))
+                }
+
+                Err(err) => {
+                    tracing::error!("SSE error: {err}");
+                    None
+                }
             }
+        })
+        // the stream is dropped with the connection, which ends the presence
+        .map(move |event| {
+            let _presence = &presence;
+            event
+        });
+
+    Ok(Sse::new(stream))
+}
+
+/// Sends an event about the user to their own streams and to everyone sharing a chat.
+///
+/// Failures are only logged, the change behind the event is already done.
+pub(crate) async fn notify_chat_peers(state: &AppState, user_id: UserId, event: SseEvent) {
+    let mut recipients = match state.chats.get_chat_peers(user_id).await {
+        Ok(peers) => peers,
+        Err(err) => {
+            tracing::error!("failed to get chat peers: {err}");
+            return;
         }
-    });
+    };
+
+    recipients.push(user_id);
+    for recipient in &recipients {
+        if let Some(recipient) = state.events.get(recipient)
+            && let Err(err) = recipient.send(event.clone()) {
+                tracing::trace!("no event streams for {}: {err}", event.event_type.as_ref());
+            }
+    }
 
-    Sse::new(stream)
+    state.webhooks.notify_bots(&*state.bots, &recipients, &event).await;
 }
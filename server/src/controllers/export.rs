@@ -0,0 +1,244 @@
+use std::sync::Arc;
+use axum::{
+    Extension,
+    extract::{Path, State},
+};
+use time::OffsetDateTime;
+use crate::{
+    AppState,
+    error::{ApiError, RepositoryError},
+    services::{auth::Auth, export::{create_archive, write_archive}, trace::TraceId},
+    models::{
+        export::{DownloadExportResponse, ExportData, ExportedAccount, GetExportResponse, NewExportResponse},
+        messages::Message,
+        users::{User, UserId},
+    },
+};
+
+/// Messages fetched per query while building an archive.
+const EXPORT_PAGE_SIZE: i64 = 1000;
+
+/// Request data export
+///
+/// Starts building a ZIP archive of the account, its sessions, chats and every message sent by the user.
+/// The archive is built in the background, poll the export to follow its progress.
+/// Requesting a new export discards the previous archive, unless one is still being built.
+#[utoipa::path(
+    post,
+    path = "/account/export",
+    tag = "export",
+    responses(
+        (status = ACCEPTED, description = "Export started or already in progress", body = GetExportResponse),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn new_export(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<NewExportResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    let token = state.random.lock().await.get_export_token();
+    let (export, started, replaced) = state
+        .exports
+        .start(auth.user.id, token.clone(), OffsetDateTime::now_utc());
+
+    if let Some(path) = replaced {
+        remove_archive(path).await;
+    }
+
+    if started {
+        tracing::info!("user {} requested a data export", auth.user.id);
+        tokio::spawn(build_export(state.clone(), auth.user.clone(), token));
+    }
+
+    Ok(NewExportResponse(export))
+}
+
+/// Get data export
+#[utoipa::path(
+    get,
+    path = "/account/export",
+    tag = "export",
+    responses(
+        (status = OK, description = "Latest data export", body = GetExportResponse),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError),
+        (status = NOT_FOUND, description = "No data export requested, or it was downloaded or expired", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn get_export(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<GetExportResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    state
+        .exports
+        .get(auth.user.id)
+        .ok_or(ApiError::NotFound { trace_id })
+}
+
+/// Download data export
+///
+/// The link works once and expires 24 hours after the archive is ready.
+#[utoipa::path(
+    get,
+    path = "/account/export/{token}",
+    tag = "export",
+    params(
+        ("token" = String, Path, description = "Download token of the export")
+    ),
+    responses(
+        (status = OK, description = "ZIP archive", content_type = "application/zip"),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError),
+        (status = NOT_FOUND, description = "Invalid, used or expired link", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn download_export(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> Result<DownloadExportResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    let Some(path) = state.exports.take(auth.user.id, &token, OffsetDateTime::now_utc()) else {
+        tracing::warn!("invalid export link used by user {}", auth.user.id);
+        return Err(ApiError::NotFound { trace_id });
+    };
+
+    let data = tokio::fs::read(&path).await;
+    remove_archive(path).await;
+    match data {
+        Ok(data) => {
+            tracing::info!("user {} downloaded their data export", auth.user.id);
+            Ok(DownloadExportResponse {
+                filename: format!("justice-{}.zip", auth.user.username),
+                data,
+            })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to read export archive: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Drops the export of a deleted account.
+pub(crate) async fn discard_export(state: &AppState, user_id: UserId) {
+    if let Some(path) = state.exports.remove(user_id) {
+        remove_archive(path).await;
+    }
+}
+
+async fn remove_archive(path: std::path::PathBuf) {
+    if let Err(err) = tokio::fs::remove_file(&path).await {
+        tracing::warn!("failed to remove export archive {}: {err}", path.display());
+    }
+}
+
+async fn build_export(state: Arc<AppState>, user: User, token: String) {
+    let user_id = user.id;
+    let data = match collect_export(&state, user, &token).await {
+        Ok(data) => data,
+        Err(err) => {
+            tracing::error!("failed to collect data export of user {user_id}: {err}");
+            state.exports.fail(user_id, &token);
+            return;
+        }
+    };
+
+    let path = state.exports.path(&token);
+    let archive_path = path.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let file = create_archive(&archive_path)?;
+        write_archive(std::io::BufWriter::new(file), &data).map_err(std::io::Error::other)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(())) => {
+            if state.exports.finish(user_id, &token, OffsetDateTime::now_utc()) {
+                tracing::info!("data export of user {user_id} is ready");
+            } else {
+                remove_archive(path).await;
+            }
+        }
+
+        Ok(Err(err)) => {
+            tracing::error!("failed to write data export of user {user_id}: {err}");
+            state.exports.fail(user_id, &token);
+            remove_archive(path).await;
+        }
+
+        Err(err) => {
+            tracing::error!("data export task of user {user_id} failed: {err}");
+            state.exports.fail(user_id, &token);
+        }
+    }
+}
+
+async fn collect_export(state: &AppState, user: User, token: &str) -> Result<ExportData, RepositoryError> {
+    let profile = match state.profiles.get_profile(user.id).await {
+        Ok(profile) => Some(profile),
+        Err(RepositoryError::NotFound) => None,
+        Err(err) => return Err(err),
+    };
+
+    let sessions = state.sessions.get_user_sessions(user.id).await?;
+    let chats = state.chats.get_user_chats(user.id).await?;
+    let total = state.messages.count_user_messages(user.id).await?;
+    state.exports.set_progress(user.id, token, 5);
+
+    let mut messages: Vec<Message> = Vec::with_capacity(total as usize);
+    loop {
+        let after = messages.last().map(|message| message.id);
+        let page = state.messages.get_user_messages(user.id, after, EXPORT_PAGE_SIZE).await?;
+        let done = (page.len() as i64) < EXPORT_PAGE_SIZE;
+        messages.extend(page);
+        state.exports.set_progress(user.id, token, progress(messages.len(), total));
+
+        if done {
+            break;
+        }
+    }
+
+    Ok(ExportData {
+        account: ExportedAccount::new(user, profile),
+        sessions: sessions.into_iter().map(Into::into).collect(),
+        chats,
+        messages,
+    })
+}
+
+/// Collecting messages takes the bulk of the export, from 5% to 95%.
+fn progress(fetched: usize, total: i64) -> u8 {
+    if total <= 0 {
+        return 95;
+    }
+
+    (5 + (fetched as i64).min(total) * 90 / total) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress() {
+        assert_eq!(progress(0, 0), 95);
+        assert_eq!(progress(0, 200), 5);
+        assert_eq!(progress(100, 200), 50);
+        assert_eq!(progress(200, 200), 95);
+        // messages sent while exporting
+        assert_eq!(progress(210, 200), 95);
+    }
+}
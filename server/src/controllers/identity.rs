@@ -0,0 +1,414 @@
+use std::sync::Arc;
+use axum::{
+    Extension,
+    extract::{Path, Query, State},
+    http::{HeaderMap, header},
+};
+use time::{Duration, OffsetDateTime};
+use crate::{
+    AppState,
+    controllers::users::create_session,
+    error::{ApiError, RepositoryError},
+    rand::RandomGenerator,
+    repositories::{identities::IdentitiesRepository, users::UsersRepository},
+    services::{
+        cookies::AUTH_STATE_COOKIE_NAME,
+        identity::{self, ExternalIdentity, IdentityProvider},
+        trace::TraceId,
+    },
+    models::{
+        sessions::ClientInfo,
+        users::UserId,
+        identity::{
+            AuthRequest,
+            ExternalCallbackParams,
+            ExternalCallbackResponse,
+            ExternalLoginResponse,
+            AUTH_REQUEST_LIFETIME,
+        },
+    },
+};
+
+/// Attempts at finding a free username for a provisioned account.
+const PROVISION_ATTEMPTS: usize = 5;
+
+/// Log in with an identity provider
+///
+/// Redirects the browser to the provider, which sends it back to the callback endpoint.
+#[utoipa::path(
+    get,
+    path = "/auth/{provider}/login",
+    tag = "identity",
+    params(("provider" = String, Path, description = "Identity provider name")),
+    responses(
+        (status = SEE_OTHER, description = "Redirect to the identity provider"),
+        (status = NOT_FOUND, description = "Unknown identity provider", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn external_login(
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(provider): Path<String>,
+) -> Result<ExternalLoginResponse, ApiError> {
+    let provider = get_provider(&state, &provider, &trace_id)?;
+
+    let request = {
+        let mut random = state.random.lock().await;
+        AuthRequest {
+            state: random.get_session_uid(),
+            provider: provider.name().to_owned(),
+            nonce: random.get_session_uid(),
+            code_verifier: random.get_session_uid(),
+            expires_at: OffsetDateTime::now_utc()
+                .saturating_add(Duration::seconds(AUTH_REQUEST_LIFETIME)),
+        }
+    };
+
+    if let Err(err) = state.identities.create_auth_request(&request).await {
+        tracing::error!("failed to store auth request: {err}");
+        return Err(ApiError::Unknown { trace_id });
+    }
+
+    tracing::info!("external login with {} started", provider.name());
+    Ok(ExternalLoginResponse {
+        location: provider.authorization_url(
+            &request.state,
+            &request.nonce,
+            &identity::code_challenge(&request.code_verifier),
+        ),
+        state: request.state,
+    })
+}
+
+/// Identity provider callback
+///
+/// Logs in the user linked to the external identity, creating one if the provider allows it,
+/// and redirects to the web app.
+#[utoipa::path(
+    get,
+    path = "/auth/{provider}/callback",
+    tag = "identity",
+    params(
+        ("provider" = String, Path, description = "Identity provider name"),
+        ExternalCallbackParams,
+    ),
+    responses(
+        (status = SEE_OTHER, description = "User logged in, redirect to the web app"),
+        (status = UNAUTHORIZED, description = "Login failed or was not started by this browser", body = ApiError),
+        (status = FORBIDDEN, description = "No account is linked to the identity, or the account is suspended", body = ApiError),
+        (status = NOT_FOUND, description = "Unknown identity provider", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn external_callback(
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(provider): Path<String>,
+    Query(params): Query<ExternalCallbackParams>,
+    headers: HeaderMap,
+    client: ClientInfo,
+) -> Result<ExternalCallbackResponse, ApiError> {
+    let provider = get_provider(&state, &provider, &trace_id)?;
+
+    if let Some(error) = &params.error {
+        tracing::warn!("external login with {} failed: {error}", provider.name());
+        return Err(ApiError::Unauthorized { trace_id });
+    }
+
+    let (Some(code), Some(auth_state)) = (&params.code, &params.state) else {
+        tracing::warn!("callback of {} without code or state", provider.name());
+        return Err(ApiError::Unauthorized { trace_id });
+    };
+
+    // the state must come back to the browser that started the login
+    if state_cookie(&headers).as_deref() != Some(auth_state.as_str()) {
+        tracing::warn!("external login state doesn't match the cookie");
+        return Err(ApiError::Unauthorized { trace_id });
+    }
+
+    let request = match state.identities.take_auth_request(auth_state).await {
+        Ok(request) => request,
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("unknown external login state");
+            return Err(ApiError::Unauthorized { trace_id });
+        }
+
+        Err(err) => {
+            tracing::error!("failed to get auth request: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    };
+
+    if request.provider != provider.name() || request.expires_at < OffsetDateTime::now_utc() {
+        tracing::warn!("external login state expired or issued for {}", request.provider);
+        return Err(ApiError::Unauthorized { trace_id });
+    }
+
+    let identity = match provider
+        .exchange_code(code, &request.code_verifier, &request.nonce)
+        .await
+    {
+        Ok(identity) => identity,
+        Err(err) => {
+            tracing::warn!("failed to exchange code with {}: {err}", provider.name());
+            return Err(ApiError::Unauthorized { trace_id });
+        }
+    };
+
+    let user_id = get_or_provision_user(
+        &state.random,
+        &*state.identities,
+        &*provider,
+        &identity,
+        &trace_id,
+    )
+    .await?;
+
+    check_not_suspended(&*state.users, user_id, &trace_id).await?;
+
+    // the login at the provider stands in for the password of accounts without one
+    let session = create_session(
+        &state.random,
+        &*state.sessions,
+        user_id,
+        &client,
+        Some(OffsetDateTime::now_utc()),
+        &trace_id,
+    )
+    .await?;
+
+    Ok(ExternalCallbackResponse { session })
+}
+
+fn get_provider(
+    state: &AppState,
+    name: &str,
+    trace_id: &TraceId,
+) -> Result<Arc<dyn IdentityProvider>, ApiError> {
+    state.identity_providers.get(name).ok_or_else(|| {
+        tracing::warn!("identity provider {name} not configured");
+        ApiError::NotFound {
+            trace_id: trace_id.clone(),
+        }
+    })
+}
+
+fn state_cookie(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|c| c.trim().parse::<cookie::Cookie>().ok())
+        .find(|c| c.name() == AUTH_STATE_COOKIE_NAME)
+        .map(|c| c.value().to_owned())
+}
+
+/// Refuses suspended users like the password login does, before any session is created.
+async fn check_not_suspended(
+    users: &dyn UsersRepository,
+    user_id: UserId,
+    trace_id: &TraceId,
+) -> Result<(), ApiError> {
+    let user = match users.get_user_by_id(&user_id).await {
+        Ok(user) => user,
+        Err(err) => {
+            tracing::error!("failed to get user: {err}");
+            return Err(ApiError::Unknown {
+                trace_id: trace_id.clone(),
+            });
+        }
+    };
+
+    if user.suspended_at.is_some() {
+        tracing::warn!("external login attempted for suspended user {}", user.username);
+        return Err(ApiError::Forbidden {
+            trace_id: trace_id.clone(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Finds the user linked to the identity or creates one when the provider allows it.
+async fn get_or_provision_user(
+    rand: &tokio::sync::Mutex<dyn RandomGenerator>,
+    identities: &dyn IdentitiesRepository,
+    provider: &dyn IdentityProvider,
+    identity: &ExternalIdentity,
+    trace_id: &TraceId,
+) -> Result<UserId, ApiError> {
+    for attempt in 0..PROVISION_ATTEMPTS {
+        match identities.get_linked_user(provider.name(), &identity.subject).await {
+            Ok(user_id) => {
+                tracing::info!("user {user_id} logged in with {}", provider.name());
+                return Ok(user_id);
+            }
+            Err(RepositoryError::NotFound) => {}
+            Err(err) => {
+                tracing::error!("failed to get linked user: {err}");
+                return Err(ApiError::Unknown {
+                    trace_id: trace_id.clone(),
+                });
+            }
+        }
+
+        if !provider.auto_provision() {
+            tracing::warn!("no user linked to {} subject {}", provider.name(), identity.subject);
+            return Err(ApiError::Forbidden {
+                trace_id: trace_id.clone(),
+            });
+        }
+
+        let suffix = match attempt {
+            0 => None,
+            _ => Some(rand.lock().await.get_salt()[..4].to_owned()),
+        };
+
+        let username = identity::username_candidate(identity, suffix.as_deref());
+        match identities
+            .create_linked_user(provider.name(), &identity.subject, &username)
+            .await
+        {
+            Ok(user_id) => {
+                tracing::info!("user {username} provisioned by {}", provider.name());
+                return Ok(user_id);
+            }
+            // either the username is taken or a parallel login linked the subject first
+            Err(RepositoryError::Conflict) => {
+                tracing::warn!("failed to provision user {username}, retrying");
+                continue;
+            }
+            Err(err) => {
+                tracing::error!("failed to provision user: {err}");
+                return Err(ApiError::Unknown {
+                    trace_id: trace_id.clone(),
+                });
+            }
+        }
+    }
+
+    tracing::error!("failed to find a free username for {} subject {}", provider.name(), identity.subject);
+    Err(ApiError::Conflict {
+        trace_id: trace_id.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::Mutex;
+    use crate::{
+        rand::MockRandomGenerator,
+        models::users::User,
+        repositories::{identities::MockIdentitiesRepository, users::MockUsersRepository},
+        services::identity::MockIdentityProvider,
+    };
+
+    fn provider(auto_provision: bool) -> MockIdentityProvider {
+        let mut provider = MockIdentityProvider::new();
+        provider.expect_name().return_const("oidc".to_owned());
+        provider.expect_auto_provision().return_const(auto_provision);
+        provider
+    }
+
+    fn identity() -> ExternalIdentity {
+        ExternalIdentity {
+            subject: "248289761001".to_owned(),
+            preferred_username: Some("alice".to_owned()),
+            email: None,
+        }
+    }
+
+    #[test]
+    fn test_state_cookie() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, "session=abc; auth_state=xyz".parse().unwrap());
+
+        assert_eq!(state_cookie(&headers).as_deref(), Some("xyz"));
+        assert_eq!(state_cookie(&HeaderMap::new()), None);
+    }
+
+    #[tokio::test]
+    async fn test_get_or_provision_user_linked() {
+        let mut identities = MockIdentitiesRepository::new();
+        identities
+            .expect_get_linked_user()
+            .withf(|provider, subject| provider == "oidc" && subject == "248289761001")
+            .returning(|_, _| Ok(UserId::new(7)));
+
+        let rand = Mutex::new(MockRandomGenerator::new());
+        let result = get_or_provision_user(&rand, &identities, &provider(false), &identity(), &TraceId::new()).await;
+
+        assert_eq!(result.unwrap(), UserId::new(7));
+    }
+
+    #[tokio::test]
+    async fn test_get_or_provision_user_not_linked() {
+        let mut identities = MockIdentitiesRepository::new();
+        identities
+            .expect_get_linked_user()
+            .returning(|_, _| Err(RepositoryError::NotFound));
+
+        let rand = Mutex::new(MockRandomGenerator::new());
+        let result = get_or_provision_user(&rand, &identities, &provider(false), &identity(), &TraceId::new()).await;
+
+        assert!(matches!(result, Err(ApiError::Forbidden { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_get_or_provision_user_retries_taken_username() {
+        let mut identities = MockIdentitiesRepository::new();
+        identities
+            .expect_get_linked_user()
+            .returning(|_, _| Err(RepositoryError::NotFound));
+        identities
+            .expect_create_linked_user()
+            .withf(|_, _, username| username == "alice")
+            .returning(|_, _, _| Err(RepositoryError::Conflict));
+        identities
+            .expect_create_linked_user()
+            .withf(|_, _, username| username == "alice-0a1b")
+            .returning(|_, _, _| Ok(UserId::new(8)));
+
+        let mut rand = MockRandomGenerator::new();
+        rand.expect_get_salt()
+            .returning(|| "0a1b2c3d4e5f60718293a4b5c6d7e8f9".to_owned());
+        let rand = Mutex::new(rand);
+        let result = get_or_provision_user(&rand, &identities, &provider(true), &identity(), &TraceId::new()).await;
+
+        assert_eq!(result.unwrap(), UserId::new(8));
+    }
+
+    #[tokio::test]
+    async fn test_check_not_suspended() {
+        fn user(id: i32, suspended_at: Option<OffsetDateTime>) -> User {
+            User {
+                id: UserId::new(id),
+                username: format!("alice{id}"),
+                password: String::new(),
+                is_bot: false,
+                is_admin: false,
+                suspended_at,
+                created_at: OffsetDateTime::now_utc(),
+            }
+        }
+
+        let mut users = MockUsersRepository::new();
+        users
+            .expect_get_user_by_id()
+            .withf(|id| *id == UserId::new(1))
+            .returning(|_| Ok(user(1, None)));
+        users
+            .expect_get_user_by_id()
+            .withf(|id| *id == UserId::new(2))
+            .returning(|_| Ok(user(2, Some(OffsetDateTime::now_utc()))));
+
+        assert!(check_not_suspended(&users, UserId::new(1), &TraceId::new()).await.is_ok());
+        assert!(matches!(
+            check_not_suspended(&users, UserId::new(2), &TraceId::new()).await,
+            Err(ApiError::Forbidden { .. })
+        ));
+    }
+}
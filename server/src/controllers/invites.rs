@@ -0,0 +1,317 @@
+use std::{collections::HashMap, sync::Arc};
+use axum::{
+    Json,
+    Extension,
+    extract::{Path, State},
+};
+use time::{Duration, OffsetDateTime};
+use crate::{
+    AppState,
+    error::{ApiError, RepositoryError},
+    controllers::{chats::authorize, members::notify_members_changed},
+    services::{auth::Auth, trace::TraceId},
+    models::{
+        tokens::Scope,
+        chats::{Chat, ChatId, ChatKind, ChatPermission},
+        invites::{
+            INVITE_MAX_USES,
+            ChatInvite,
+            GetInvitesResponse,
+            InvitePreview,
+            JoinChatResponse,
+            NewInviteRequest,
+            NewInviteResponse,
+            RemoveInviteResponse,
+        },
+    },
+};
+
+/// Create invite link
+///
+/// Requires the owner or an admin. Anyone knowing the code can join the chat until the invite
+/// expires, is used up or is revoked.
+#[utoipa::path(
+    post,
+    path = "/chats/{chat_id}/invites",
+    tag = "invites",
+    params(
+        ("chat_id" = ChatId, Path, description = "Chat id")
+    ),
+    request_body = NewInviteRequest,
+    responses(
+        (status = CREATED, description = "Invite created", body = ChatInvite),
+        (status = BAD_REQUEST, description = "Invalid limits, or a direct chat", body = ApiError, example = json!({"type": "Validation", "fields": {"max_uses": ["Must be between 1 and 10000"]}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Not allowed to manage members, or the token lacks the manage-chats scope", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["manage-chats"]))
+)]
+pub async fn new_invite(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<ChatId>,
+    Json(req): Json<NewInviteRequest>,
+) -> Result<NewInviteResponse, ApiError> {
+    auth.require_scope(Scope::ManageChats, &trace_id)?;
+    authorize(&*state.chats, auth.user.id, chat_id, ChatPermission::ManageMembers, &trace_id).await?;
+
+    let mut errors = validate_invite(&req);
+    let chat = get_chat(&state, chat_id, &auth, &trace_id).await?;
+    if chat.kind == ChatKind::Direct {
+        errors.insert("chat_id".to_owned(), vec!["Direct chats cannot have invites".to_owned()]);
+    }
+
+    if !errors.is_empty() {
+        return Err(ApiError::Validation {
+            fields: errors,
+            trace_id,
+        });
+    }
+
+    let code = state.random.lock().await.get_invite_code();
+    let expires_at = req.expires_in_hours.map(|hours| {
+        OffsetDateTime::now_utc().saturating_add(Duration::hours(hours.into()))
+    });
+
+    match state
+        .invites
+        .create_invite(&code, chat_id, auth.user.id, req.max_uses, expires_at)
+        .await
+    {
+        Ok(invite) => {
+            tracing::info!("user {} created an invite to chat {chat_id}", auth.user.id);
+            Ok(NewInviteResponse(invite))
+        }
+
+        Err(err) => {
+            tracing::error!("failed to create invite: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Get invite links
+#[utoipa::path(
+    get,
+    path = "/chats/{chat_id}/invites",
+    tag = "invites",
+    params(
+        ("chat_id" = ChatId, Path, description = "Chat id")
+    ),
+    responses(
+        (status = OK, description = "Invites of the chat, newest first", body = GetInvitesResponse),
+        (status = FORBIDDEN, description = "Not allowed to manage members, or the token lacks the manage-chats scope", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["manage-chats"]))
+)]
+pub async fn get_invites(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<ChatId>,
+) -> Result<GetInvitesResponse, ApiError> {
+    auth.require_scope(Scope::ManageChats, &trace_id)?;
+    authorize(&*state.chats, auth.user.id, chat_id, ChatPermission::ManageMembers, &trace_id).await?;
+
+    match state.invites.get_chat_invites(chat_id).await {
+        Ok(invites) => Ok(GetInvitesResponse(invites)),
+        Err(err) => {
+            tracing::error!("failed to get invites: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Revoke invite link
+///
+/// Users can no longer join with the code, members who already joined stay.
+#[utoipa::path(
+    delete,
+    path = "/chats/{chat_id}/invites/{code}",
+    tag = "invites",
+    params(
+        ("chat_id" = ChatId, Path, description = "Chat id"),
+        ("code" = String, Path, description = "Invite code")
+    ),
+    responses(
+        (status = NO_CONTENT, description = "Invite revoked", body = RemoveInviteResponse),
+        (status = FORBIDDEN, description = "Not allowed to manage members, or the token lacks the manage-chats scope", body = ApiError),
+        (status = NOT_FOUND, description = "Invite not found", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["manage-chats"]))
+)]
+pub async fn remove_invite(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path((chat_id, code)): Path<(ChatId, String)>,
+) -> Result<RemoveInviteResponse, ApiError> {
+    auth.require_scope(Scope::ManageChats, &trace_id)?;
+    authorize(&*state.chats, auth.user.id, chat_id, ChatPermission::ManageMembers, &trace_id).await?;
+
+    match state.invites.remove_invite(chat_id, &code).await {
+        Ok(_) => {
+            tracing::info!("user {} revoked an invite to chat {chat_id}", auth.user.id);
+            Ok(RemoveInviteResponse)
+        }
+
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("invite not found in chat {chat_id}");
+            Err(ApiError::NotFound { trace_id })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to remove invite: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Preview invite link
+#[utoipa::path(
+    get,
+    path = "/invites/{code}",
+    tag = "invites",
+    params(
+        ("code" = String, Path, description = "Invite code")
+    ),
+    responses(
+        (status = OK, description = "Chat behind the invite", body = InvitePreview),
+        (status = NOT_FOUND, description = "Invalid, expired, used up or revoked invite", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = []))
+)]
+pub async fn get_invite(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+) -> Result<InvitePreview, ApiError> {
+    let invite = match state.invites.get_invite(&code).await {
+        Ok(invite) => invite,
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("user {} used an invalid invite", auth.user.id);
+            return Err(ApiError::NotFound { trace_id });
+        }
+
+        Err(err) => {
+            tracing::error!("failed to get invite: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    };
+
+    let chat = get_chat(&state, invite.chat_id, &auth, &trace_id).await?;
+    Ok(InvitePreview {
+        chat_id: chat.id,
+        is_member: chat.users_ids.contains(&auth.user.id),
+        members_count: chat.users_ids.len(),
+        title: chat.title,
+        description: chat.description,
+        expires_at: invite.expires_at,
+    })
+}
+
+/// Join chat with invite link
+///
+/// Members are notified with a `ChatMembers` event. Joining a chat the user is already
+/// a member of returns it without using the invite.
+#[utoipa::path(
+    post,
+    path = "/invites/{code}",
+    tag = "invites",
+    params(
+        ("code" = String, Path, description = "Invite code")
+    ),
+    responses(
+        (status = OK, description = "Joined chat", body = Chat),
+        (status = FORBIDDEN, description = "Token lacks the manage-chats scope", body = ApiError),
+        (status = NOT_FOUND, description = "Invalid, expired, used up or revoked invite", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["manage-chats"]))
+)]
+pub async fn join_chat(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+) -> Result<JoinChatResponse, ApiError> {
+    auth.require_scope(Scope::ManageChats, &trace_id)?;
+
+    let (chat_id, joined) = match state.invites.use_invite(&code, auth.user.id).await {
+        Ok(result) => result,
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("user {} used an invalid invite", auth.user.id);
+            return Err(ApiError::NotFound { trace_id });
+        }
+
+        Err(err) => {
+            tracing::error!("failed to use invite: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    };
+
+    let chat = get_chat(&state, chat_id, &auth, &trace_id).await?;
+    if joined {
+        tracing::info!("user {} joined chat {chat_id} with an invite", auth.user.id);
+        notify_members_changed(&state, chat_id, &chat.title, &chat.users_ids, vec![auth.user.id], Vec::new()).await;
+    }
+
+    Ok(JoinChatResponse(chat))
+}
+
+async fn get_chat(state: &AppState, chat_id: ChatId, auth: &Auth, trace_id: &TraceId) -> Result<Chat, ApiError> {
+    state.chats.get_chat(chat_id, auth.user.id).await.map_err(|err| {
+        tracing::error!("failed to get chat: {err}");
+        ApiError::Unknown {
+            trace_id: trace_id.clone(),
+        }
+    })
+}
+
+fn validate_invite(req: &NewInviteRequest) -> HashMap<String, Vec<String>> {
+    let mut errors = HashMap::new();
+    if let Some(max_uses) = req.max_uses
+        && !(1..=INVITE_MAX_USES).contains(&max_uses)
+    {
+        errors.insert("max_uses".to_owned(), vec![format!("Must be between 1 and {INVITE_MAX_USES}")]);
+    }
+
+    if req.expires_in_hours == Some(0) {
+        errors.insert("expires_in_hours".to_owned(), vec!["Lifetime must be at least one hour".to_owned()]);
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_invite() {
+        let req = NewInviteRequest {
+            expires_in_hours: Some(24),
+            max_uses: Some(10),
+        };
+        assert!(validate_invite(&req).is_empty());
+
+        let req = NewInviteRequest {
+            expires_in_hours: None,
+            max_uses: None,
+        };
+        assert!(validate_invite(&req).is_empty());
+
+        let req = NewInviteRequest {
+            expires_in_hours: Some(0),
+            max_uses: Some(0),
+        };
+        let errors = validate_invite(&req);
+        assert!(errors.contains_key("max_uses"));
+        assert!(errors.contains_key("expires_in_hours"));
+    }
+}
@@ -0,0 +1,378 @@
+use std::{collections::HashMap, sync::Arc};
+use axum::{
+    Json,
+    Extension,
+    extract::{Path, State},
+};
+use crate::{
+    AppState,
+    error::{ApiError, RepositoryError},
+    controllers::{blocks, chats::authorize},
+    services::{auth::Auth, trace::TraceId},
+    models::{
+        users::UserId,
+        tokens::Scope,
+        events::{ChatMembersEvent, SseEvent, SseEventType},
+        chats::{
+            AddMembersRequest,
+            Chat,
+            ChatId,
+            ChatKind,
+            ChatMembersResponse,
+            ChatPermission,
+            ChatRole,
+            ChatTitle,
+            GetChatMembersResponse,
+            RemoveChatResponse,
+            SetRoleRequest,
+            SetRoleResponse,
+        },
+    },
+};
+
+/// Get chat members
+#[utoipa::path(
+    get,
+    path = "/chats/{chat_id}/members",
+    tag = "chats",
+    params(
+        ("chat_id" = ChatId, Path, description = "Chat id")
+    ),
+    responses(
+        (status = OK, description = "Members with their roles, in the order they joined", body = GetChatMembersResponse),
+        (status = FORBIDDEN, description = "Not a member, or the token lacks the read-messages scope", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["read-messages"]))
+)]
+pub async fn get_members(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<ChatId>,
+) -> Result<GetChatMembersResponse, ApiError> {
+    auth.require_scope(Scope::ReadMessages, &trace_id)?;
+    authorize(&*state.chats, auth.user.id, chat_id, ChatPermission::ReadMessages, &trace_id).await?;
+
+    match state.chats.get_members(chat_id).await {
+        Ok(members) => Ok(GetChatMembersResponse(members)),
+        Err(err) => {
+            tracing::error!("failed to get chat members: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Add chat members
+///
+/// Requires the owner or an admin, direct chats cannot get more members. Users already in the chat are ignored,
+/// members are notified with a `ChatMembers` event.
+#[utoipa::path(
+    post,
+    path = "/chats/{chat_id}/members",
+    tag = "chats",
+    params(
+        ("chat_id" = ChatId, Path, description = "Chat id")
+    ),
+    request_body = AddMembersRequest,
+    responses(
+        (status = OK, description = "Members after the change", body = ChatMembersResponse),
+        (status = BAD_REQUEST, description = "No users, a direct chat, or one of the users blocked you", body = ApiError, example = json!({"type": "Validation", "fields": {"users_ids": ["Some users cannot be added to this chat"]}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Not allowed to manage members, or the token lacks the manage-chats scope", body = ApiError),
+        (status = NOT_FOUND, description = "User not found", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["manage-chats"]))
+)]
+pub async fn add_members(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<ChatId>,
+    Json(req): Json<AddMembersRequest>,
+) -> Result<ChatMembersResponse, ApiError> {
+    auth.require_scope(Scope::ManageChats, &trace_id)?;
+
+    if req.users_ids.is_empty() {
+        return Err(ApiError::Validation {
+            fields: HashMap::from([("users_ids".to_owned(), vec!["At least one user is required".to_owned()])]),
+            trace_id,
+        });
+    }
+
+    authorize(&*state.chats, auth.user.id, chat_id, ChatPermission::ManageMembers, &trace_id).await?;
+    let chat = get_chat(&state, chat_id, auth.user.id, &trace_id).await?;
+    if chat.kind == ChatKind::Direct {
+        tracing::warn!("user {} tried to add members to direct chat {chat_id}", auth.user.id);
+        return Err(ApiError::Validation {
+            fields: HashMap::from([("users_ids".to_owned(), vec!["Members cannot be added to a direct chat".to_owned()])]),
+            trace_id,
+        });
+    }
+
+    blocks::check_not_blocked(&*state.blocks, auth.user.id, &req.users_ids, &trace_id).await?;
+
+    let added = match state.chats.add_chat_members(chat_id, &req.users_ids).await {
+        Ok(added) => added,
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("some of {:?} do not exist", req.users_ids);
+            return Err(ApiError::NotFound { trace_id });
+        }
+
+        Err(err) => {
+            tracing::error!("failed to add chat members: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    };
+
+    let mut users_ids = chat.users_ids;
+    users_ids.extend(&added);
+    if !added.is_empty() {
+        tracing::info!("user {} added {added:?} to chat {chat_id}", auth.user.id);
+        notify_members_changed(&state, chat_id, &chat.title, &users_ids, added, Vec::new()).await;
+    }
+
+    Ok(ChatMembersResponse { users_ids })
+}
+
+/// Remove chat member
+///
+/// Requires a role above the one of the removed member, so admins can remove members
+/// and only the owner can remove admins. Removing yourself is the same as leaving the chat.
+/// The removed user and the remaining members are notified with a `ChatMembers` event.
+#[utoipa::path(
+    delete,
+    path = "/chats/{chat_id}/members/{user_id}",
+    tag = "chats",
+    params(
+        ("chat_id" = ChatId, Path, description = "Chat id"),
+        ("user_id" = UserId, Path, description = "Member to remove")
+    ),
+    responses(
+        (status = NO_CONTENT, description = "Member removed", body = RemoveChatResponse),
+        (status = FORBIDDEN, description = "Not allowed to remove this member, or the token lacks the manage-chats scope", body = ApiError),
+        (status = NOT_FOUND, description = "The user is not a member", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["manage-chats"]))
+)]
+pub async fn remove_member(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path((chat_id, user_id)): Path<(ChatId, UserId)>,
+) -> Result<RemoveChatResponse, ApiError> {
+    auth.require_scope(Scope::ManageChats, &trace_id)?;
+
+    let permission = if user_id == auth.user.id {
+        ChatPermission::ReadMessages
+    } else {
+        ChatPermission::ManageMembers
+    };
+
+    let role = authorize(&*state.chats, auth.user.id, chat_id, permission, &trace_id).await?;
+    if user_id != auth.user.id {
+        let member_role = get_member_role(&state, chat_id, user_id, &trace_id).await?;
+        if !role.outranks(member_role) {
+            tracing::warn!("user {} cannot remove {} {user_id}", auth.user.id, member_role.as_ref());
+            return Err(ApiError::Forbidden { trace_id });
+        }
+    }
+
+    let chat = get_chat(&state, chat_id, auth.user.id, &trace_id).await?;
+    remove_chat_member(&state, chat, user_id, &trace_id).await?;
+    tracing::info!("user {} removed {user_id} from chat {chat_id}", auth.user.id);
+    Ok(RemoveChatResponse)
+}
+
+/// Leave chat
+///
+/// When the owner leaves, the earliest admin or else the earliest member becomes the owner.
+/// The chat is deleted once its last member leaves.
+#[utoipa::path(
+    post,
+    path = "/chats/{chat_id}/leave",
+    tag = "chats",
+    params(
+        ("chat_id" = ChatId, Path, description = "Chat id")
+    ),
+    responses(
+        (status = NO_CONTENT, description = "Chat left", body = RemoveChatResponse),
+        (status = FORBIDDEN, description = "Not a member, or the token lacks the manage-chats scope", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["manage-chats"]))
+)]
+pub async fn leave_chat(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<ChatId>,
+) -> Result<RemoveChatResponse, ApiError> {
+    auth.require_scope(Scope::ManageChats, &trace_id)?;
+    authorize(&*state.chats, auth.user.id, chat_id, ChatPermission::ReadMessages, &trace_id).await?;
+
+    let chat = get_chat(&state, chat_id, auth.user.id, &trace_id).await?;
+    remove_chat_member(&state, chat, auth.user.id, &trace_id).await?;
+    tracing::info!("user {} left chat {chat_id}", auth.user.id);
+    Ok(RemoveChatResponse)
+}
+
+/// Set member role
+///
+/// Only the owner can change roles. Making a member the owner hands the ownership over
+/// and the previous owner becomes an admin.
+#[utoipa::path(
+    put,
+    path = "/chats/{chat_id}/members/{user_id}/role",
+    tag = "chats",
+    params(
+        ("chat_id" = ChatId, Path, description = "Chat id"),
+        ("user_id" = UserId, Path, description = "Member")
+    ),
+    request_body = SetRoleRequest,
+    responses(
+        (status = NO_CONTENT, description = "Role changed", body = SetRoleResponse),
+        (status = BAD_REQUEST, description = "Changing your own role", body = ApiError, example = json!({"type": "Validation", "fields": {"user_id": ["You cannot change your own role"]}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Not the owner, or the token lacks the manage-chats scope", body = ApiError),
+        (status = NOT_FOUND, description = "The user is not a member", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["manage-chats"]))
+)]
+pub async fn set_role(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path((chat_id, user_id)): Path<(ChatId, UserId)>,
+    Json(req): Json<SetRoleRequest>,
+) -> Result<SetRoleResponse, ApiError> {
+    auth.require_scope(Scope::ManageChats, &trace_id)?;
+    authorize(&*state.chats, auth.user.id, chat_id, ChatPermission::ManageRoles, &trace_id).await?;
+
+    if user_id == auth.user.id {
+        return Err(ApiError::Validation {
+            fields: HashMap::from([("user_id".to_owned(), vec!["You cannot change your own role".to_owned()])]),
+            trace_id,
+        });
+    }
+
+    let result = match req.role {
+        ChatRole::Owner => state.chats.transfer_ownership(chat_id, auth.user.id, user_id).await,
+        role => state.chats.set_member_role(chat_id, user_id, role).await,
+    };
+
+    match result {
+        Ok(_) => {
+            tracing::info!("user {} made {user_id} {} of chat {chat_id}", auth.user.id, req.role.as_ref());
+            Ok(SetRoleResponse)
+        }
+
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("user {user_id} is not a member of chat {chat_id}");
+            Err(ApiError::NotFound { trace_id })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to set member role: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+async fn get_chat(state: &AppState, chat_id: ChatId, viewer_id: UserId, trace_id: &TraceId) -> Result<Chat, ApiError> {
+    state.chats.get_chat(chat_id, viewer_id).await.map_err(|err| {
+        tracing::error!("failed to get chat: {err}");
+        ApiError::Unknown {
+            trace_id: trace_id.clone(),
+        }
+    })
+}
+
+async fn get_member_role(
+    state: &AppState,
+    chat_id: ChatId,
+    user_id: UserId,
+    trace_id: &TraceId,
+) -> Result<ChatRole, ApiError> {
+    match state.chats.get_member_role(chat_id, user_id).await {
+        Ok(role) => Ok(role),
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("user {user_id} is not a member of chat {chat_id}");
+            Err(ApiError::NotFound {
+                trace_id: trace_id.clone(),
+            })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to get member role: {err}");
+            Err(ApiError::Unknown {
+                trace_id: trace_id.clone(),
+            })
+        }
+    }
+}
+
+async fn remove_chat_member(
+    state: &AppState,
+    chat: Chat,
+    user_id: UserId,
+    trace_id: &TraceId,
+) -> Result<(), ApiError> {
+    let remaining = match state.chats.remove_chat_member(chat.id, user_id).await {
+        Ok(remaining) => remaining,
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("user {user_id} is not a member of chat {}", chat.id);
+            return Err(ApiError::NotFound {
+                trace_id: trace_id.clone(),
+            });
+        }
+
+        Err(err) => {
+            tracing::error!("failed to remove chat member: {err}");
+            return Err(ApiError::Unknown {
+                trace_id: trace_id.clone(),
+            });
+        }
+    };
+
+    if remaining.is_empty() {
+        tracing::info!("chat {} removed after its last member left", chat.id);
+    }
+
+    notify_members_changed(state, chat.id, &chat.title, &remaining, Vec::new(), vec![user_id]).await;
+    Ok(())
+}
+
+/// Notifies the members and the removed users about the change.
+pub(crate) async fn notify_members_changed(
+    state: &AppState,
+    chat_id: ChatId,
+    title: &ChatTitle,
+    users_ids: &[UserId],
+    added: Vec<UserId>,
+    removed: Vec<UserId>,
+) {
+    let mut recipients = users_ids.to_vec();
+    recipients.extend(&removed);
+
+    let event = SseEvent::new(
+        SseEventType::ChatMembers,
+        ChatMembersEvent {
+            chat_id,
+            title: ChatTitle::new(title.to_string()),
+            users_ids: users_ids.to_vec(),
+            added,
+            removed,
+        },
+    );
+
+    for recipient in &recipients {
+        if let Some(recipient) = state.events.get(recipient)
+            && let Err(err) = recipient.send(event.clone()) {
+                tracing::trace!("no event streams for {}: {err}", event.event_type.as_ref());
+            }
+    }
+
+    state.webhooks.notify_bots(&*state.bots, &recipients, &event).await;
+}
@@ -7,11 +7,11 @@ use axum::{
 use crate::{
     AppState,
     error::ApiError,
-    repositories::chats::ChatsRepository,
+    controllers::chats::authorize,
     services::{auth::Auth, trace::TraceId},
     models::{
-        chats::ChatId,
-        users::UserId,
+        chats::{ChatId, ChatPermission},
+        tokens::Scope,
         events::{
             SseEvent,
             SseEventType,
@@ -44,7 +44,7 @@ const MAX_MESSAGES: i64 = 100;
         (status = FORBIDDEN, description = "Forbidden", example = json!({"type": "Forbidden", "trace_id": TraceId::new()})),
         (status = INTERNAL_SERVER_ERROR, description = "Internal server error", example = json!({"type": "Unknown", "trace_id": TraceId::new()}))
     ),
-    security(("auth" = []))
+    security(("auth" = []), ("token" = ["send-messages"]))
 )]
 pub async fn new_message(
     Extension(auth): Extension<Arc<Auth>>,
@@ -54,11 +54,8 @@ pub async fn new_message(
     Json(req): Json<NewMessageRequest>,
 ) -> Result<NewMessageResponse, ApiError> {
     tracing::trace!("new message for chat {chat_id} from user {}", auth.user.id);
-
-    if !check_chat_access(&*state.chats, auth.user.id, chat_id).await {
-        tracing::error!("forbidden");
-        return Err(ApiError::Forbidden { trace_id });
-    }
+    auth.require_scope(Scope::SendMessages, &trace_id)?;
+    authorize(&*state.chats, auth.user.id, chat_id, ChatPermission::SendMessages, &trace_id).await?;
 
     let mut errors = HashMap::new();
     let content_errors = req.content.validate();
@@ -91,42 +88,47 @@ pub async fn new_message(
 
     let chat_members = state.chats.get_chat_members(chat_id).await.map_err(|e| {
         tracing::error!("failed to get chat members: {e}");
-        ApiError::Unknown { trace_id }
+        ApiError::Unknown { trace_id: trace_id.clone() }
     })?;
 
-    for member in chat_members {
-        if member == auth.user.id {
-            continue;
-        }
+    let event = SseEvent::new(
+        SseEventType::Message,
+        MessageEvent {
+            user_id: auth.user.id,
+            message: message.clone(),
+            chat_id,
+        },
+    );
 
-        if let Some(member) = state.events.get(&member) {
-            if let Err(e) = member.send(SseEvent::new(
-                SseEventType::Message,
-                MessageEvent {
-                    user_id: auth.user.id,
-                    message: message.clone(),
-                    chat_id,
-                },
-            )) {
+    let blockers = state
+        .blocks
+        .get_blockers(auth.user.id, &chat_members)
+        .await
+        .map_err(|e| {
+            tracing::error!("failed to get blockers: {e}");
+            ApiError::Unknown { trace_id }
+        })?;
+
+    // members who blocked the sender still see the message flagged, but are not notified
+    let recipients = chat_members
+        .into_iter()
+        .filter(|member| *member != auth.user.id && !blockers.contains(member))
+        .collect::<Vec<_>>();
+
+    for member in &recipients {
+        if let Some(member) = state.events.get(member)
+            && let Err(e) = member.send(event.clone()) {
                 tracing::error!("failed to send message event: {e}");
             }
-        }
     }
 
+    state.webhooks.notify_bots(&*state.bots, &recipients, &event).await;
+
     Ok(NewMessageResponse {
         message_id: message.id,
     })
 }
 
-#[tracing::instrument(skip(chats), ret)]
-async fn check_chat_access(chats: &dyn ChatsRepository, user_id: UserId, chat_id: ChatId) -> bool {
-    let Ok(chats) = chats.get_user_chats_ids(user_id).await else {
-        return false;
-    };
-
-    chats.contains(&chat_id)
-}
-
 /// Get chat messages
 #[utoipa::path(
     get,
@@ -141,7 +143,7 @@ async fn check_chat_access(chats: &dyn ChatsRepository, user_id: UserId, chat_id
         (status = OK, description = "Messages ", body = GetMessagesResponse),
         (status = FORBIDDEN, description = "Forbidden", example = json!({"type": "Forbidden", "trace_id": TraceId::new()}))
     ),
-    security(("auth" = []))
+    security(("auth" = []), ("token" = ["read-messages"]))
 )]
 pub async fn get_messages(
     Extension(auth): Extension<Arc<Auth>>,
@@ -150,10 +152,8 @@ pub async fn get_messages(
     Path(chat_id): Path<ChatId>,
     Query(params): Query<GetMessagesParams>,
 ) -> Result<GetMessagesResponse, ApiError> {
-    if !check_chat_access(&*state.chats, auth.user.id, chat_id).await {
-        tracing::error!("forbidden");
-        return Err(ApiError::Forbidden { trace_id });
-    }
+    auth.require_scope(Scope::ReadMessages, &trace_id)?;
+    authorize(&*state.chats, auth.user.id, chat_id, ChatPermission::ReadMessages, &trace_id).await?;
 
     let limit = if params.limit > MAX_MESSAGES {
         MAX_MESSAGES
@@ -163,7 +163,7 @@ pub async fn get_messages(
 
     let mut messages = state
         .messages
-        .get_messages(chat_id, limit + 1, params.last_message_id)
+        .get_messages(chat_id, auth.user.id, limit + 1, params.last_message_id)
         .await
         .map_err(|e| {
             tracing::error!("failed to get messages: {e}");
@@ -175,38 +175,4 @@ pub async fn get_messages(
     messages.truncate(limit as usize);
 
     Ok(GetMessagesResponse { messages, has_more })
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tokio::test;
-    use std::collections::HashSet;
-    use crate::repositories::chats::MockChatsRepository;
-
-    #[test]
-    async fn test_check_chat_access_ok() {
-        let mut chats = MockChatsRepository::new();
-        chats.expect_get_user_chats_ids().returning(|_| {
-            let set = HashSet::from([ChatId::new(1), ChatId::new(2), ChatId::new(3)]);
-            Ok(set)
-        });
-        let user_id = UserId::new(1);
-        let chat_id = ChatId::new(2);
-
-        assert!(check_chat_access(&chats, user_id, chat_id).await);
-    }
-
-    #[test]
-    async fn test_check_chat_access_fail() {
-        let mut chats = MockChatsRepository::new();
-        chats.expect_get_user_chats_ids().returning(|_| {
-            let set = HashSet::from([ChatId::new(1), ChatId::new(2), ChatId::new(3)]);
-            Ok(set)
-        });
-        let user_id = UserId::new(1);
-        let chat_id = ChatId::new(4);
-
-        assert!(!check_chat_access(&chats, user_id, chat_id).await);
-    }
 }
\ No newline at end of file
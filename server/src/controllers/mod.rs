@@ -2,4 +2,18 @@ pub mod users;
 pub mod chats;
 pub mod events;
 pub mod search;
-pub mod messages;
\ No newline at end of file
+pub mod sessions;
+pub mod tokens;
+pub mod bots;
+pub mod totp;
+pub mod profiles;
+pub mod presence;
+pub mod blocks;
+pub mod identity;
+pub mod messages;
+pub mod admin;
+pub mod export;
+pub mod members;
+pub mod invites;
+pub mod channels;
+pub mod receipts;
\ No newline at end of file
@@ -0,0 +1,244 @@
+use std::{collections::HashMap, sync::Arc};
+use axum::{
+    Json,
+    Extension,
+    extract::{Query, State},
+};
+use time::OffsetDateTime;
+use crate::{
+    AppState,
+    error::ApiError,
+    controllers::events,
+    services::{auth::Auth, presence::Disconnect, trace::TraceId},
+    models::{
+        tokens::Scope,
+        sessions::SessionId,
+        users::{UpdateAccountResponse, UserId},
+        events::{SseEvent, SseEventType},
+        presence::{
+            GetPresenceQuery,
+            GetPresenceResponse,
+            PresenceEvent,
+            PresenceSettingsRequest,
+            PresenceStatus,
+            SetActivityRequest,
+            UserPresence,
+        },
+    },
+};
+
+/// Get presence
+///
+/// Only the caller and users sharing a chat with them are returned.
+#[utoipa::path(
+    get,
+    path = "/presence",
+    tag = "presence",
+    params(
+        ("users_ids" = String, Query, description = "Comma separated users ids")
+    ),
+    responses(
+        (status = OK, description = "Presence of the users", body = GetPresenceResponse),
+        (status = BAD_REQUEST, description = "Invalid users ids", body = ApiError, example = json!({"type": "Validation", "fields": {"users_ids": ["Invalid user id: me"]}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Token lacks the read-messages scope", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["read-messages"]))
+)]
+pub async fn get_presence(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<GetPresenceQuery>,
+) -> Result<GetPresenceResponse, ApiError> {
+    auth.require_scope(Scope::ReadMessages, &trace_id)?;
+
+    let users_ids = match params.users_ids() {
+        Ok(users_ids) => users_ids,
+        Err(errors) => {
+            return Err(ApiError::Validation {
+                fields: HashMap::from([("users_ids".to_owned(), errors)]),
+                trace_id,
+            });
+        }
+    };
+
+    let peers = match state.chats.get_chat_peers(auth.user.id).await {
+        Ok(peers) => peers,
+        Err(err) => {
+            tracing::error!("failed to get chat peers: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    };
+
+    let users_ids = users_ids
+        .into_iter()
+        .filter(|user_id| *user_id == auth.user.id || peers.contains(user_id))
+        .collect::<Vec<_>>();
+
+    let records = match state.presences.get_presences(&users_ids).await {
+        Ok(records) => records
+            .into_iter()
+            .map(|record| (record.user_id, record))
+            .collect::<HashMap<_, _>>(),
+        Err(err) => {
+            tracing::error!("failed to get presence: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    };
+
+    let presences = users_ids
+        .into_iter()
+        .map(|user_id| {
+            let status = state.presence.status(user_id);
+            let mut record = records.get(&user_id).cloned();
+            if user_id == auth.user.id
+                && let Some(record) = &mut record {
+                    // hiding only applies to others
+                    record.hidden = false;
+                }
+
+            UserPresence::new(user_id, status, record.as_ref())
+        })
+        .collect();
+
+    Ok(GetPresenceResponse(presences))
+}
+
+/// Report activity
+///
+/// Clients report when the user stops or starts interacting with them,
+/// the user is idle once every event stream is idle.
+#[utoipa::path(
+    put,
+    path = "/presence",
+    tag = "presence",
+    request_body = SetActivityRequest,
+    responses(
+        (status = NO_CONTENT, description = "Activity recorded", body = UpdateAccountResponse),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn set_activity(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SetActivityRequest>,
+) -> Result<UpdateAccountResponse, ApiError> {
+    let (_, session_id) = auth.require_session(&trace_id)?;
+
+    if let Some(status) = state.presence.set_idle(auth.user.id, session_id, req.idle) {
+        tracing::trace!("user {} is {status:?}", auth.user.id);
+        publish_presence(&state, auth.user.id, status).await;
+    }
+
+    Ok(UpdateAccountResponse)
+}
+
+/// Update presence settings
+#[utoipa::path(
+    put,
+    path = "/account/presence",
+    tag = "presence",
+    request_body = PresenceSettingsRequest,
+    responses(
+        (status = NO_CONTENT, description = "Settings updated", body = UpdateAccountResponse),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn update_presence_settings(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<PresenceSettingsRequest>,
+) -> Result<UpdateAccountResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    if let Err(err) = state.presences.set_hidden(auth.user.id, !req.visible).await {
+        tracing::error!("failed to update presence settings: {err}");
+        return Err(ApiError::Unknown { trace_id });
+    }
+
+    tracing::info!("presence of user {} is now {}", auth.user.id, if req.visible { "visible" } else { "hidden" });
+    if req.visible {
+        publish_presence(&state, auth.user.id, state.presence.status(auth.user.id)).await;
+    } else {
+        notify_presence(&state, UserPresence::hidden(auth.user.id)).await;
+    }
+
+    Ok(UpdateAccountResponse)
+}
+
+/// Presence of one event stream, the stream counts as connected until this is dropped.
+pub(crate) struct PresenceGuard {
+    state: Arc<AppState>,
+    user_id: UserId,
+    connection_id: u64,
+}
+
+impl PresenceGuard {
+    pub(crate) fn connect(state: Arc<AppState>, user_id: UserId, session_id: Option<SessionId>) -> Self {
+        let (connection_id, change) = state.presence.connect(user_id, session_id);
+        if let Some(status) = change {
+            let state = state.clone();
+            tokio::spawn(async move { publish_presence(&state, user_id, status).await });
+        }
+
+        Self {
+            state,
+            user_id,
+            connection_id,
+        }
+    }
+}
+
+impl Drop for PresenceGuard {
+    fn drop(&mut self) {
+        let (state, user_id) = (self.state.clone(), self.user_id);
+        match state.presence.disconnect(user_id, self.connection_id) {
+            Disconnect::Remaining(None) => {}
+            Disconnect::Remaining(Some(status)) => {
+                tokio::spawn(async move { publish_presence(&state, user_id, status).await });
+            }
+
+            Disconnect::Last { generation } => {
+                tokio::spawn(async move {
+                    tokio::time::sleep(state.presence.grace_period).await;
+                    if state.presence.expire(user_id, generation) {
+                        publish_presence(&state, user_id, PresenceStatus::Offline).await;
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Saves the last seen time and tells chat peers about the new status, unless the user hides it.
+async fn publish_presence(state: &AppState, user_id: UserId, status: PresenceStatus) {
+    if let Err(err) = state.presences.set_last_seen(user_id, OffsetDateTime::now_utc()).await {
+        tracing::error!("failed to save last seen time: {err}");
+    }
+
+    let record = match state.presences.get_presences(&[user_id]).await {
+        Ok(mut records) => records.pop(),
+        Err(err) => {
+            tracing::error!("failed to get presence: {err}");
+            return;
+        }
+    };
+
+    if record.as_ref().is_some_and(|record| record.hidden) {
+        return;
+    }
+
+    notify_presence(state, UserPresence::new(user_id, status, record.as_ref())).await;
+}
+
+async fn notify_presence(state: &AppState, presence: UserPresence) {
+    let user_id = presence.user_id;
+    let event = SseEvent::new(SseEventType::Presence, PresenceEvent { presence });
+    events::notify_chat_peers(state, user_id, event).await;
+}
@@ -0,0 +1,392 @@
+use std::{collections::HashMap, sync::Arc};
+use axum::{
+    Json,
+    Extension,
+    body::Bytes,
+    extract::{Path, State},
+};
+use time::OffsetDateTime;
+use crate::{
+    AppState,
+    error::{ApiError, RepositoryError},
+    controllers::events,
+    repositories::profiles::ProfilesRepository,
+    services::{auth::Auth, trace::TraceId},
+    models::{
+        events::{SseEvent, SseEventType},
+        users::{PublicUser, UpdateAccountResponse, User, UserId},
+        profiles::{
+            AvatarFormat,
+            GetAvatarResponse,
+            ProfileEvent,
+            SetStatusRequest,
+            UpdateProfileRequest,
+            AVATAR_MAX_SIZE,
+        },
+    },
+};
+
+/// Update profile
+///
+/// Replaces the display name and bio, a missing field is cleared.
+#[utoipa::path(
+    put,
+    path = "/account/profile",
+    tag = "profiles",
+    request_body = UpdateProfileRequest,
+    responses(
+        (status = NO_CONTENT, description = "Profile updated", body = UpdateAccountResponse),
+        (status = BAD_REQUEST, description = "Invalid profile", body = ApiError, example = json!({"type": "Validation", "fields": {"display_name": ["Display name must be at most 64 characters"]}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn update_profile(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<UpdateProfileRequest>,
+) -> Result<UpdateAccountResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    let mut errors = HashMap::new();
+    if let Some(display_name) = &req.display_name {
+        let display_name_errors = display_name.validate();
+        if !display_name_errors.is_empty() {
+            errors.insert("display_name".to_owned(), display_name_errors);
+        }
+    }
+
+    if let Some(bio) = &req.bio {
+        let bio_errors = bio.validate();
+        if !bio_errors.is_empty() {
+            errors.insert("bio".to_owned(), bio_errors);
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(ApiError::Validation {
+            fields: errors,
+            trace_id,
+        });
+    }
+
+    let display_name = req.display_name.as_deref().map(str::to_owned);
+    let bio = req.bio.as_deref().filter(|bio| !bio.is_empty()).map(str::to_owned);
+    if let Err(err) = state.profiles.update_profile(auth.user.id, display_name, bio).await {
+        tracing::error!("failed to update profile: {err}");
+        return Err(ApiError::Unknown { trace_id });
+    }
+
+    tracing::info!("profile of user {} updated", auth.user.id);
+    notify_profile_changed(&state, &auth.user).await;
+    Ok(UpdateAccountResponse)
+}
+
+/// Set status
+#[utoipa::path(
+    put,
+    path = "/account/status",
+    tag = "profiles",
+    request_body = SetStatusRequest,
+    responses(
+        (status = NO_CONTENT, description = "Status set", body = UpdateAccountResponse),
+        (status = BAD_REQUEST, description = "Invalid status", body = ApiError, example = json!({"type": "Validation", "fields": {"expires_at": ["Expiration must be in the future"]}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn set_status(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SetStatusRequest>,
+) -> Result<UpdateAccountResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    let mut errors = HashMap::new();
+    let text_errors = req.text.validate();
+    if !text_errors.is_empty() {
+        errors.insert("text".to_owned(), text_errors);
+    }
+
+    if req.expires_at.is_some_and(|expires_at| expires_at <= OffsetDateTime::now_utc()) {
+        errors.insert("expires_at".to_owned(), vec!["Expiration must be in the future".to_owned()]);
+    }
+
+    if !errors.is_empty() {
+        return Err(ApiError::Validation {
+            fields: errors,
+            trace_id,
+        });
+    }
+
+    let text = Some(req.text.to_owned());
+    if let Err(err) = state.profiles.set_status(auth.user.id, text, req.expires_at).await {
+        tracing::error!("failed to set status: {err}");
+        return Err(ApiError::Unknown { trace_id });
+    }
+
+    tracing::info!("status of user {} set", auth.user.id);
+    notify_profile_changed(&state, &auth.user).await;
+    Ok(UpdateAccountResponse)
+}
+
+/// Clear status
+#[utoipa::path(
+    delete,
+    path = "/account/status",
+    tag = "profiles",
+    responses(
+        (status = NO_CONTENT, description = "Status cleared", body = UpdateAccountResponse),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn clear_status(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<UpdateAccountResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    if let Err(err) = state.profiles.set_status(auth.user.id, None, None).await {
+        tracing::error!("failed to clear status: {err}");
+        return Err(ApiError::Unknown { trace_id });
+    }
+
+    tracing::info!("status of user {} cleared", auth.user.id);
+    notify_profile_changed(&state, &auth.user).await;
+    Ok(UpdateAccountResponse)
+}
+
+/// Upload avatar
+///
+/// The request body is the raw PNG, JPEG, GIF or WebP image, up to 1 MiB.
+#[utoipa::path(
+    put,
+    path = "/account/avatar",
+    tag = "profiles",
+    request_body(content = Vec<u8>, content_type = "image/*"),
+    responses(
+        (status = NO_CONTENT, description = "Avatar uploaded", body = UpdateAccountResponse),
+        (status = BAD_REQUEST, description = "Unsupported or too large image", body = ApiError, example = json!({"type": "Validation", "fields": {"avatar": ["Unsupported image format"]}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn set_avatar(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    body: Bytes,
+) -> Result<UpdateAccountResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    let format = validate_image(&body, "avatar", &trace_id)?;
+    if let Err(err) = state.profiles.set_avatar(auth.user.id, format.content_type(), &body).await {
+        tracing::error!("failed to store avatar: {err}");
+        return Err(ApiError::Unknown { trace_id });
+    }
+
+    tracing::info!("avatar of user {} uploaded", auth.user.id);
+    notify_profile_changed(&state, &auth.user).await;
+    Ok(UpdateAccountResponse)
+}
+
+/// Remove avatar
+#[utoipa::path(
+    delete,
+    path = "/account/avatar",
+    tag = "profiles",
+    responses(
+        (status = NO_CONTENT, description = "Avatar removed", body = UpdateAccountResponse),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError),
+        (status = NOT_FOUND, description = "No avatar", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn remove_avatar(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<UpdateAccountResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    match state.profiles.remove_avatar(auth.user.id).await {
+        Ok(_) => {
+            tracing::info!("avatar of user {} removed", auth.user.id);
+            notify_profile_changed(&state, &auth.user).await;
+            Ok(UpdateAccountResponse)
+        }
+
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("user {} has no avatar", auth.user.id);
+            Err(ApiError::NotFound { trace_id })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to remove avatar: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Get user avatar
+#[utoipa::path(
+    get,
+    path = "/users/{id}/avatar",
+    tag = "profiles",
+    params(
+        ("id" = UserId, Path, description = "User id")
+    ),
+    responses(
+        (status = OK, description = "Avatar image", content_type = "image/*"),
+        (status = NOT_FOUND, description = "No avatar", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = []))
+)]
+pub async fn get_avatar(
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(user_id): Path<UserId>,
+) -> Result<GetAvatarResponse, ApiError> {
+    match state.profiles.get_avatar(user_id).await {
+        Ok(avatar) => Ok(GetAvatarResponse(avatar)),
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("user {user_id} has no avatar");
+            Err(ApiError::NotFound { trace_id })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to get avatar: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Checks an uploaded avatar or chat image, errors are reported under `field`.
+pub(crate) fn validate_image(data: &[u8], field: &str, trace_id: &TraceId) -> Result<AvatarFormat, ApiError> {
+    let error = if data.len() > AVATAR_MAX_SIZE {
+        "Image must be at most 1 MiB"
+    } else if let Some(format) = AvatarFormat::detect(data) {
+        return Ok(format);
+    } else {
+        "Unsupported image format"
+    };
+
+    Err(ApiError::Validation {
+        fields: HashMap::from([(field.to_owned(), vec![error.to_owned()])]),
+        trace_id: trace_id.clone(),
+    })
+}
+
+/// Adds the profile, if any, to every user.
+pub(crate) async fn public_users(
+    profiles: &dyn ProfilesRepository,
+    users: Vec<User>,
+    trace_id: &TraceId,
+) -> Result<Vec<PublicUser>, ApiError> {
+    let ids = users.iter().map(|user| user.id).collect::<Vec<_>>();
+    let mut profiles = match profiles.get_profiles(&ids).await {
+        Ok(profiles) => profiles
+            .into_iter()
+            .map(|profile| (profile.user_id, profile))
+            .collect::<HashMap<_, _>>(),
+        Err(err) => {
+            tracing::error!("failed to get profiles: {err}");
+            return Err(ApiError::Unknown {
+                trace_id: trace_id.clone(),
+            });
+        }
+    };
+
+    let now = OffsetDateTime::now_utc();
+    Ok(users
+        .into_iter()
+        .map(|user| {
+            let profile = profiles.remove(&user.id);
+            let user = PublicUser::from(user);
+            match profile {
+                Some(profile) => user.with_profile(profile, now),
+                None => user,
+            }
+        })
+        .collect())
+}
+
+/// Sends the updated profile to the user's other streams and everyone sharing a chat.
+async fn notify_profile_changed(state: &AppState, user: &User) {
+    let user = match public_users(&*state.profiles, vec![user.clone()], &TraceId::new()).await {
+        Ok(mut users) => users.remove(0),
+        Err(_) => return,
+    };
+
+    let user_id = user.id;
+    let event = SseEvent::new(SseEventType::Profile, ProfileEvent { user });
+    events::notify_chat_peers(state, user_id, event).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::profiles::Profile, repositories::profiles::MockProfilesRepository};
+
+    fn user(id: i32) -> User {
+        User {
+            id: UserId::new(id),
+            username: format!("user{id}"),
+            password: String::new(),
+            is_bot: false,
+            is_admin: false,
+            suspended_at: None,
+            created_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    #[test]
+    fn test_validate_image() {
+        let trace_id = TraceId::new();
+
+        assert_eq!(validate_image(b"GIF89a....", "avatar", &trace_id).unwrap(), AvatarFormat::Gif);
+        assert!(matches!(
+            validate_image(b"<svg onload=alert(1)>", "avatar", &trace_id),
+            Err(ApiError::Validation { .. })
+        ));
+
+        let mut large = b"GIF89a".to_vec();
+        large.resize(AVATAR_MAX_SIZE + 1, 0);
+        assert!(matches!(validate_image(&large, "avatar", &trace_id), Err(ApiError::Validation { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_public_users_adds_profiles() {
+        let mut profiles = MockProfilesRepository::new();
+        profiles
+            .expect_get_profiles()
+            .withf(|ids| ids == [UserId::new(1), UserId::new(2)])
+            .returning(|_| {
+                Ok(vec![Profile {
+                    user_id: UserId::new(2),
+                    display_name: Some("Иван".to_owned()),
+                    bio: None,
+                    status_text: None,
+                    status_expires_at: None,
+                    avatar_updated_at: None,
+                }])
+            });
+
+        let users = public_users(&profiles, vec![user(1), user(2)], &TraceId::new())
+            .await
+            .unwrap();
+
+        assert_eq!(users[0].display_name, None);
+        assert_eq!(users[1].display_name.as_deref(), Some("Иван"));
+    }
+}
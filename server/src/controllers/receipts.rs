@@ -0,0 +1,223 @@
+use std::sync::Arc;
+use axum::{
+    Json,
+    Extension,
+    extract::{Path, State},
+};
+use crate::{
+    AppState,
+    error::{ApiError, RepositoryError},
+    controllers::chats::authorize,
+    services::{auth::Auth, trace::TraceId},
+    models::{
+        tokens::Scope,
+        users::{UpdateAccountResponse, UserId},
+        chats::{ChatId, ChatPermission},
+        messages::MessageId,
+        events::{ReadMarkerEvent, SseEvent, SseEventType},
+        receipts::{
+            MarkReadRequest,
+            MessageReadersResponse,
+            ReadMarkerResponse,
+            ReadReceiptsSettingsRequest,
+        },
+    },
+};
+
+/// Mark chat as read
+///
+/// Moves the read marker of the user forward to the message, earlier messages count as read too.
+/// The other devices of the user get a `ReadMarker` event, and so do the other members unless
+/// the user disabled read receipts.
+#[utoipa::path(
+    put,
+    path = "/chats/{chat_id}/read",
+    tag = "receipts",
+    params(
+        ("chat_id" = ChatId, Path, description = "Chat id")
+    ),
+    request_body = MarkReadRequest,
+    responses(
+        (status = OK, description = "Read marker after the change", body = ReadMarkerResponse),
+        (status = FORBIDDEN, description = "Not a member, or the token lacks the read-messages scope", body = ApiError),
+        (status = NOT_FOUND, description = "No such message in the chat", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["read-messages"]))
+)]
+pub async fn mark_read(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<ChatId>,
+    Json(req): Json<MarkReadRequest>,
+) -> Result<ReadMarkerResponse, ApiError> {
+    auth.require_scope(Scope::ReadMessages, &trace_id)?;
+    authorize(&*state.chats, auth.user.id, chat_id, ChatPermission::ReadMessages, &trace_id).await?;
+
+    let (last_read_message_id, moved) = match state.receipts.mark_read(chat_id, auth.user.id, req.message_id).await {
+        Ok(result) => result,
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("user {} marked message {} read, which is not in chat {chat_id}", auth.user.id, *req.message_id);
+            return Err(ApiError::NotFound { trace_id });
+        }
+
+        Err(err) => {
+            tracing::error!("failed to mark chat read: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    };
+
+    if moved {
+        tracing::trace!("user {} read chat {chat_id} up to message {}", auth.user.id, *last_read_message_id);
+        notify_read_marker(&state, chat_id, auth.user.id, last_read_message_id).await;
+    }
+
+    Ok(ReadMarkerResponse {
+        chat_id,
+        last_read_message_id,
+    })
+}
+
+/// Get message readers
+#[utoipa::path(
+    get,
+    path = "/chats/{chat_id}/messages/{message_id}/readers",
+    tag = "receipts",
+    params(
+        ("chat_id" = ChatId, Path, description = "Chat id"),
+        ("message_id" = MessageId, Path, description = "Message id")
+    ),
+    responses(
+        (status = OK, description = "Members who read the message", body = MessageReadersResponse),
+        (status = FORBIDDEN, description = "Not a member, or the token lacks the read-messages scope", body = ApiError),
+        (status = NOT_FOUND, description = "No such message in the chat", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []), ("token" = ["read-messages"]))
+)]
+pub async fn get_message_readers(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path((chat_id, message_id)): Path<(ChatId, MessageId)>,
+) -> Result<MessageReadersResponse, ApiError> {
+    auth.require_scope(Scope::ReadMessages, &trace_id)?;
+    authorize(&*state.chats, auth.user.id, chat_id, ChatPermission::ReadMessages, &trace_id).await?;
+
+    match state.receipts.get_readers(chat_id, message_id).await {
+        Ok(users_ids) => Ok(MessageReadersResponse { users_ids }),
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("message {} not found in chat {chat_id}", *message_id);
+            Err(ApiError::NotFound { trace_id })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to get message readers: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Update read receipts settings
+///
+/// Without read receipts the read marker still clears unread counts on the devices of the user,
+/// but other members neither get `ReadMarker` events nor see the user among the readers.
+#[utoipa::path(
+    put,
+    path = "/account/read-receipts",
+    tag = "receipts",
+    request_body = ReadReceiptsSettingsRequest,
+    responses(
+        (status = NO_CONTENT, description = "Settings updated", body = UpdateAccountResponse),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn update_read_receipts_settings(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ReadReceiptsSettingsRequest>,
+) -> Result<UpdateAccountResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    if let Err(err) = state.receipts.set_read_receipts(auth.user.id, req.enabled).await {
+        tracing::error!("failed to update read receipts settings: {err}");
+        return Err(ApiError::Unknown { trace_id });
+    }
+
+    tracing::info!("read receipts of user {} are now {}", auth.user.id, if req.enabled { "enabled" } else { "disabled" });
+    Ok(UpdateAccountResponse)
+}
+
+async fn notify_read_marker(state: &AppState, chat_id: ChatId, user_id: UserId, message_id: MessageId) {
+    let members = match state.chats.get_chat_members(chat_id).await {
+        Ok(members) => members,
+        Err(err) => {
+            tracing::error!("failed to get chat members: {err}");
+            return;
+        }
+    };
+
+    let send_receipts = match state.receipts.get_read_receipts(user_id).await {
+        Ok(enabled) => enabled,
+        Err(err) => {
+            tracing::error!("failed to get read receipts settings: {err}");
+            false
+        }
+    };
+
+    let blockers = match state.blocks.get_blockers(user_id, &members).await {
+        Ok(blockers) => blockers,
+        Err(err) => {
+            tracing::error!("failed to get blockers: {err}");
+            return;
+        }
+    };
+
+    let event = SseEvent::new(
+        SseEventType::ReadMarker,
+        ReadMarkerEvent {
+            chat_id,
+            user_id,
+            message_id,
+        },
+    );
+
+    let recipients = receipt_recipients(user_id, members, &blockers, send_receipts);
+    for recipient in &recipients {
+        if let Some(recipient) = state.events.get(recipient)
+            && let Err(err) = recipient.send(event.clone()) {
+                tracing::trace!("no event streams for read marker: {err}");
+            }
+    }
+
+    state.webhooks.notify_bots(&*state.bots, &recipients, &event).await;
+}
+
+/// The reader always gets its own marker, the other members only when receipts are sent
+/// and they did not block the reader.
+fn receipt_recipients(reader_id: UserId, members: Vec<UserId>, blockers: &[UserId], send_receipts: bool) -> Vec<UserId> {
+    members
+        .into_iter()
+        .filter(|member| *member == reader_id || (send_receipts && !blockers.contains(member)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_receipt_recipients() {
+        let members = vec![UserId::new(1), UserId::new(2), UserId::new(3)];
+
+        let recipients = receipt_recipients(UserId::new(1), members.clone(), &[UserId::new(3)], true);
+        assert_eq!(recipients, vec![UserId::new(1), UserId::new(2)]);
+
+        let recipients = receipt_recipients(UserId::new(1), members, &[], false);
+        assert_eq!(recipients, vec![UserId::new(1)]);
+    }
+}
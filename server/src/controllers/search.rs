@@ -1,8 +1,13 @@
 use crate::{
     AppState,
     error::ApiError,
-    services::trace::TraceId,
-    models::search::{SearchUsersQuery, SearchUsersResponse},
+    controllers::profiles,
+    repositories::users::UsersRepository,
+    services::{auth::Auth, trace::TraceId},
+    models::{
+        search::{SearchUsersQuery, SearchUsersResponse},
+        users::{User, UserId},
+    },
 };
 use axum::{
     Extension,
@@ -11,6 +16,8 @@ use axum::{
 use std::{collections::HashMap, sync::Arc};
 
 /// Search users
+///
+/// Users blocked by the caller, if signed in, are left out.
 #[utoipa::path(
     get,
     path = "/search/users",
@@ -24,6 +31,7 @@ use std::{collections::HashMap, sync::Arc};
     )
 )]
 pub async fn search_users(
+    auth: Option<Extension<Arc<Auth>>>,
     Extension(trace_id): Extension<TraceId>,
     State(state): State<Arc<AppState>>,
     Query(params): Query<SearchUsersQuery>,
@@ -36,17 +44,49 @@ pub async fn search_users(
         });
     }
 
-    let users = state.users.search_users_by_username(&params.username).await;
-    match users {
-        Ok(users) => Ok(SearchUsersResponse(
-            users.into_iter().map(|user| user.into()).collect(),
-        )),
+    let searcher_id = auth.map(|Extension(auth)| auth.user.id);
+    let users = find_users(&*state.users, &params.username, searcher_id, &trace_id).await?;
+    Ok(SearchUsersResponse(
+        profiles::public_users(&*state.profiles, users, &trace_id).await?,
+    ))
+}
 
-        Err(err) => {
-            tracing::error!("failed to search users: {}", err);
-            Err(ApiError::Unknown {
-                trace_id: trace_id.clone(),
-            })
+/// Blocked users are filtered by the query, so they do not take up any of the results.
+async fn find_users(
+    users: &dyn UsersRepository,
+    username: &str,
+    searcher_id: Option<UserId>,
+    trace_id: &TraceId,
+) -> Result<Vec<User>, ApiError> {
+    users.search_users_by_username(username, searcher_id).await.map_err(|err| {
+        tracing::error!("failed to search users: {}", err);
+        ApiError::Unknown {
+            trace_id: trace_id.clone(),
         }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::users::MockUsersRepository;
+
+    #[tokio::test]
+    async fn test_find_users_passes_searcher_to_query() {
+        let mut users = MockUsersRepository::new();
+        users
+            .expect_search_users_by_username()
+            .withf(|username, searcher_id| username == "alice" && *searcher_id == Some(UserId::new(1)))
+            .times(1)
+            .returning(|_, _| Ok(Vec::new()));
+        users
+            .expect_search_users_by_username()
+            .withf(|username, searcher_id| username == "bob" && searcher_id.is_none())
+            .times(1)
+            .returning(|_, _| Ok(Vec::new()));
+
+        // blocked users are left out by the query, before the page is cut
+        assert!(find_users(&users, "alice", Some(UserId::new(1)), &TraceId::new()).await.is_ok());
+        assert!(find_users(&users, "bob", None, &TraceId::new()).await.is_ok());
     }
-}
\ No newline at end of file
+}
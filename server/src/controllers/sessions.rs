@@ -0,0 +1,131 @@
+use std::sync::Arc;
+use axum::{
+    Extension,
+    extract::{Path, State},
+};
+use crate::{
+    AppState,
+    error::{ApiError, RepositoryError},
+    services::{
+        auth::Auth,
+        session,
+        trace::TraceId,
+    },
+    models::sessions::{
+        SessionId,
+        PublicSession,
+        GetSessionsResponse,
+        RemoveSessionsResponse,
+    },
+};
+
+/// Get active sessions of the current user
+#[utoipa::path(
+    get,
+    path = "/sessions",
+    tag = "sessions",
+    responses(
+        (status = OK, description = "User sessions", body = GetSessionsResponse),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError, example = json!({"type": "Forbidden", "trace_id": "aa23dcd356c"})),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError, example = json!({"type": "Unknown", "trace_id": "aa23dcd356c"}))
+    ),
+    security(("auth" = []))
+)]
+pub async fn get_sessions(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<GetSessionsResponse, ApiError> {
+    let (_, current) = auth.require_session(&trace_id)?;
+
+    tracing::trace!("getting sessions for user {}", auth.user.id);
+    match state.sessions.get_user_sessions(auth.user.id).await {
+        Ok(sessions) => Ok(GetSessionsResponse(
+            sessions
+                .into_iter()
+                .map(|session| PublicSession::new(session, current))
+                .collect(),
+        )),
+
+        Err(err) => {
+            tracing::error!("failed to get user sessions: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Revoke session
+#[utoipa::path(
+    delete,
+    path = "/sessions/{session_id}",
+    tag = "sessions",
+    params(
+        ("session_id" = SessionId, Path, description = "Session id")
+    ),
+    responses(
+        (status = NO_CONTENT, description = "Session revoked", body = RemoveSessionsResponse),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError, example = json!({"type": "Forbidden", "trace_id": "aa23dcd356c"})),
+        (status = NOT_FOUND, description = "Session not found", body = ApiError, example = json!({"type": "NotFound", "trace_id": "aa23dcd356c"})),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError, example = json!({"type": "Unknown", "trace_id": "aa23dcd356c"}))
+    ),
+    security(("auth" = []))
+)]
+pub async fn remove_session(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<SessionId>,
+) -> Result<RemoveSessionsResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    match state.sessions.remove_user_session(auth.user.id, session_id).await {
+        Ok(_) => {
+            tracing::info!("session {session_id} of user {} revoked", auth.user.id);
+            session::close_streams(&state.events, auth.user.id, vec![session_id]);
+            Ok(RemoveSessionsResponse)
+        }
+
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("session {session_id} of user {} not found", auth.user.id);
+            Err(ApiError::NotFound { trace_id })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to revoke session: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Revoke all sessions except the current one
+#[utoipa::path(
+    delete,
+    path = "/sessions",
+    tag = "sessions",
+    responses(
+        (status = NO_CONTENT, description = "Other sessions revoked", body = RemoveSessionsResponse),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError, example = json!({"type": "Forbidden", "trace_id": "aa23dcd356c"})),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError, example = json!({"type": "Unknown", "trace_id": "aa23dcd356c"}))
+    ),
+    security(("auth" = []))
+)]
+pub async fn remove_other_sessions(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<RemoveSessionsResponse, ApiError> {
+    let (_, current) = auth.require_session(&trace_id)?;
+
+    match state.sessions.remove_other_sessions(auth.user.id, current).await {
+        Ok(removed) => {
+            tracing::info!("{} sessions of user {} revoked", removed.len(), auth.user.id);
+            session::close_streams(&state.events, auth.user.id, removed);
+            Ok(RemoveSessionsResponse)
+        }
+
+        Err(err) => {
+            tracing::error!("failed to revoke sessions: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
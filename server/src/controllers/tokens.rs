@@ -0,0 +1,196 @@
+use std::{collections::HashMap, sync::Arc};
+use axum::{
+    Json,
+    Extension,
+    extract::{Path, State},
+};
+use time::{Duration, OffsetDateTime};
+use crate::{
+    AppState,
+    error::{ApiError, RepositoryError},
+    services::{
+        auth::{Auth, hash_api_token},
+        trace::TraceId,
+    },
+    models::tokens::{
+        ApiTokenId,
+        NewTokenRequest,
+        NewTokenResponse,
+        GetTokensResponse,
+        RemoveTokenResponse,
+        API_TOKEN_PREFIX,
+    },
+};
+
+/// Get personal API tokens
+#[utoipa::path(
+    get,
+    path = "/tokens",
+    tag = "tokens",
+    responses(
+        (status = OK, description = "User API tokens", body = GetTokensResponse),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError, example = json!({"type": "Forbidden", "trace_id": "aa23dcd356c"})),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError, example = json!({"type": "Unknown", "trace_id": "aa23dcd356c"}))
+    ),
+    security(("auth" = []))
+)]
+pub async fn get_tokens(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<GetTokensResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    match state.tokens.get_user_tokens(auth.user.id).await {
+        Ok(tokens) => Ok(GetTokensResponse(tokens)),
+        Err(err) => {
+            tracing::error!("failed to get user tokens: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Create personal API token
+///
+/// The secret is returned only once, only its hash is stored.
+#[utoipa::path(
+    post,
+    path = "/tokens",
+    tag = "tokens",
+    request_body = NewTokenRequest,
+    responses(
+        (status = CREATED, description = "Token created", body = NewTokenResponse),
+        (status = BAD_REQUEST, description = "Validation error", example = json!({"type": "Validation", "fields": {"name": ["Name is empty"]}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError, example = json!({"type": "Forbidden", "trace_id": "aa23dcd356c"})),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError, example = json!({"type": "Unknown", "trace_id": "aa23dcd356c"}))
+    ),
+    security(("auth" = []))
+)]
+pub async fn new_token(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<NewTokenRequest>,
+) -> Result<NewTokenResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    let errors = validate_token(&req);
+    if !errors.is_empty() {
+        return Err(ApiError::Validation {
+            fields: errors,
+            trace_id,
+        });
+    }
+
+    let token = format!("{API_TOKEN_PREFIX}{}", state.random.lock().await.get_api_token());
+    let expires_at = req.expires_in_days.map(|days| {
+        OffsetDateTime::now_utc().saturating_add(Duration::days(days.into()))
+    });
+
+    match state
+        .tokens
+        .create_token(auth.user.id, req.name.trim(), &hash_api_token(&token), &req.scopes, expires_at)
+        .await
+    {
+        Ok(info) => {
+            tracing::info!("API token {} created for user {}", info.id, auth.user.id);
+            Ok(NewTokenResponse { token, info })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to create API token: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Revoke personal API token
+#[utoipa::path(
+    delete,
+    path = "/tokens/{token_id}",
+    tag = "tokens",
+    params(
+        ("token_id" = ApiTokenId, Path, description = "Token id")
+    ),
+    responses(
+        (status = NO_CONTENT, description = "Token revoked", body = RemoveTokenResponse),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError, example = json!({"type": "Forbidden", "trace_id": "aa23dcd356c"})),
+        (status = NOT_FOUND, description = "Token not found", body = ApiError, example = json!({"type": "NotFound", "trace_id": "aa23dcd356c"})),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError, example = json!({"type": "Unknown", "trace_id": "aa23dcd356c"}))
+    ),
+    security(("auth" = []))
+)]
+pub async fn remove_token(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Path(token_id): Path<ApiTokenId>,
+) -> Result<RemoveTokenResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    match state.tokens.remove_token(auth.user.id, token_id).await {
+        Ok(_) => {
+            tracing::info!("API token {token_id} of user {} revoked", auth.user.id);
+            Ok(RemoveTokenResponse)
+        }
+
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("API token {token_id} of user {} not found", auth.user.id);
+            Err(ApiError::NotFound { trace_id })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to revoke API token: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+fn validate_token(req: &NewTokenRequest) -> HashMap<String, Vec<String>> {
+    let mut errors = HashMap::new();
+    let name_errors = req.name.validate();
+    if !name_errors.is_empty() {
+        errors.insert("name".to_owned(), name_errors);
+    }
+
+    if req.scopes.is_empty() {
+        errors.insert("scopes".to_owned(), vec!["At least one scope is required".to_owned()]);
+    }
+
+    if req.expires_in_days == Some(0) {
+        errors.insert("expires_in_days".to_owned(), vec!["Lifetime must be at least one day".to_owned()]);
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::tokens::{Scope, TokenName};
+
+    #[test]
+    fn test_validate_token_ok() {
+        let req = NewTokenRequest {
+            name: TokenName::new("deploy script"),
+            scopes: vec![Scope::SendMessages],
+            expires_in_days: Some(30),
+        };
+
+        assert!(validate_token(&req).is_empty());
+    }
+
+    #[test]
+    fn test_validate_token_invalid() {
+        let req = NewTokenRequest {
+            name: TokenName::new(" "),
+            scopes: Vec::new(),
+            expires_in_days: Some(0),
+        };
+
+        let errors = validate_token(&req);
+        assert!(errors.contains_key("name"));
+        assert!(errors.contains_key("scopes"));
+        assert!(errors.contains_key("expires_in_days"));
+    }
+}
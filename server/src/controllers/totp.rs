@@ -0,0 +1,241 @@
+use std::{collections::HashMap, sync::Arc};
+use axum::{
+    Json,
+    Extension,
+    extract::State,
+};
+use time::OffsetDateTime;
+use crate::{
+    AppState,
+    error::{ApiError, RepositoryError},
+    services::{
+        auth::Auth,
+        totp,
+        trace::TraceId,
+    },
+    models::totp::{
+        Totp,
+        TotpCode,
+        ConfirmTotpRequest,
+        ConfirmTotpResponse,
+        DisableTotpRequest,
+        DisableTotpResponse,
+        EnrollTotpResponse,
+        RECOVERY_CODES_COUNT,
+    },
+};
+
+/// Start TOTP enrollment
+///
+/// Two-factor authentication stays disabled until a code is confirmed,
+/// calling this again replaces an unconfirmed secret.
+#[utoipa::path(
+    post,
+    path = "/account/totp",
+    tag = "totp",
+    responses(
+        (status = CREATED, description = "Secret generated", body = EnrollTotpResponse),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError),
+        (status = CONFLICT, description = "TOTP is already enabled", body = ApiError, example = json!({"type": "Conflict", "trace_id": "aa23dcd356c"})),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn enroll_totp(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<EnrollTotpResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    let secret = state.random.lock().await.get_totp_secret();
+    match state.totp.set_secret(auth.user.id, &secret).await {
+        Ok(_) => {
+            tracing::info!("TOTP enrollment started for user {}", auth.user.id);
+            Ok(EnrollTotpResponse {
+                otpauth_uri: totp::otpauth_uri(&auth.user.username, &secret),
+                secret,
+            })
+        }
+
+        Err(RepositoryError::Conflict) => {
+            tracing::warn!("TOTP already enabled for user {}", auth.user.id);
+            Err(ApiError::Conflict { trace_id })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to store TOTP secret: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Confirm TOTP enrollment
+///
+/// Enables two-factor authentication and returns the recovery codes.
+#[utoipa::path(
+    post,
+    path = "/account/totp/confirm",
+    tag = "totp",
+    request_body = ConfirmTotpRequest,
+    responses(
+        (status = OK, description = "TOTP enabled", body = ConfirmTotpResponse),
+        (status = BAD_REQUEST, description = "Invalid code", body = ApiError, example = json!({"type": "Validation", "fields": {"code": ["Invalid code"]}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError),
+        (status = NOT_FOUND, description = "Enrollment not started", body = ApiError),
+        (status = CONFLICT, description = "TOTP is already enabled", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn confirm_totp(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ConfirmTotpRequest>,
+) -> Result<ConfirmTotpResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+    validate_code(&req.code, &trace_id)?;
+
+    let enrollment = get_totp(&state, &auth, &trace_id).await?;
+    if enrollment.enabled {
+        tracing::warn!("TOTP already enabled for user {}", auth.user.id);
+        return Err(ApiError::Conflict { trace_id });
+    }
+
+    let now = OffsetDateTime::now_utc();
+    let Some(step) = totp::matching_step(&enrollment.secret, req.code.as_ref(), now) else {
+        tracing::warn!("invalid TOTP confirmation code for user {}", auth.user.id);
+        return Err(invalid_code(trace_id));
+    };
+
+    let recovery_codes = {
+        let mut random = state.random.lock().await;
+        (0..RECOVERY_CODES_COUNT)
+            .map(|_| random.get_recovery_code())
+            .collect::<Vec<_>>()
+    };
+
+    let hashes = recovery_codes
+        .iter()
+        .map(|code| totp::hash_recovery_code(code))
+        .collect::<Vec<_>>();
+
+    match state.totp.enable_totp(auth.user.id, step, &hashes).await {
+        Ok(_) => {
+            tracing::info!("TOTP enabled for user {}", auth.user.id);
+            Ok(ConfirmTotpResponse { recovery_codes })
+        }
+
+        Err(RepositoryError::Conflict) => {
+            tracing::warn!("TOTP already enabled for user {}", auth.user.id);
+            Err(ApiError::Conflict { trace_id })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to enable TOTP: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Disable TOTP
+///
+/// Requires a current TOTP code or an unused recovery code.
+#[utoipa::path(
+    delete,
+    path = "/account/totp",
+    tag = "totp",
+    request_body = DisableTotpRequest,
+    responses(
+        (status = NO_CONTENT, description = "TOTP disabled", body = DisableTotpResponse),
+        (status = BAD_REQUEST, description = "Invalid code", body = ApiError, example = json!({"type": "Validation", "fields": {"code": ["Invalid code"]}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError),
+        (status = NOT_FOUND, description = "TOTP is not enabled", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn disable_totp(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<DisableTotpRequest>,
+) -> Result<DisableTotpResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+    validate_code(&req.code, &trace_id)?;
+
+    let enrollment = get_totp(&state, &auth, &trace_id).await?;
+    if !enrollment.enabled {
+        tracing::warn!("TOTP not enabled for user {}", auth.user.id);
+        return Err(ApiError::NotFound { trace_id });
+    }
+
+    let now = OffsetDateTime::now_utc();
+    match totp::check_code(&*state.totp, &enrollment, req.code.as_ref(), now).await {
+        Ok(true) => {}
+        Ok(false) => {
+            tracing::warn!("invalid TOTP code for user {}", auth.user.id);
+            return Err(invalid_code(trace_id));
+        }
+
+        Err(err) => {
+            tracing::error!("failed to check TOTP code: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    }
+
+    match state.totp.disable_totp(auth.user.id).await {
+        Ok(_) => {
+            tracing::info!("TOTP disabled for user {}", auth.user.id);
+            Ok(DisableTotpResponse)
+        }
+
+        Err(err) => {
+            tracing::error!("failed to disable TOTP: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+async fn get_totp(
+    state: &AppState,
+    auth: &Auth,
+    trace_id: &TraceId,
+) -> Result<Totp, ApiError> {
+    match state.totp.get_totp(auth.user.id).await {
+        Ok(enrollment) => Ok(enrollment),
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("no TOTP enrollment for user {}", auth.user.id);
+            Err(ApiError::NotFound {
+                trace_id: trace_id.clone(),
+            })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to get TOTP enrollment: {err}");
+            Err(ApiError::Unknown {
+                trace_id: trace_id.clone(),
+            })
+        }
+    }
+}
+
+fn validate_code(code: &TotpCode, trace_id: &TraceId) -> Result<(), ApiError> {
+    let errors = code.validate();
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    Err(ApiError::Validation {
+        fields: HashMap::from([("code".to_owned(), errors)]),
+        trace_id: trace_id.clone(),
+    })
+}
+
+pub(crate) fn invalid_code(trace_id: TraceId) -> ApiError {
+    ApiError::Validation {
+        fields: HashMap::from([("code".to_owned(), vec!["Invalid code".to_owned()])]),
+        trace_id,
+    }
+}
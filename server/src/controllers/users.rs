@@ -4,8 +4,10 @@ use time::{Duration, OffsetDateTime};
 use crate::{
     rand::RandomGenerator,
     state::AppState,
+    controllers::{export, profiles, totp::invalid_code},
     repositories::{
         sessions::SessionsRepository,
+        totp::TotpRepository,
         users::UsersRepository,
     },
     error::{
@@ -14,17 +16,31 @@ use crate::{
     },
     services::{
         auth::Auth,
+        session,
         trace::TraceId,
+        totp,
+        password::{self, PasswordHasher, Verification},
+        throttle::{Throttle, ThrottleKey},
     },
-    models::users::{
-        UserId,
-        Username,
-        PasswordHash,
-        GetUserResponse,
-        LoginUserRequest,
-        LoginUserResponse,
-        LogoutUserResponse,
-        SESSION_LIFETIME,
+    models::{
+        sessions::ClientInfo,
+        totp::{LoginTotpRequest, Totp, MAX_PENDING_ATTEMPTS, PENDING_LOGIN_LIFETIME},
+        users::{
+            User,
+            UserId,
+            PasswordHash,
+            GetUserResponse,
+            LoginUserRequest,
+            LoginUserResponse,
+            LogoutUserResponse,
+            ChangePasswordRequest,
+            ChangeUsernameRequest,
+            DeleteAccountRequest,
+            UpdateAccountResponse,
+            DeleteAccountResponse,
+            SESSION_LIFETIME,
+            EXTERNAL_AUTH_MAX_AGE,
+        },
     },
 };
 
@@ -38,12 +54,14 @@ use crate::{
     responses(
         (status = OK, description = "User created", body = LoginUserResponse),
         (status = BAD_REQUEST, description = "Invalid user credentials", body = ApiError),
+        (status = TOO_MANY_REQUESTS, description = "Too many registrations from this client", body = ApiError),
         (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
     )
 )]
 pub async fn new_user(
     Extension(trace_id): Extension<TraceId>,
     State(state): State<Arc<AppState>>,
+    client: ClientInfo,
     Json(user): Json<LoginUserRequest>,
 ) -> Result<LoginUserResponse, ApiError> {
     tracing::trace!("validationg user credentials");
@@ -57,8 +75,18 @@ pub async fn new_user(
         });
     }
 
-    let user_id = create_user(&state.random, &*state.users, &user, &trace_id).await?;
-    let session = create_session(&*state.sessions, user_id, &trace_id).await?;
+    let keys = client
+        .ip
+        .iter()
+        .map(|ip| ThrottleKey::Registration(ip.clone()))
+        .collect::<Vec<_>>();
+
+    check_throttle(&state.throttle, &keys, &trace_id).await?;
+    state.throttle.record(&keys, OffsetDateTime::now_utc()).await;
+
+    let user_id = create_user(&state.random, &state.hasher, &*state.users, &user, &trace_id).await?;
+    let session =
+        create_session(&state.random, &*state.sessions, user_id, &client, None, &trace_id).await?;
 
     Ok(LoginUserResponse::new(user_id, session))
 }
@@ -76,7 +104,7 @@ pub async fn new_user(
         (status = NOT_FOUND, description = "User not found", body = ApiError),
         (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
     ),
-    security(("auth" = []))
+    security(("auth" = []), ("token" = []))
 )]
 pub async fn get_user(
     Extension(auth): Extension<Arc<Auth>>,
@@ -84,33 +112,39 @@ pub async fn get_user(
     State(state): State<Arc<AppState>>,
     Path(user_id): Path<UserId>,
 ) -> Result<GetUserResponse, ApiError> {
-    if user_id == 0 {
-        Ok(GetUserResponse { user: auth.user.clone().into() })
+    let user = if user_id == 0 {
+        auth.user.clone()
     } else {
         match state.users.get_user_by_id(&user_id).await {
             Ok(user) => {
                 tracing::info!("user {} found", user.username);
-                Ok(GetUserResponse { user: user.into() })
+                user
             }
 
             Err(RepositoryError::NotFound) => {
                 tracing::warn!("user {} not found", user_id);
-                Err(ApiError::NotFound {
+                return Err(ApiError::NotFound {
                     trace_id: trace_id.clone(),
-                })
+                });
             }
 
             Err(err) => {
                 tracing::error!("failed to get user: {}", err);
-                Err(ApiError::Unknown {
+                return Err(ApiError::Unknown {
                     trace_id: trace_id.clone(),
-                })
+                });
             }
         }
-    }
+    };
+
+    let mut users = profiles::public_users(&*state.profiles, vec![user], &trace_id).await?;
+    Ok(GetUserResponse { user: users.remove(0) })
 }
 
 /// Login user
+///
+/// When two-factor authentication is enabled no session is created,
+/// the response carries a pending token for `/login/totp` instead.
 #[utoipa::path(
     post,
     path = "/login",
@@ -120,30 +154,134 @@ pub async fn get_user(
         description = "User credentials"
     ),
     responses(
-        (status = OK, description = "User logged in", body = LoginUserResponse),
-        (status = BAD_REQUEST, description = "Invalid user credentials", body = ApiError),
+        (status = CREATED, description = "User logged in", body = LoginUserResponse),
+        (status = OK, description = "Second factor required", body = LoginUserResponse, example = json!({"user_id": 1, "second_factor_required": true, "pending_token": "5d1c0f..."})),
+        (status = FORBIDDEN, description = "Account suspended", body = ApiError),
+        (status = NOT_FOUND, description = "Unknown user or wrong password", body = ApiError),
+        (status = TOO_MANY_REQUESTS, description = "Too many failed attempts", body = ApiError, example = json!({"type": "TooManyRequests", "retry_after": 30, "trace_id": "aa23dcd356c"})),
         (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
     )
 )]
 pub async fn login_user(
     Extension(trace_id): Extension<TraceId>,
     State(state): State<Arc<AppState>>,
+    client: ClientInfo,
     Json(user): Json<LoginUserRequest>,
 ) -> Result<LoginUserResponse, ApiError> {
+    let keys = login_keys(&user, &client);
+    check_throttle(&state.throttle, &keys, &trace_id).await?;
+
     tracing::trace!("getting user id by name {}...", *user.username);
-    let user_id = get_user_id(&*state.users, &user, &trace_id).await?;
-    let session = get_or_create_session(&*state.sessions, user_id, &trace_id).await?;
+    let user_id = match get_user_id(&state.random, &state.hasher, &*state.users, &user, &trace_id).await {
+        Ok(user_id) => user_id,
+        Err(err @ ApiError::NotFound { .. }) => {
+            state.throttle.record(&keys, OffsetDateTime::now_utc()).await;
+            return Err(err);
+        }
+
+        Err(err) => return Err(err),
+    };
+
+    // only the account counter is cleared, a client can't reset its own by logging into another account
+    state.throttle.reset(&keys[0]).await;
+    if totp_enabled(&*state.totp, user_id, &trace_id).await? {
+        let pending_token =
+            create_pending_login(&state.random, &*state.totp, user_id, &trace_id).await?;
+        return Ok(LoginUserResponse::second_factor(user_id, pending_token));
+    }
+
+    let session =
+        create_session(&state.random, &*state.sessions, user_id, &client, None, &trace_id).await?;
 
     Ok(LoginUserResponse::new(user_id, session))
 }
 
+/// Finish login with a TOTP or recovery code
+#[utoipa::path(
+    post,
+    path = "/login/totp",
+    tag = "users",
+    request_body = LoginTotpRequest,
+    responses(
+        (status = CREATED, description = "User logged in", body = LoginUserResponse),
+        (status = BAD_REQUEST, description = "Invalid code", body = ApiError, example = json!({"type": "Validation", "fields": {"code": ["Invalid code"]}, "trace_id": "aa23dcd356c"})),
+        (status = NOT_FOUND, description = "Pending login expired or unknown", body = ApiError),
+        (status = TOO_MANY_REQUESTS, description = "Too many invalid codes for the account or from the client", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn login_totp(
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Json(req): Json<LoginTotpRequest>,
+) -> Result<LoginUserResponse, ApiError> {
+    let now = OffsetDateTime::now_utc();
+    let pending = match state.totp.get_pending_login(&req.pending_token).await {
+        Ok(pending) if pending.expires_at > now && pending.attempts < MAX_PENDING_ATTEMPTS => pending,
+        Ok(_) | Err(RepositoryError::NotFound) => {
+            tracing::warn!("pending login not found or expired");
+            return Err(ApiError::NotFound { trace_id });
+        }
+
+        Err(err) => {
+            tracing::error!("failed to get pending login: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    };
+
+    let enrollment = match state.totp.get_totp(pending.user_id).await {
+        Ok(enrollment) if enrollment.enabled => enrollment,
+        Ok(_) | Err(RepositoryError::NotFound) => {
+            tracing::warn!("TOTP of user {} was disabled during login", pending.user_id);
+            return Err(ApiError::NotFound { trace_id });
+        }
+
+        Err(err) => {
+            tracing::error!("failed to get TOTP enrollment: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    };
+
+    check_second_factor(
+        &state.throttle,
+        &*state.totp,
+        &enrollment,
+        &req.pending_token,
+        req.code.as_ref(),
+        &client,
+        &trace_id,
+    )
+    .await?;
+
+    // only one request may turn the pending login into a session
+    match state.totp.remove_pending_login(&req.pending_token).await {
+        Ok(_) => {}
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("pending login of user {} already used", pending.user_id);
+            return Err(ApiError::NotFound { trace_id });
+        }
+
+        Err(err) => {
+            tracing::error!("failed to remove pending login: {err}");
+            return Err(ApiError::Unknown { trace_id });
+        }
+    }
+
+    let session =
+        create_session(&state.random, &*state.sessions, pending.user_id, &client, None, &trace_id).await?;
+
+    Ok(LoginUserResponse::new(pending.user_id, session))
+}
+
 /// Logout user
 #[utoipa::path(
-    get,
+    post,
     path = "/logout",
     tag = "users",
     responses(
-        (status = OK, description = "User logged out", body = LogoutUserResponse),
+        (status = SEE_OTHER, description = "User logged out", body = LogoutUserResponse),
+        (status = FORBIDDEN, description = "Cross-site request", body = ApiError),
         (status = NOT_FOUND, description = "Session not found", body = ApiError),
         (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
     ),
@@ -154,16 +292,18 @@ pub async fn logout_user(
     Extension(trace_id): Extension<TraceId>,
     State(state): State<Arc<AppState>>,
 ) -> Result<LogoutUserResponse, ApiError> {
+    let (uid, session_id) = auth.require_session(&trace_id)?;
     tracing::trace!("logging out user {}...", auth.user.id);
 
-    match state.sessions.remove_session(&auth.session).await {
+    match state.sessions.remove_session(uid).await {
         Ok(_) => {
-            tracing::info!("session {} deleted", auth.session);
+            tracing::info!("session {} deleted", session_id);
+            session::close_streams(&state.events, auth.user.id, vec![session_id]);
             Ok(LogoutUserResponse)
         }
 
         Err(RepositoryError::NotFound) => {
-            tracing::warn!("session {} not found", auth.session);
+            tracing::warn!("session {} not found", session_id);
             Err(ApiError::NotFound {
                 trace_id: trace_id.clone(),
             })
@@ -178,6 +318,224 @@ pub async fn logout_user(
     }
 }
 
+/// Change password
+///
+/// Every other session of the user is revoked. Accounts created by an identity provider
+/// confirm the change by logging in with the provider shortly before instead of `current_password`,
+/// and only get a password when the server allows it.
+#[utoipa::path(
+    put,
+    path = "/account/password",
+    tag = "users",
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = NO_CONTENT, description = "Password changed", body = UpdateAccountResponse),
+        (status = BAD_REQUEST, description = "Wrong current password, no recent identity provider login or invalid new password", body = ApiError, example = json!({"type": "Validation", "fields": {"current_password": ["Wrong password"]}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn change_password(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ChangePasswordRequest>,
+) -> Result<UpdateAccountResponse, ApiError> {
+    let (_, current) = auth.require_session(&trace_id)?;
+
+    let password_errors = req.new_password.validate();
+    if !password_errors.is_empty() {
+        return Err(ApiError::Validation {
+            fields: HashMap::from([("new_password".to_owned(), password_errors)]),
+            trace_id,
+        });
+    }
+
+    if auth.user.password.is_empty() && !state.identity_providers.allows_local_passwords() {
+        tracing::warn!("user {} of an identity provider tried to set a password", auth.user.id);
+        return Err(ApiError::Validation {
+            fields: HashMap::from([(
+                "new_password".to_owned(),
+                vec!["Accounts of identity providers can't have a password".to_owned()],
+            )]),
+            trace_id,
+        });
+    }
+
+    confirm_user(
+        &state.random,
+        &state.hasher,
+        &*state.users,
+        &auth,
+        &req.current_password,
+        "current_password",
+        &trace_id,
+    )
+    .await?;
+
+    let hash = hash_password(&state.random, &state.hasher, &req.new_password, &trace_id).await?;
+    if let Err(err) = state.users.update_password(auth.user.id, hash).await {
+        tracing::error!("failed to update password: {err}");
+        return Err(ApiError::Unknown { trace_id });
+    }
+
+    tracing::info!("password of user {} changed", auth.user.id);
+    match state.sessions.remove_other_sessions(auth.user.id, current).await {
+        Ok(removed) => {
+            tracing::info!("{} sessions of user {} revoked", removed.len(), auth.user.id);
+            session::close_streams(&state.events, auth.user.id, removed);
+            Ok(UpdateAccountResponse)
+        }
+
+        Err(err) => {
+            tracing::error!("failed to revoke sessions: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Change username
+#[utoipa::path(
+    put,
+    path = "/account/username",
+    tag = "users",
+    request_body = ChangeUsernameRequest,
+    responses(
+        (status = NO_CONTENT, description = "Username changed", body = UpdateAccountResponse),
+        (status = BAD_REQUEST, description = "Invalid username", body = ApiError),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError),
+        (status = CONFLICT, description = "Username is taken", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn change_username(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ChangeUsernameRequest>,
+) -> Result<UpdateAccountResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    let username_errors = req.username.validate();
+    if !username_errors.is_empty() {
+        return Err(ApiError::Validation {
+            fields: HashMap::from([("username".to_owned(), username_errors)]),
+            trace_id,
+        });
+    }
+
+    match state.users.update_username(auth.user.id, req.username.trim()).await {
+        Ok(_) => {
+            tracing::info!("user {} renamed to {}", auth.user.id, req.username.trim());
+            Ok(UpdateAccountResponse)
+        }
+
+        Err(RepositoryError::Conflict) => {
+            tracing::warn!("user {} already exists", req.username.trim());
+            Err(ApiError::Conflict { trace_id })
+        }
+
+        Err(err) => {
+            tracing::error!("failed to rename user: {err}");
+            Err(ApiError::Unknown { trace_id })
+        }
+    }
+}
+
+/// Delete account
+///
+/// Owned bots are deleted as well. Sent messages stay in their chats without a sender,
+/// chats left without members are deleted. Accounts created by an identity provider confirm
+/// the deletion by logging in with the provider shortly before instead of `password`.
+#[utoipa::path(
+    delete,
+    path = "/account",
+    tag = "users",
+    request_body = DeleteAccountRequest,
+    responses(
+        (status = NO_CONTENT, description = "Account deleted", body = DeleteAccountResponse),
+        (status = BAD_REQUEST, description = "Wrong password or no recent identity provider login", body = ApiError, example = json!({"type": "Validation", "fields": {"password": ["Wrong password"]}, "trace_id": "aa23dcd356c"})),
+        (status = FORBIDDEN, description = "Not available for API tokens", body = ApiError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ApiError)
+    ),
+    security(("auth" = []))
+)]
+pub async fn delete_account(
+    Extension(auth): Extension<Arc<Auth>>,
+    Extension(trace_id): Extension<TraceId>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<DeleteAccountRequest>,
+) -> Result<DeleteAccountResponse, ApiError> {
+    auth.require_session(&trace_id)?;
+
+    confirm_user(
+        &state.random,
+        &state.hasher,
+        &*state.users,
+        &auth,
+        &req.password,
+        "password",
+        &trace_id,
+    )
+    .await?;
+
+    if let Err(err) = state.users.delete_user(auth.user.id).await {
+        tracing::error!("failed to delete user: {err}");
+        return Err(ApiError::Unknown { trace_id });
+    }
+
+    tracing::info!("user {} deleted", auth.user.id);
+    // dropping the sender ends every open event stream of the user
+    state.events.remove(&auth.user.id);
+    export::discard_export(&state, auth.user.id).await;
+    Ok(DeleteAccountResponse)
+}
+
+/// Confirms a sensitive change with the password of the user.
+///
+/// Accounts created by an identity provider have none, their session must come from
+/// a provider login made within `EXTERNAL_AUTH_MAX_AGE` instead.
+async fn confirm_user(
+    rand: &tokio::sync::Mutex<dyn RandomGenerator>,
+    hasher: &PasswordHasher,
+    users: &dyn UsersRepository,
+    auth: &Auth,
+    password: &str,
+    field: &str,
+    trace_id: &TraceId,
+) -> Result<(), ApiError> {
+    if auth.user.password.is_empty() {
+        let max_age = Duration::seconds(EXTERNAL_AUTH_MAX_AGE);
+        if auth.has_recent_external_auth(max_age, OffsetDateTime::now_utc()) {
+            return Ok(());
+        }
+
+        tracing::warn!("user {} has no recent identity provider login", auth.user.id);
+        return Err(ApiError::Validation {
+            fields: HashMap::from([(
+                field.to_owned(),
+                vec!["Log in with your identity provider again".to_owned()],
+            )]),
+            trace_id: trace_id.clone(),
+        });
+    }
+
+    if check_password(rand, hasher, users, &auth.user, password, trace_id).await? {
+        Ok(())
+    } else {
+        Err(wrong_password(field, trace_id.clone()))
+    }
+}
+
+fn wrong_password(field: &str, trace_id: TraceId) -> ApiError {
+    ApiError::Validation {
+        fields: HashMap::from([(field.to_owned(), vec!["Wrong password".to_owned()])]),
+        trace_id,
+    }
+}
+
 fn validate_user(user: &LoginUserRequest) -> HashMap<String, Vec<String>> {
     let mut errors = HashMap::new();
     let username_errors = user.username.validate();
@@ -197,14 +555,12 @@ fn validate_user(user: &LoginUserRequest) -> HashMap<String, Vec<String>> {
 
 async fn create_user(
     rand: &tokio::sync::Mutex<dyn RandomGenerator>,
+    hasher: &PasswordHasher,
     users: &dyn UsersRepository,
     user: &LoginUserRequest,
     trace_id: &TraceId,
 ) -> Result<UserId, ApiError> {
-    tracing::trace!("hashing password...");
-
-    let salt = rand.lock().await.get_salt();
-    let password_hash = PasswordHash::new(&user.password, &salt);
+    let password_hash = hash_password(rand, hasher, &user.password, trace_id).await?;
 
     tracing::trace!("saving user credintials in database...");
     let result = users.create_user(&user.username, password_hash).await;
@@ -229,19 +585,38 @@ async fn create_user(
     }
 }
 
+pub(crate) async fn hash_password(
+    rand: &tokio::sync::Mutex<dyn RandomGenerator>,
+    hasher: &PasswordHasher,
+    password: &str,
+    trace_id: &TraceId,
+) -> Result<PasswordHash, ApiError> {
+    tracing::trace!("hashing password...");
+    let salt = rand.lock().await.get_salt();
+    match hasher.hash(password, &salt).await {
+        Ok(hash) => Ok(hash),
+        Err(err) => {
+            tracing::error!("failed to hash password: {err}");
+            Err(ApiError::Unknown {
+                trace_id: trace_id.clone(),
+            })
+        }
+    }
+}
+
 async fn get_user_salt(
     users: &dyn UsersRepository,
-    username: &Username,
+    username: &str,
     trace_id: &TraceId,
 ) -> Result<String, ApiError> {
     match users.get_user_salt(username).await {
         Ok(salt) => {
-            tracing::info!("user {} salt found", **username);
+            tracing::info!("user {} salt found", username);
             Ok(salt)
         }
 
         Err(RepositoryError::NotFound) => {
-            tracing::warn!("user {} not found", **username);
+            tracing::warn!("user {} not found", username);
             Err(ApiError::NotFound {
                 trace_id: trace_id.clone(),
             })
@@ -257,31 +632,235 @@ async fn get_user_salt(
 }
 
 async fn get_user_id(
+    rand: &tokio::sync::Mutex<dyn RandomGenerator>,
+    hasher: &PasswordHasher,
     users: &dyn UsersRepository,
     user: &LoginUserRequest,
     trace_id: &TraceId,
 ) -> Result<UserId, ApiError> {
-    tracing::trace!("getting user salt...");
+    tracing::trace!("getting user...");
+    let stored = match users.get_user_by_name(&user.username).await {
+        Ok(stored) => stored,
+        Err(RepositoryError::NotFound) => {
+            tracing::warn!("user {} not found", *user.username);
+            equalize_timing(rand, hasher, &user.password).await;
+            return Err(ApiError::NotFound {
+                trace_id: trace_id.clone(),
+            });
+        }
+
+        Err(err) => {
+            tracing::error!("failed to get user: {}", err);
+            return Err(ApiError::Unknown {
+                trace_id: trace_id.clone(),
+            });
+        }
+    };
+
+    if stored.is_bot {
+        tracing::warn!("password login attempted for bot {}", stored.username);
+        equalize_timing(rand, hasher, &user.password).await;
+        return Err(ApiError::NotFound {
+            trace_id: trace_id.clone(),
+        });
+    }
+
+    if !check_password(rand, hasher, users, &stored, &user.password, trace_id).await? {
+        return Err(ApiError::NotFound {
+            trace_id: trace_id.clone(),
+        });
+    }
+
+    // only told after the password matched, so suspension can't be probed
+    if stored.suspended_at.is_some() {
+        tracing::warn!("login attempted for suspended user {}", stored.username);
+        return Err(ApiError::Forbidden {
+            trace_id: trace_id.clone(),
+        });
+    }
+
+    tracing::info!("user {} found", stored.username);
+    Ok(stored.id)
+}
 
-    let salt = get_user_salt(users, &user.username, trace_id).await?;
-    let password_hash = PasswordHash::new(&user.password, &salt);
+/// Spends the time of a password check, so unknown users can't be told apart by response time.
+async fn equalize_timing(
+    rand: &tokio::sync::Mutex<dyn RandomGenerator>,
+    hasher: &PasswordHasher,
+    password: &str,
+) {
+    let salt = rand.lock().await.get_salt();
+    if let Err(err) = hasher.hash(password, &salt).await {
+        tracing::error!("failed to hash password: {err}");
+    }
+}
+
+fn login_keys(user: &LoginUserRequest, client: &ClientInfo) -> Vec<ThrottleKey> {
+    let mut keys = vec![ThrottleKey::Username(user.username.trim().to_owned())];
+    if let Some(ip) = &client.ip {
+        keys.push(ThrottleKey::Ip(ip.clone()));
+    }
 
-    tracing::trace!("getting user id...");
-    match users.get_user(&user.username, password_hash).await {
-        Ok(user) => {
-            tracing::info!("user {} found", user.username);
-            Ok(user.id)
+    keys
+}
+
+fn second_factor_keys(user_id: UserId, client: &ClientInfo) -> Vec<ThrottleKey> {
+    let mut keys = vec![ThrottleKey::SecondFactor(user_id)];
+    if let Some(ip) = &client.ip {
+        keys.push(ThrottleKey::Ip(ip.clone()));
+    }
+
+    keys
+}
+
+/// Checks the code of a pending login.
+///
+/// Failures are counted for the account and the client across all pending logins, so new
+/// pending tokens from repeated password logins do not bring fresh guesses.
+async fn check_second_factor(
+    throttle: &Throttle,
+    totp_repository: &dyn TotpRepository,
+    enrollment: &Totp,
+    pending_token: &str,
+    code: &str,
+    client: &ClientInfo,
+    trace_id: &TraceId,
+) -> Result<(), ApiError> {
+    let keys = second_factor_keys(enrollment.user_id, client);
+    check_throttle(throttle, &keys, trace_id).await?;
+
+    let now = OffsetDateTime::now_utc();
+    match totp::check_code(totp_repository, enrollment, code, now).await {
+        Ok(true) => {
+            throttle.reset(&keys[0]).await;
+            Ok(())
         }
 
-        Err(RepositoryError::NotFound) => {
-            tracing::warn!("user {} not found", *user.username);
-            Err(ApiError::NotFound {
+        Ok(false) => {
+            tracing::warn!("invalid TOTP code for user {}", enrollment.user_id);
+            throttle.record(&keys, now).await;
+            if let Err(err) = totp_repository.fail_pending_login(pending_token).await {
+                tracing::error!("failed to count TOTP attempt: {err}");
+            }
+
+            Err(invalid_code(trace_id.clone()))
+        }
+
+        Err(err) => {
+            tracing::error!("failed to check TOTP code: {err}");
+            Err(ApiError::Unknown {
                 trace_id: trace_id.clone(),
             })
         }
+    }
+}
+
+async fn check_throttle(
+    throttle: &Throttle,
+    keys: &[ThrottleKey],
+    trace_id: &TraceId,
+) -> Result<(), ApiError> {
+    let Some(retry_after) = throttle.check(keys, OffsetDateTime::now_utc()).await else {
+        return Ok(());
+    };
+
+    let retry_after = retry_after.as_seconds_f64().ceil() as u64;
+    tracing::warn!("attempt throttled for {retry_after} seconds: {keys:?}");
+    Err(ApiError::TooManyRequests {
+        retry_after,
+        trace_id: trace_id.clone(),
+    })
+}
+
+/// Checks `password` against the stored hash, upgrading outdated hashes on success.
+async fn check_password(
+    rand: &tokio::sync::Mutex<dyn RandomGenerator>,
+    hasher: &PasswordHasher,
+    users: &dyn UsersRepository,
+    stored: &User,
+    password: &str,
+    trace_id: &TraceId,
+) -> Result<bool, ApiError> {
+    tracing::trace!("verifying password...");
+    let verification = if password::is_legacy(&stored.password) {
+        let salt = get_user_salt(users, &stored.username, trace_id).await?;
+        password::verify_legacy(password, &stored.password, &salt)
+    } else {
+        hasher.verify(password, &stored.password).await
+    };
+
+    match verification {
+        Verification::Valid => Ok(true),
+        Verification::Outdated => {
+            rehash_password(rand, hasher, users, stored, password).await;
+            Ok(true)
+        }
+        Verification::Invalid => {
+            tracing::warn!("wrong password for user {}", stored.username);
+            Ok(false)
+        }
+    }
+}
 
+/// Replaces an outdated hash with one using the current parameters.
+///
+/// Failures are only logged, the user is already authenticated at this point.
+async fn rehash_password(
+    rand: &tokio::sync::Mutex<dyn RandomGenerator>,
+    hasher: &PasswordHasher,
+    users: &dyn UsersRepository,
+    user: &User,
+    password: &str,
+) {
+    tracing::trace!("upgrading password hash for user {}...", user.id);
+    let salt = rand.lock().await.get_salt();
+    let hash = match hasher.hash(password, &salt).await {
+        Ok(hash) => hash,
         Err(err) => {
-            tracing::error!("failed to get user: {}", err);
+            tracing::error!("failed to rehash password: {err}");
+            return;
+        }
+    };
+
+    match users.update_password(user.id, hash).await {
+        Ok(_) => tracing::info!("password hash upgraded for user {}", user.id),
+        Err(err) => tracing::error!("failed to upgrade password hash: {err}"),
+    }
+}
+
+async fn totp_enabled(
+    totp: &dyn TotpRepository,
+    user_id: UserId,
+    trace_id: &TraceId,
+) -> Result<bool, ApiError> {
+    match totp.get_totp(user_id).await {
+        Ok(enrollment) => Ok(enrollment.enabled),
+        Err(RepositoryError::NotFound) => Ok(false),
+        Err(err) => {
+            tracing::error!("failed to get TOTP enrollment: {err}");
+            Err(ApiError::Unknown {
+                trace_id: trace_id.clone(),
+            })
+        }
+    }
+}
+
+async fn create_pending_login(
+    rand: &tokio::sync::Mutex<dyn RandomGenerator>,
+    totp: &dyn TotpRepository,
+    user_id: UserId,
+    trace_id: &TraceId,
+) -> Result<String, ApiError> {
+    let uid = rand.lock().await.get_session_uid();
+    let expires_at = OffsetDateTime::now_utc().saturating_add(Duration::seconds(PENDING_LOGIN_LIFETIME));
+    match totp.create_pending_login(&uid, user_id, expires_at).await {
+        Ok(_) => {
+            tracing::info!("pending login created for user {}", user_id);
+            Ok(uid)
+        }
+
+        Err(err) => {
+            tracing::error!("failed to create pending login: {err}");
             Err(ApiError::Unknown {
                 trace_id: trace_id.clone(),
             })
@@ -289,27 +868,30 @@ async fn get_user_id(
     }
 }
 
-async fn create_session(
+pub(crate) async fn create_session(
+    rand: &tokio::sync::Mutex<dyn RandomGenerator>,
     sessions: &dyn SessionsRepository,
     user_id: UserId,
+    client: &ClientInfo,
+    external_auth_at: Option<OffsetDateTime>,
     trace_id: &TraceId,
 ) -> Result<String, ApiError> {
     for _ in 0..5 {
         tracing::trace!("generating session UID...");
-        let uid = small_uid::SmallUid::new().to_string();
+        let uid = rand.lock().await.get_session_uid();
         let expires_at = OffsetDateTime::now_utc().saturating_add(Duration::seconds(SESSION_LIFETIME));
-        tracing::trace!("trying to save session UID {uid} for user id {user_id} in database...");
-        match sessions.create_session(&uid, user_id, expires_at).await {
-            Ok(_) => {
-                tracing::info!("session {} created for user {}", uid, user_id);
+        tracing::trace!("trying to save session for user id {user_id} in database...");
+        match sessions.create_session(&uid, user_id, expires_at, client, external_auth_at).await {
+            Ok(id) => {
+                tracing::info!("session {} created for user {}", id, user_id);
                 return Ok(uid);
             }
             Err(RepositoryError::Conflict) => {
-                tracing::warn!("session UID {} collision for user {}", uid, user_id);
+                tracing::warn!("session UID collision for user {}", user_id);
                 continue;
             }
             Err(err) => {
-                tracing::error!("failed to create {} session: {}", uid, err);
+                tracing::error!("failed to create session for user {}: {}", user_id, err);
                 return Err(ApiError::Unknown {
                     trace_id: trace_id.clone(),
                 });
@@ -323,41 +905,62 @@ async fn create_session(
     })
 }
 
-async fn get_or_create_session(
-    sessions: &dyn SessionsRepository,
-    user_id: UserId,
-    trace_id: &TraceId,
-) -> Result<String, ApiError> {
-    match sessions.get_session_by_user_id(user_id).await {
-        Ok(session) => {
-            tracing::info!("session {session} found");
-            Ok(session)
-        }
-
-        Err(err) => {
-            tracing::error!("failed to get session: {err}");
-            tracing::trace!("trying to create new session");
-            create_session(sessions, user_id, trace_id).await
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fmt::Display;
+    use sha2::Digest;
+    use argon2::Params;
     use tokio::{sync::Mutex, test};
     use crate::{
         error::RepositoryError,
         rand::MockRandomGenerator,
-        models::users::{LoginUserRequest, Password, User, Username},
-        repositories::{sessions::MockSessionsRepository, users::MockUsersRepository},
+        services::auth::Credentials,
+        models::{
+            sessions::SessionId,
+            users::{LoginUserRequest, Password, User, Username},
+        },
+        repositories::{
+            sessions::MockSessionsRepository,
+            totp::MockTotpRepository,
+            users::MockUsersRepository,
+        },
     };
 
+    const SALT: &str = "0123456789abcdef0123456789abcdef";
+
+    fn hasher() -> PasswordHasher {
+        PasswordHasher::new(Params::new(8, 1, 1, None).unwrap())
+    }
+
+    fn salted_rand() -> Mutex<MockRandomGenerator> {
+        let mut rand = MockRandomGenerator::new();
+        rand.expect_get_salt().returning(|| SALT.to_string());
+        Mutex::new(rand)
+    }
+
+    fn session_rand() -> Mutex<MockRandomGenerator> {
+        let mut rand = MockRandomGenerator::new();
+        rand.expect_get_session_uid().returning(|| "session".to_string());
+        Mutex::new(rand)
+    }
+
+    fn stored_user(password: String) -> User {
+        User {
+            id: UserId::new(1),
+            username: "valid_user".into(),
+            password,
+            is_bot: false,
+            is_admin: false,
+            suspended_at: None,
+            created_at: OffsetDateTime::now_utc(),
+        }
+    }
+
     #[test]
     async fn test_create_user_ok() {
         let mut rand = MockRandomGenerator::new();
-        rand.expect_get_salt().returning(|| "salt".to_string());
+        rand.expect_get_salt().returning(|| SALT.to_string());
         let mut users = MockUsersRepository::new();
         users
             .expect_create_user()
@@ -368,7 +971,7 @@ mod tests {
             password: Password::new("ValidPass123"),
         };
 
-        let result = create_user(&Mutex::new(rand), &users, &user, &TraceId::new())
+        let result = create_user(&Mutex::new(rand), &hasher(), &users, &user, &TraceId::new())
             .await
             .expect("failed to create user");
         assert_eq!(result, 1);
@@ -380,7 +983,7 @@ mod tests {
         rand.lock()
             .await
             .expect_get_salt()
-            .returning(|| "salt".to_string());
+            .returning(|| SALT.to_string());
         let mut users = MockUsersRepository::new();
         users
             .expect_create_user()
@@ -391,7 +994,7 @@ mod tests {
             password: Password::new("ValidPass123"),
         };
 
-        let result = create_user(&rand, &users, &user, &TraceId::new()).await;
+        let result = create_user(&rand, &hasher(), &users, &user, &TraceId::new()).await;
         assert!(matches!(result, Err(ApiError::Conflict { .. })));
     }
 
@@ -442,20 +1045,17 @@ mod tests {
 
     #[test]
     async fn test_get_user_id_by_username_ok() {
+        let hasher = hasher();
+        let hash = hasher.hash("ValidPass123", SALT).await.unwrap();
         let mut users = MockUsersRepository::new();
         users
-            .expect_get_user_salt()
-            .returning(|_| Ok("salt".to_string()));
-        users.expect_get_user().returning(|username, password| {
-            Ok(User {
-                id: UserId::new(1),
-                username: username.into(),
-                password: password.to_string(),
-                created_at: OffsetDateTime::now_utc(),
-            })
-        });
+            .expect_get_user_by_name()
+            .returning(move |_| Ok(stored_user(hash.to_string())));
+        users.expect_update_password().never();
 
         let result = get_user_id(
+            &salted_rand(),
+            &hasher,
             &users,
             &LoginUserRequest {
                 username: Username::new("valid_user"),
@@ -471,14 +1071,235 @@ mod tests {
     #[test]
     async fn test_get_user_id_by_username_not_found() {
         let mut users = MockUsersRepository::new();
+        users
+            .expect_get_user_by_name()
+            .returning(|_| Err(RepositoryError::NotFound));
+
+        let result = get_user_id(
+            &salted_rand(),
+            &hasher(),
+            &users,
+            &LoginUserRequest {
+                username: Username::new("valid_user"),
+                password: Password::new("ValidPass123"),
+            },
+            &TraceId::new(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(ApiError::NotFound { .. })));
+    }
+
+    #[test]
+    async fn test_get_user_id_wrong_password() {
+        let hasher = hasher();
+        let hash = hasher.hash("ValidPass123", SALT).await.unwrap();
+        let mut users = MockUsersRepository::new();
+        users
+            .expect_get_user_by_name()
+            .returning(move |_| Ok(stored_user(hash.to_string())));
+
+        let result = get_user_id(
+            &salted_rand(),
+            &hasher,
+            &users,
+            &LoginUserRequest {
+                username: Username::new("valid_user"),
+                password: Password::new("WrongPass123"),
+            },
+            &TraceId::new(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(ApiError::NotFound { .. })));
+    }
+
+    #[test]
+    async fn test_check_password() {
+        let hasher = hasher();
+        let hash = hasher.hash("ValidPass123", SALT).await.unwrap();
+        let users = MockUsersRepository::new();
+        let stored = stored_user(hash.to_string());
+        let trace_id = TraceId::new();
+
+        let valid = check_password(&salted_rand(), &hasher, &users, &stored, "ValidPass123", &trace_id).await;
+        let invalid = check_password(&salted_rand(), &hasher, &users, &stored, "WrongPass123", &trace_id).await;
+
+        assert!(matches!(valid, Ok(true)));
+        assert!(matches!(invalid, Ok(false)));
+    }
+
+    fn session_auth(user: User, external_auth_at: Option<OffsetDateTime>) -> Auth {
+        Auth {
+            user,
+            credentials: Credentials::Session {
+                uid: "session".to_owned(),
+                id: SessionId::new(1),
+                external_auth_at,
+            },
+        }
+    }
+
+    #[test]
+    async fn test_confirm_user_without_password_needs_recent_external_login() {
+        let users = MockUsersRepository::new();
+        let trace_id = TraceId::new();
+        let now = OffsetDateTime::now_utc();
+
+        for external_auth_at in [None, Some(now - Duration::hours(1))] {
+            let auth = session_auth(stored_user(String::new()), external_auth_at);
+            let result = confirm_user(&salted_rand(), &hasher(), &users, &auth, "", "password", &trace_id).await;
+
+            let Err(ApiError::Validation { fields, .. }) = result else {
+                panic!("passwordless user confirmed without a recent external login");
+            };
+            assert!(fields.contains_key("password"));
+        }
+
+        let auth = session_auth(stored_user(String::new()), Some(now));
+        let result = confirm_user(&salted_rand(), &hasher(), &users, &auth, "", "password", &trace_id).await;
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    async fn test_confirm_user_with_password_ignores_external_login() {
+        let hasher = hasher();
+        let hash = hasher.hash("ValidPass123", SALT).await.unwrap();
+        let users = MockUsersRepository::new();
+        let trace_id = TraceId::new();
+        let auth = session_auth(stored_user(hash.to_string()), Some(OffsetDateTime::now_utc()));
+
+        let wrong = confirm_user(&salted_rand(), &hasher, &users, &auth, "WrongPass123", "password", &trace_id).await;
+        let valid = confirm_user(&salted_rand(), &hasher, &users, &auth, "ValidPass123", "password", &trace_id).await;
+
+        assert!(matches!(wrong, Err(ApiError::Validation { .. })));
+        assert!(valid.is_ok());
+    }
+
+    #[test]
+    async fn test_check_throttle_after_failed_logins() {
+        let throttle = Throttle::default();
+        let user = LoginUserRequest {
+            username: Username::new("alice"),
+            password: Password::new("WrongPass123"),
+        };
+        let client = ClientInfo {
+            user_agent: None,
+            ip: Some("10.0.0.1".to_owned()),
+        };
+        let keys = login_keys(&user, &client);
+        let now = OffsetDateTime::now_utc();
+
+        assert!(check_throttle(&throttle, &keys, &TraceId::new()).await.is_ok());
+        for _ in 0..5 {
+            throttle.record(&keys, now).await;
+        }
+
+        let result = check_throttle(&throttle, &keys, &TraceId::new()).await;
+        assert!(matches!(result, Err(ApiError::TooManyRequests { retry_after: 1, .. })));
+    }
+
+    #[test]
+    async fn test_second_factor_throttled_across_pending_logins() {
+        let throttle = Throttle::default();
+        let mut totp = MockTotpRepository::new();
+        totp.expect_fail_pending_login().returning(|_| Ok(()));
+        // every code is of an already used step, so none is accepted
+        let enrollment = Totp {
+            user_id: UserId::new(1),
+            secret: "JBSWY3DPEHPK3PXP".to_owned(),
+            enabled: true,
+            last_step: i64::MAX,
+        };
+        let client = ClientInfo {
+            user_agent: None,
+            ip: Some("10.0.0.1".to_owned()),
+        };
+        let trace_id = TraceId::new();
+
+        for pending_token in ["first", "first", "first", "second", "second"] {
+            let result =
+                check_second_factor(&throttle, &totp, &enrollment, pending_token, "123456", &client, &trace_id).await;
+            assert!(matches!(result, Err(ApiError::Validation { .. })));
+        }
+
+        // a correct password clears the account counter of /login, not the one of the second factor
+        throttle.reset(&ThrottleKey::Username("alice".to_owned())).await;
+
+        let other_client = ClientInfo {
+            user_agent: None,
+            ip: Some("10.0.0.2".to_owned()),
+        };
+        let result =
+            check_second_factor(&throttle, &totp, &enrollment, "third", "123456", &other_client, &trace_id).await;
+        assert!(matches!(result, Err(ApiError::TooManyRequests { .. })));
+    }
+
+    #[test]
+    async fn test_totp_enabled() {
+        let mut repository = MockTotpRepository::new();
+        repository.expect_get_totp().returning(|user_id| {
+            if user_id == 1 {
+                Err(RepositoryError::NotFound)
+            } else {
+                Ok(Totp {
+                    user_id,
+                    secret: "GEZDGNBV".to_owned(),
+                    enabled: user_id == 2,
+                    last_step: 0,
+                })
+            }
+        });
+
+        let trace_id = TraceId::new();
+        assert!(!totp_enabled(&repository, UserId::new(1), &trace_id).await.unwrap());
+        assert!(totp_enabled(&repository, UserId::new(2), &trace_id).await.unwrap());
+        assert!(!totp_enabled(&repository, UserId::new(3), &trace_id).await.unwrap());
+    }
+
+    #[test]
+    async fn test_get_user_id_rejects_bot() {
+        let mut users = MockUsersRepository::new();
+        users.expect_get_user_by_name().returning(|_| {
+            let mut bot = stored_user(String::new());
+            bot.is_bot = true;
+            Ok(bot)
+        });
+
+        let result = get_user_id(
+            &salted_rand(),
+            &hasher(),
+            &users,
+            &LoginUserRequest {
+                username: Username::new("valid_user"),
+                password: Password::new("ValidPass123"),
+            },
+            &TraceId::new(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(ApiError::NotFound { .. })));
+    }
+
+    #[test]
+    async fn test_get_user_id_upgrades_legacy_hash() {
+        let legacy = hex::encode(sha2::Sha256::digest(b"ValidPass123salt"));
+        let mut users = MockUsersRepository::new();
+        users
+            .expect_get_user_by_name()
+            .returning(move |_| Ok(stored_user(legacy.clone())));
         users
             .expect_get_user_salt()
             .returning(|_| Ok("salt".to_string()));
         users
-            .expect_get_user()
-            .returning(|_, _| Err(RepositoryError::NotFound));
+            .expect_update_password()
+            .withf(|id, hash| *id == 1 && hash.starts_with("$argon2id$"))
+            .times(1)
+            .returning(|_, _| Ok(()));
 
         let result = get_user_id(
+            &salted_rand(),
+            &hasher(),
             &users,
             &LoginUserRequest {
                 username: Username::new("valid_user"),
@@ -488,15 +1309,25 @@ mod tests {
         )
         .await;
 
-        assert!(matches!(result, Err(ApiError::NotFound { .. })));
+        assert_eq!(result.expect("failed to login legacy user"), 1);
     }
 
     #[test]
     async fn test_create_session_ok() {
         let mut sessions = MockSessionsRepository::new();
-        sessions.expect_create_session().returning(|_, _, _| Ok(()));
-
-        let result = create_session(&sessions, UserId::new(1), &TraceId::new()).await;
+        sessions
+            .expect_create_session()
+            .returning(|_, _, _, _, _| Ok(SessionId::new(1)));
+
+        let result = create_session(
+            &session_rand(),
+            &sessions,
+            UserId::new(1),
+            &ClientInfo::default(),
+            None,
+            &TraceId::new(),
+        )
+        .await;
         assert!(result.is_ok());
     }
 
@@ -505,9 +1336,17 @@ mod tests {
         let mut sessions = MockSessionsRepository::new();
         sessions
             .expect_create_session()
-            .returning(|_, _, _| Err(RepositoryError::Conflict));
-
-        let result = create_session(&sessions, UserId::new(1), &TraceId::new()).await;
+            .returning(|_, _, _, _, _| Err(RepositoryError::Conflict));
+
+        let result = create_session(
+            &session_rand(),
+            &sessions,
+            UserId::new(1),
+            &ClientInfo::default(),
+            None,
+            &TraceId::new(),
+        )
+        .await;
         assert!(matches!(result, Err(ApiError::Conflict { .. })));
     }
 
@@ -547,36 +1386,21 @@ mod tests {
         }
 
         let mut sessions = MockSessionsRepository::new();
-        sessions.expect_create_session().returning(|_, _, _| {
+        sessions.expect_create_session().returning(|_, _, _, _, _| {
             Err(RepositoryError::Unknown(sqlx::Error::Database(Box::new(
                 UnknownSqlxError,
             ))))
         });
 
-        let result = create_session(&sessions, UserId::new(1), &TraceId::new()).await;
+        let result = create_session(
+            &session_rand(),
+            &sessions,
+            UserId::new(1),
+            &ClientInfo::default(),
+            None,
+            &TraceId::new(),
+        )
+        .await;
         assert!(matches!(result, Err(ApiError::Unknown { .. })));
     }
-
-    #[test]
-    async fn test_get_or_create_session_ok() {
-        let mut sessions = MockSessionsRepository::new();
-        sessions
-            .expect_get_session_by_user_id()
-            .returning(|_| Ok("session".into()));
-
-        let result = get_or_create_session(&sessions, UserId::new(1), &TraceId::new()).await;
-        assert!(result.is_ok());
-    }
-
-    #[test]
-    async fn test_get_or_create_session_not_found() {
-        let mut sessions = MockSessionsRepository::new();
-        sessions
-            .expect_get_session_by_user_id()
-            .returning(|_| Err(RepositoryError::NotFound));
-        sessions.expect_create_session().returning(|_, _, _| Ok(()));
-
-        let result = get_or_create_session(&sessions, UserId::new(1), &TraceId::new()).await;
-        assert!(result.is_ok());
-    }
 }
\ No newline at end of file
@@ -3,7 +3,7 @@ use utoipa::{
     OpenApi,
     openapi::{
         ComponentsBuilder,
-        security::{ApiKey, ApiKeyValue, SecurityScheme},
+        security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
     },
 };
 
@@ -29,6 +29,18 @@ impl Modify for SecurityAddon {
             SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new("session"))),
         );
 
+        components.add_security_scheme(
+            "token",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some(
+                        "Personal or bot API token with the scopes read-messages, send-messages or manage-chats",
+                    ))
+                    .build(),
+            ),
+        );
+
         openapi.components = Some(components);
     }
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
-use crate::services::{trace::TraceId, auth::SESSION_COOKIE_NAME};
-use axum::{Json, http::StatusCode, response::IntoResponse};
+use crate::services::{trace::TraceId, cookies::removal_cookie};
+use axum::{Json, http::{HeaderValue, StatusCode, header}, response::IntoResponse};
 use std::{collections::HashMap, fmt::Display};
 use serde::Serialize;
 use utoipa::ToSchema;
@@ -27,6 +27,15 @@ pub enum ApiError {
     Forbidden {
         trace_id: TraceId,
     },
+    /// The resource changed since the version given in `If-Match`.
+    PreconditionFailed {
+        trace_id: TraceId,
+    },
+    TooManyRequests {
+        /// Seconds to wait before trying again, also sent as `Retry-After`.
+        retry_after: u64,
+        trace_id: TraceId,
+    },
 }
 
 impl IntoResponse for ApiError {
@@ -40,14 +49,22 @@ impl IntoResponse for ApiError {
                 return (
                     StatusCode::UNAUTHORIZED,
                     [
-                        ("Set-Cookie", format!("{SESSION_COOKIE_NAME}=_; Max-Age=0")),
-                        ("Location", "/unauthorized".to_owned()),
+                        (header::SET_COOKIE, removal_cookie()),
+                        (header::LOCATION, HeaderValue::from_static("/unauthorized")),
                     ],
                     Json(self),
                 ).into_response();
             }
             ApiError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::Forbidden { .. } => StatusCode::FORBIDDEN,
+            ApiError::PreconditionFailed { .. } => StatusCode::PRECONDITION_FAILED,
+            ApiError::TooManyRequests { retry_after, .. } => {
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, HeaderValue::from(*retry_after))],
+                    Json(self),
+                ).into_response();
+            }
         };
         (status, Json(self)).into_response()
     }
@@ -84,6 +101,58 @@ impl From<sqlx::Error> for RepositoryError {
     }
 }
 
+#[derive(Debug)]
+pub enum PasswordError {
+    Hash(argon2::password_hash::Error),
+    Task(tokio::task::JoinError),
+}
+
+impl Display for PasswordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PasswordError::Hash(err) => write!(f, "failed to hash password: {err}"),
+            PasswordError::Task(err) => write!(f, "password hashing task failed: {err}"),
+        }
+    }
+}
+
+impl From<argon2::password_hash::Error> for PasswordError {
+    fn from(err: argon2::password_hash::Error) -> Self {
+        PasswordError::Hash(err)
+    }
+}
+
+impl From<tokio::task::JoinError> for PasswordError {
+    fn from(err: tokio::task::JoinError) -> Self {
+        PasswordError::Task(err)
+    }
+}
+
+#[derive(Debug)]
+pub enum IdentityError {
+    Http(reqwest::Error),
+    /// The provider answered with something that isn't a valid OpenID Connect response.
+    InvalidResponse(String),
+    /// The ID token doesn't belong to this client or this login.
+    InvalidToken(&'static str),
+}
+
+impl Display for IdentityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdentityError::Http(err) => write!(f, "identity provider request failed: {err}"),
+            IdentityError::InvalidResponse(err) => write!(f, "invalid identity provider response: {err}"),
+            IdentityError::InvalidToken(reason) => write!(f, "invalid ID token: {reason}"),
+        }
+    }
+}
+
+impl From<reqwest::Error> for IdentityError {
+    fn from(err: reqwest::Error) -> Self {
+        IdentityError::Http(err)
+    }
+}
+
 pub struct SseError {
     pub message: String,
 }
@@ -1,13 +1,24 @@
-use std::{net::Ipv4Addr, sync::Arc};
+use std::{net::{Ipv4Addr, SocketAddr}, sync::Arc};
 use axum::middleware;
 use server::{
     docs::ApiDoc,
     init_db, init_logs,
     AppState,
-    rand::SmallRandom,
-    services::{session, trace::trace},
+    rand::SecureRandom,
+    services::{
+        session,
+        export,
+        auth::{optional_auth, require_admin},
+        csrf::csrf,
+        rate_limit::{RateLimiter, rate_limit},
+        trace::trace,
+        cookies::CookieConfig,
+        password::PasswordHasher,
+        identity::IdentityProviders,
+        oidc::{OidcConfig, OidcProvider},
+    },
     controllers::{
-        chats, events, messages, search,
+        admin, blocks, bots, channels, export as exports, chats, members, invites, events, identity, messages, presence, profiles, receipts, search, sessions, tokens, totp,
         users::{self},
     },
 };
@@ -19,26 +30,102 @@ use utoipa_axum::{router::OpenApiRouter, routes};
 #[tokio::main]
 async fn main() {
     let _guard = init_logs();
+    CookieConfig::from_env().init();
     let db = init_db().await;
     session::start_cleanup_task(db.clone());
-    let rng = Arc::new(Mutex::new(SmallRandom::new(807234275934919497)));
-    let state = Arc::new(AppState::new(rng, db));
+    let rng = Arc::new(Mutex::new(SecureRandom::new()));
+    let mut identity_providers = IdentityProviders::from_env();
+    if let Some(config) = OidcConfig::from_env() {
+        let provider = OidcProvider::discover(config)
+            .await
+            .expect("failed to discover OpenID Connect provider");
+        identity_providers.add(Arc::new(provider));
+    }
+
+    let state = Arc::new(AppState::new(
+        rng,
+        PasswordHasher::from_env(),
+        RateLimiter::from_env(),
+        identity_providers,
+        db,
+    ));
+    admin::grant_configured_admins(&state).await;
+    export::start_cleanup_task(state.exports.clone());
     let root_path = std::env::current_exe().expect("failed to get executable path");
     let root_path = root_path.parent().expect("failed to get parent directory");
+    // anonymous routes are limited by client address, so they get their own layer
+    // search leaves out users blocked by the caller, so it needs to know who is asking
+    let search = OpenApiRouter::new()
+        .routes(routes!(search::search_users))
+        .layer(middleware::from_fn_with_state(state.clone(), optional_auth));
+    let public = OpenApiRouter::new()
+        .merge(search)
+        .routes(routes!(users::login_user))
+        .routes(routes!(users::login_totp))
+        .routes(routes!(users::new_user))
+        .routes(routes!(identity::external_login))
+        .routes(routes!(identity::external_callback))
+        .layer(middleware::from_fn_with_state(state.clone(), rate_limit));
+    let admin = OpenApiRouter::new()
+        .routes(routes!(admin::get_users))
+        .routes(routes!(admin::delete_user))
+        .routes(routes!(admin::suspend_user, admin::unsuspend_user))
+        .routes(routes!(admin::force_logout))
+        .routes(routes!(admin::reset_password))
+        .routes(routes!(admin::get_actions))
+        .layer(middleware::from_fn(require_admin));
+
     let (app, api) = OpenApiRouter::with_openapi(ApiDoc::openapi())
         .routes(routes!(events::events))
         .routes(routes!(messages::new_message, messages::get_messages))
-        .routes(routes!(chats::remove_chat))
+        .routes(routes!(chats::remove_chat, chats::update_chat))
+        .routes(routes!(chats::set_chat_image, chats::remove_chat_image, chats::get_chat_image))
         .routes(routes!(chats::new_chat, chats::get_chats))
+        .routes(routes!(chats::direct_chat))
+        .routes(routes!(members::get_members, members::add_members))
+        .routes(routes!(members::remove_member))
+        .routes(routes!(members::leave_chat))
+        .routes(routes!(members::set_role))
+        .routes(routes!(invites::get_invites, invites::new_invite))
+        .routes(routes!(invites::remove_invite))
+        .routes(routes!(invites::get_invite, invites::join_chat))
+        .routes(routes!(channels::get_channels))
+        .routes(routes!(channels::join_channel))
+        .routes(routes!(channels::get_channel_messages))
+        .routes(routes!(receipts::mark_read))
+        .routes(routes!(receipts::get_message_readers))
         .routes(routes!(users::get_user))
         .routes(routes!(users::logout_user))
+        .routes(routes!(users::delete_account))
+        .routes(routes!(users::change_password))
+        .routes(routes!(users::change_username))
+        .routes(routes!(profiles::update_profile))
+        .routes(routes!(profiles::set_status, profiles::clear_status))
+        .routes(routes!(profiles::set_avatar, profiles::remove_avatar))
+        .routes(routes!(profiles::get_avatar))
+        .routes(routes!(presence::get_presence, presence::set_activity))
+        .routes(routes!(presence::update_presence_settings))
+        .routes(routes!(receipts::update_read_receipts_settings))
+        .routes(routes!(blocks::get_blocked_users))
+        .routes(routes!(blocks::block_user, blocks::unblock_user))
+        .routes(routes!(exports::new_export, exports::get_export))
+        .routes(routes!(exports::download_export))
+        .routes(routes!(totp::enroll_totp, totp::disable_totp))
+        .routes(routes!(totp::confirm_totp))
+        .routes(routes!(sessions::get_sessions, sessions::remove_other_sessions))
+        .routes(routes!(sessions::remove_session))
+        .routes(routes!(tokens::get_tokens, tokens::new_token))
+        .routes(routes!(tokens::remove_token))
+        .routes(routes!(bots::get_bots, bots::new_bot))
+        .routes(routes!(bots::remove_bot))
+        .merge(admin)
+        .layer(middleware::from_fn_with_state(state.clone(), rate_limit))
+        .layer(middleware::from_fn(csrf))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             server::services::auth::auth,
         ))
-        .routes(routes!(search::search_users))
-        .routes(routes!(users::login_user))
-        .routes(routes!(users::new_user))
+        .merge(public)
         .with_state(state)
         .layer(middleware::from_fn(trace))
         .nest_service(
@@ -61,7 +148,7 @@ async fn main() {
 
     tracing::info!("Listening on port {port}");
 
-    axum::serve(listener, app)
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .expect("failed to start server");
 }
\ No newline at end of file
@@ -0,0 +1,111 @@
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use strum::{AsRefStr, EnumString};
+use serde::{Deserialize, Serialize};
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use crate::models::users::{Password, UserId};
+
+pub const MAX_ADMIN_PAGE_SIZE: i64 = 100;
+const DEFAULT_ADMIN_PAGE_SIZE: i64 = 50;
+
+/// User as seen by administrators.
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct AdminUser {
+    pub id: UserId,
+    pub username: String,
+    pub is_bot: bool,
+    pub is_admin: bool,
+    #[serde(with = "time::serde::iso8601::option")]
+    pub suspended_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::iso8601")]
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct GetAdminUsersQuery {
+    /// Part of the username, matched case-insensitively.
+    pub username: Option<String>,
+    /// Last user id of the previous page.
+    pub after: Option<UserId>,
+    #[serde(default = "default_page_size")]
+    pub limit: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GetAdminUsersResponse {
+    pub users: Vec<AdminUser>,
+    pub has_more: bool,
+}
+
+impl IntoResponse for GetAdminUsersResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, AsRefStr, EnumString, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum AdminActionKind {
+    Suspend,
+    Unsuspend,
+    ForceLogout,
+    ResetPassword,
+    DeleteUser,
+}
+
+/// Audit record of an administrator action.
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct AdminAction {
+    pub id: i32,
+    /// Acting administrator, missing once their account is deleted.
+    pub admin_id: Option<UserId>,
+    pub action: AdminActionKind,
+    pub target_id: UserId,
+    /// Username of the target when the action was taken.
+    pub target_name: String,
+    #[serde(with = "time::serde::iso8601")]
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct GetAdminActionsQuery {
+    /// Id of the oldest action of the previous page.
+    pub before: Option<i32>,
+    #[serde(default = "default_page_size")]
+    pub limit: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GetAdminActionsResponse {
+    pub actions: Vec<AdminAction>,
+    pub has_more: bool,
+}
+
+impl IntoResponse for GetAdminActionsResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ResetPasswordRequest {
+    pub password: Password,
+}
+
+#[derive(ToSchema)]
+pub struct AdminActionResponse;
+
+impl IntoResponse for AdminActionResponse {
+    fn into_response(self) -> Response {
+        StatusCode::NO_CONTENT.into_response()
+    }
+}
+
+fn default_page_size() -> i64 {
+    DEFAULT_ADMIN_PAGE_SIZE
+}
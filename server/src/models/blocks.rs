@@ -0,0 +1,22 @@
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use serde::Serialize;
+use axum::response::IntoResponse;
+use crate::models::users::UserId;
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct BlockedUser {
+    pub user_id: UserId,
+    pub username: String,
+    #[serde(with = "time::serde::iso8601")]
+    pub blocked_at: OffsetDateTime,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GetBlockedUsersResponse(pub Vec<BlockedUser>);
+
+impl IntoResponse for GetBlockedUsersResponse {
+    fn into_response(self) -> axum::response::Response {
+        axum::Json(self).into_response()
+    }
+}
@@ -0,0 +1,112 @@
+use utoipa::ToSchema;
+use serde::{Deserialize, Serialize};
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use crate::{
+    models::users::{UserId, Username},
+    services::webhooks::{is_public_address, literal_address},
+};
+
+/// Bot account as seen by its owner.
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct Bot {
+    pub id: UserId,
+    pub username: String,
+    #[serde(skip)]
+    pub owner_id: UserId,
+    pub webhook_url: Option<String>,
+    #[serde(with = "time::serde::iso8601")]
+    pub created_at: time::OffsetDateTime,
+}
+
+/// Delivery target of a bot that has a webhook configured.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BotWebhook {
+    pub bot_id: UserId,
+    pub url: String,
+    pub secret: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct WebhookUrl(String);
+
+impl WebhookUrl {
+    pub fn new<I: Into<String>>(url: I) -> Self {
+        Self(url.into())
+    }
+
+    /// Names are only checked for `localhost` here, what they resolve to is checked on delivery.
+    pub fn validate(&self, allow_insecure_hosts: bool) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        match reqwest::Url::parse(&self.0) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => {
+                let host = url.host_str().unwrap_or_default().trim_end_matches('.').to_ascii_lowercase();
+                let internal = host == "localhost"
+                    || host.ends_with(".localhost")
+                    || literal_address(&self.0).is_some_and(|ip| !is_public_address(ip));
+                if internal && !allow_insecure_hosts {
+                    errors.push("Webhook url must not point to an internal address".to_owned());
+                }
+            }
+            Ok(_) => errors.push("Webhook url must be an http or https url".to_owned()),
+            Err(_) => errors.push("Webhook url is invalid".to_owned()),
+        }
+
+        if self.0.len() > 2048 {
+            errors.push("Webhook url is too long".to_owned());
+        }
+
+        errors
+    }
+}
+
+impl AsRef<str> for WebhookUrl {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct NewBotRequest {
+    pub username: Username,
+    /// Events of the bot chats are POSTed to this url, omit it to only use the API.
+    pub webhook_url: Option<WebhookUrl>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct NewBotResponse {
+    /// API token of the bot, shown only once.
+    pub token: String,
+    /// Key of the `X-Webhook-Signature` HMAC, shown only once.
+    pub webhook_secret: String,
+    #[serde(flatten)]
+    pub bot: Bot,
+}
+
+impl IntoResponse for NewBotResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::CREATED, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GetBotsResponse(pub Vec<Bot>);
+
+impl IntoResponse for GetBotsResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(ToSchema)]
+pub struct RemoveBotResponse;
+
+impl IntoResponse for RemoveBotResponse {
+    fn into_response(self) -> Response {
+        StatusCode::NO_CONTENT.into_response()
+    }
+}
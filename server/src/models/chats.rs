@@ -1,21 +1,53 @@
 use std::ops::Deref;
 use utoipa::ToSchema;
-use serde::{Deserialize, Serialize};
+use strum::{AsRefStr, EnumString};
+use serde::{Deserialize, Deserializer, Serialize};
 use axum::{
     Json,
-    http::StatusCode,
+    http::{HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
 };
-use crate::models::users::UserId;
+use crate::models::{messages::{MessageId, MessagePreview}, users::UserId};
 
-#[derive(Serialize, ToSchema)]
+pub const CHAT_TITLE_MAX_LENGTH: usize = 50;
+pub const CHAT_DESCRIPTION_MAX_LENGTH: usize = 500;
+/// Longest accepted channel search query, in characters.
+pub const CHANNEL_QUERY_MAX_LENGTH: usize = 50;
+pub const DEFAULT_CHANNELS_PAGE_SIZE: i64 = 20;
+pub const MAX_CHANNELS_PAGE_SIZE: i64 = 100;
+pub const DEFAULT_CHATS_PAGE_SIZE: i64 = 50;
+pub const MAX_CHATS_PAGE_SIZE: i64 = 100;
+/// Unread counts stop at this value, clients show it as "999+" or similar.
+pub const UNREAD_COUNT_LIMIT: i64 = 1000;
+
+#[derive(Clone, Serialize, ToSchema)]
 pub struct Chat {
     pub id: ChatId,
+    pub kind: ChatKind,
+    pub visibility: ChatVisibility,
+    /// Direct chats are titled after the other participant, by display name or else username.
     pub title: ChatTitle,
     pub users_ids: Vec<UserId>,
+    pub description: Option<String>,
+    /// Path of the chat image, versioned so clients can cache it forever.
+    pub image_url: Option<String>,
+    /// Bumped by every change of the title, description or image, also sent as the `ETag`.
+    pub version: i32,
+}
+
+impl Chat {
+    pub fn image_url(chat_id: ChatId, updated_at: Option<time::OffsetDateTime>) -> Option<String> {
+        updated_at.map(|updated_at| {
+            format!("/chats/{chat_id}/image?v={}", updated_at.unix_timestamp_nanos() / 1_000_000)
+        })
+    }
+
+    pub fn etag(&self) -> String {
+        format!("\"{}\"", self.version)
+    }
 }
 
-#[derive(Deserialize, sqlx::Type, Serialize, ToSchema)]
+#[derive(Clone, Debug, Deserialize, sqlx::Type, Serialize, ToSchema)]
 #[sqlx(transparent)]
 pub struct ChatTitle(String);
 
@@ -27,11 +59,11 @@ impl ChatTitle {
     pub fn validate(&self) -> Vec<String> {
         let mut errors = Vec::new();
 
-        if self.0.is_empty() {
+        if self.0.trim().is_empty() {
             errors.push("Title is empty".to_string());
         }
 
-        if self.0.len() > 50 {
+        if self.0.chars().count() > CHAT_TITLE_MAX_LENGTH {
             errors.push("Title is too long".to_string());
         }
 
@@ -47,6 +79,32 @@ impl Deref for ChatTitle {
     }
 }
 
+#[derive(Deserialize, ToSchema)]
+pub struct ChatDescription(String);
+
+impl ChatDescription {
+    pub fn new<I: Into<String>>(description: I) -> Self {
+        Self(description.into())
+    }
+
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.0.trim().chars().count() > CHAT_DESCRIPTION_MAX_LENGTH {
+            errors.push(format!("Description must be at most {CHAT_DESCRIPTION_MAX_LENGTH} characters"));
+        }
+
+        errors
+    }
+}
+
+impl Deref for ChatDescription {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.trim()
+    }
+}
+
 #[derive(Debug, sqlx::Type, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, ToSchema)]
 #[sqlx(transparent)]
 pub struct ChatId(i32);
@@ -87,10 +145,286 @@ impl Deref for ChatId {
 pub struct NewChatRequest {
     pub title: ChatTitle,
     pub users_ids: Option<Vec<UserId>>,
+    #[serde(default)]
+    pub visibility: ChatVisibility,
+}
+
+/// Direct chats are between two users, only ever one per pair, and nobody else can join them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, AsRefStr, EnumString, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum ChatKind {
+    Direct,
+    Group,
+}
+
+/// Public chats are listed in the channel directory, anyone can read them and join.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, AsRefStr, EnumString, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum ChatVisibility {
+    #[default]
+    Private,
+    Public,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, AsRefStr, EnumString, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum ChatRole {
+    Owner,
+    Admin,
+    Member,
 }
 
+/// Actions restricted by the role of a member.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, AsRefStr)]
+pub enum ChatPermission {
+    ReadMessages,
+    SendMessages,
+    Rename,
+    ManageMembers,
+    PinMessages,
+    ModerateMessages,
+    ManageRoles,
+    ChangeVisibility,
+    DeleteChat,
+}
+
+impl ChatRole {
+    pub fn can(self, permission: ChatPermission) -> bool {
+        match permission {
+            ChatPermission::ReadMessages | ChatPermission::SendMessages => true,
+            ChatPermission::Rename
+            | ChatPermission::ManageMembers
+            | ChatPermission::PinMessages
+            | ChatPermission::ModerateMessages => self != ChatRole::Member,
+            ChatPermission::ManageRoles
+            | ChatPermission::ChangeVisibility
+            | ChatPermission::DeleteChat => self == ChatRole::Owner,
+        }
+    }
+
+    /// Whether a member with this role may act on a member with `other` role, like removing them.
+    pub fn outranks(self, other: ChatRole) -> bool {
+        self.rank() > other.rank()
+    }
+
+    fn rank(self) -> u8 {
+        match self {
+            ChatRole::Owner => 2,
+            ChatRole::Admin => 1,
+            ChatRole::Member => 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct ChatMember {
+    pub user_id: UserId,
+    pub role: ChatRole,
+    #[serde(with = "time::serde::iso8601")]
+    pub joined_at: time::OffsetDateTime,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GetChatMembersResponse(pub Vec<ChatMember>);
+
+impl IntoResponse for GetChatMembersResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+/// Making a member the owner hands the ownership over, the previous owner becomes an admin.
+#[derive(Deserialize, ToSchema)]
+pub struct SetRoleRequest {
+    pub role: ChatRole,
+}
+
+/// Missing fields are left unchanged, a `null` description clears it.
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateChatRequest {
+    pub title: Option<ChatTitle>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    pub description: Option<Option<ChatDescription>>,
+    /// Only the owner can change it, direct chats are always private.
+    pub visibility: Option<ChatVisibility>,
+}
+
+/// Public chat as listed in the channel directory.
 #[derive(Serialize, ToSchema)]
-pub struct GetChatsResponse(pub Vec<Chat>);
+pub struct Channel {
+    pub id: ChatId,
+    pub title: ChatTitle,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub members_count: i64,
+    /// Whether the user is a member of the channel.
+    pub is_member: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct GetChannelsQuery {
+    /// Part of the title or description, matched case-insensitively.
+    pub query: Option<String>,
+    /// Last channel id of the previous page.
+    pub before: Option<ChatId>,
+    #[serde(default = "default_channels_page_size")]
+    pub limit: i64,
+}
+
+fn default_channels_page_size() -> i64 {
+    DEFAULT_CHANNELS_PAGE_SIZE
+}
+
+/// Channels, newest first.
+#[derive(Serialize, ToSchema)]
+pub struct GetChannelsResponse {
+    pub channels: Vec<Channel>,
+    pub has_more: bool,
+}
+
+impl IntoResponse for GetChannelsResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+/// Chat after the change, with its version as the `ETag`.
+pub struct UpdateChatResponse(pub Chat);
+
+impl IntoResponse for UpdateChatResponse {
+    fn into_response(self) -> Response {
+        let Ok(etag) = HeaderValue::from_str(&self.0.etag()) else {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        };
+
+        (StatusCode::OK, [(header::ETAG, etag)], Json(self.0)).into_response()
+    }
+}
+
+/// Tells a `null` field apart from a missing one, which is `None` through `#[serde(default)]`.
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::deserialize(deserializer).map(Some)
+}
+
+/// Parses an `If-Match` header holding a chat version, `*` matches any version.
+pub fn parse_if_match(value: &str) -> Option<Option<i32>> {
+    let value = value.trim();
+    if value == "*" {
+        return Some(None);
+    }
+
+    let value = value.strip_prefix("W/").unwrap_or(value);
+    value.strip_prefix('"')?.strip_suffix('"')?.parse().ok().map(Some)
+}
+
+#[derive(ToSchema)]
+pub struct SetRoleResponse;
+
+impl IntoResponse for SetRoleResponse {
+    fn into_response(self) -> Response {
+        StatusCode::NO_CONTENT.into_response()
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AddMembersRequest {
+    pub users_ids: Vec<UserId>,
+}
+
+/// Members of the chat after the change.
+#[derive(Serialize, ToSchema)]
+pub struct ChatMembersResponse {
+    pub users_ids: Vec<UserId>,
+}
+
+impl IntoResponse for ChatMembersResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+/// Entry of the chat list.
+#[derive(Serialize, ToSchema)]
+pub struct ChatSummary {
+    #[serde(flatten)]
+    pub chat: Chat,
+    /// Missing when nothing was written in the chat yet.
+    pub last_message: Option<MessagePreview>,
+    /// Latest message the user has read, missing when nothing was read yet.
+    pub last_read_message_id: Option<MessageId>,
+    /// Messages of other members after the read marker of the user, at most `UNREAD_COUNT_LIMIT`.
+    pub unread_count: i64,
+    /// Time of the latest message, or of the chat creation.
+    #[serde(with = "time::serde::iso8601")]
+    pub last_activity_at: time::OffsetDateTime,
+}
+
+impl ChatSummary {
+    pub fn cursor(&self) -> ChatCursor {
+        ChatCursor {
+            last_activity_at: self.last_activity_at,
+            chat_id: self.chat.id,
+        }
+    }
+}
+
+/// Position in the chat list, sent to clients as an opaque `{micros}_{chat_id}` string.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChatCursor {
+    pub last_activity_at: time::OffsetDateTime,
+    pub chat_id: ChatId,
+}
+
+impl std::fmt::Display for ChatCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let micros = self.last_activity_at.unix_timestamp_nanos() / 1_000;
+        write!(f, "{micros}_{}", self.chat_id)
+    }
+}
+
+impl std::str::FromStr for ChatCursor {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (micros, chat_id) = s.split_once('_').ok_or(())?;
+        let micros = micros.parse::<i128>().map_err(|_| ())?;
+        let last_activity_at =
+            time::OffsetDateTime::from_unix_timestamp_nanos(micros * 1_000).map_err(|_| ())?;
+
+        Ok(Self {
+            last_activity_at,
+            chat_id: ChatId::new(chat_id.parse().map_err(|_| ())?),
+        })
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct GetChatsQuery {
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
+    #[serde(default = "default_chats_page_size")]
+    pub limit: i64,
+}
+
+fn default_chats_page_size() -> i64 {
+    DEFAULT_CHATS_PAGE_SIZE
+}
+
+/// Chats by latest activity, most recent first.
+#[derive(Serialize, ToSchema)]
+pub struct GetChatsResponse {
+    pub chats: Vec<ChatSummary>,
+    /// Cursor of the next page, missing on the last one.
+    pub next_cursor: Option<String>,
+}
 
 impl IntoResponse for GetChatsResponse {
     fn into_response(self) -> Response {
@@ -115,6 +449,19 @@ impl IntoResponse for NewChatResponse {
     }
 }
 
+/// The direct chat, `201 Created` when it did not exist yet.
+pub struct DirectChatResponse {
+    pub chat: Chat,
+    pub created: bool,
+}
+
+impl IntoResponse for DirectChatResponse {
+    fn into_response(self) -> Response {
+        let status = if self.created { StatusCode::CREATED } else { StatusCode::OK };
+        (status, Json(self.chat)).into_response()
+    }
+}
+
 #[derive(ToSchema)]
 pub struct RemoveChatResponse;
 
@@ -122,4 +469,92 @@ impl IntoResponse for RemoveChatResponse {
     fn into_response(self) -> Response {
         StatusCode::NO_CONTENT.into_response()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_permissions() {
+        assert!(ChatRole::Owner.can(ChatPermission::DeleteChat));
+        assert!(ChatRole::Owner.can(ChatPermission::ManageRoles));
+        assert!(!ChatRole::Admin.can(ChatPermission::DeleteChat));
+        assert!(!ChatRole::Admin.can(ChatPermission::ManageRoles));
+        assert!(!ChatRole::Admin.can(ChatPermission::ChangeVisibility));
+        assert!(ChatRole::Admin.can(ChatPermission::ManageMembers));
+        assert!(ChatRole::Admin.can(ChatPermission::ModerateMessages));
+        assert!(!ChatRole::Member.can(ChatPermission::Rename));
+        assert!(!ChatRole::Member.can(ChatPermission::PinMessages));
+        assert!(ChatRole::Member.can(ChatPermission::SendMessages));
+    }
+
+    #[test]
+    fn test_chat_cursor_round_trip() {
+        let cursor = ChatCursor {
+            last_activity_at: time::macros::datetime!(2026-10-18 12:30:45.123456 UTC),
+            chat_id: ChatId::new(42),
+        };
+        assert_eq!(cursor.to_string().parse::<ChatCursor>(), Ok(cursor));
+        assert!("".parse::<ChatCursor>().is_err());
+        assert!("123".parse::<ChatCursor>().is_err());
+        assert!("abc_1".parse::<ChatCursor>().is_err());
+        assert!("123_abc".parse::<ChatCursor>().is_err());
+    }
+
+    #[test]
+    fn test_title_counts_characters() {
+        assert!(ChatTitle::new("ё".repeat(50)).validate().is_empty());
+        assert!(!ChatTitle::new("ё".repeat(51)).validate().is_empty());
+        assert!(!ChatTitle::new("  ".to_owned()).validate().is_empty());
+    }
+
+    #[test]
+    fn test_update_chat_request() {
+        let req: UpdateChatRequest = serde_json::from_str(r#"{"title": "chat"}"#).unwrap();
+        assert!(req.title.is_some());
+        assert!(req.description.is_none());
+
+        let req: UpdateChatRequest = serde_json::from_str(r#"{"description": null}"#).unwrap();
+        assert!(matches!(req.description, Some(None)));
+
+        let req: UpdateChatRequest = serde_json::from_str(r#"{"description": "about"}"#).unwrap();
+        assert!(matches!(req.description, Some(Some(_))));
+    }
+
+    #[test]
+    fn test_direct_chat_response_status() {
+        let chat = Chat {
+            id: ChatId::new(1),
+            kind: ChatKind::Direct,
+            visibility: ChatVisibility::Private,
+            title: ChatTitle::new("alice".to_owned()),
+            users_ids: vec![UserId::new(1), UserId::new(2)],
+            description: None,
+            image_url: None,
+            version: 1,
+        };
+
+        let response = DirectChatResponse { chat: chat.clone(), created: true }.into_response();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response = DirectChatResponse { chat, created: false }.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_parse_if_match() {
+        assert_eq!(parse_if_match("\"3\""), Some(Some(3)));
+        assert_eq!(parse_if_match("W/\"3\""), Some(Some(3)));
+        assert_eq!(parse_if_match("*"), Some(None));
+        assert_eq!(parse_if_match("3"), None);
+        assert_eq!(parse_if_match("\"abc\""), None);
+    }
+
+    #[test]
+    fn test_role_rank() {
+        assert!(ChatRole::Owner.outranks(ChatRole::Admin));
+        assert!(ChatRole::Admin.outranks(ChatRole::Member));
+        assert!(!ChatRole::Admin.outranks(ChatRole::Admin));
+        assert!(!ChatRole::Member.outranks(ChatRole::Owner));
+    }
+}
@@ -3,26 +3,49 @@ use serde::Serialize;
 use serde_json::to_string;
 use crate::models::{
     users::UserId,
-    messages::Message,
-    chats::{ChatId, ChatTitle}
+    messages::{Message, MessageId},
+    sessions::SessionId,
+    chats::{Chat, ChatId, ChatTitle}
 };
 
 #[derive(Clone, AsRefStr)]
 pub enum SseEventType {
     Message,
     Chat,
+    /// Members were added to or removed from a chat.
+    ChatMembers,
+    /// Title, description or image of a chat changed.
+    ChatUpdated,
+    SessionsRevoked,
+    /// Profile of a user sharing a chat with the recipient was changed.
+    Profile,
+    /// A user sharing a chat with the recipient went online, idle or offline.
+    Presence,
+    /// A member read a chat up to a message, also sent to the other devices of the reader.
+    ReadMarker,
 }
 
 #[derive(Clone)]
 pub struct SseEvent {
     pub event_type: SseEventType,
     pub data: String,
+    /// Sessions whose event streams must be closed when this event arrives.
+    pub closes: Vec<SessionId>,
 }
 
 impl SseEvent {
     pub fn new(event_type: SseEventType, data: impl Serialize) -> Self {
         let data = to_string(&data).expect("failed to serialize event data");
-        Self { event_type, data }
+        Self { event_type, data, closes: Vec::new() }
+    }
+
+    pub fn sessions_revoked(sessions_ids: Vec<SessionId>) -> Self {
+        let mut event = Self::new(
+            SseEventType::SessionsRevoked,
+            SessionsRevokedEvent { sessions_ids: sessions_ids.clone() },
+        );
+        event.closes = sessions_ids;
+        event
     }
 }
 
@@ -33,9 +56,38 @@ pub struct ChatEvent {
     pub users_ids: Vec<UserId>,
 }
 
+/// Sent to the members of the chat and to the removed ones.
+#[derive(Serialize)]
+pub struct ChatMembersEvent {
+    pub chat_id: ChatId,
+    pub title: ChatTitle,
+    /// Members after the change.
+    pub users_ids: Vec<UserId>,
+    pub added: Vec<UserId>,
+    pub removed: Vec<UserId>,
+}
+
+#[derive(Serialize)]
+pub struct ChatUpdatedEvent {
+    pub chat: Chat,
+}
+
+#[derive(Serialize)]
+pub struct ReadMarkerEvent {
+    pub chat_id: ChatId,
+    pub user_id: UserId,
+    /// Latest message read by the user, everything before it counts as read too.
+    pub message_id: MessageId,
+}
+
 #[derive(Serialize)]
 pub struct MessageEvent {
     pub message: Message,
     pub chat_id: ChatId,
     pub user_id: UserId,
+}
+
+#[derive(Serialize)]
+pub struct SessionsRevokedEvent {
+    pub sessions_ids: Vec<SessionId>,
 }
\ No newline at end of file
@@ -0,0 +1,143 @@
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use serde::Serialize;
+use axum::{
+    Json,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use crate::models::{
+    chats::Chat,
+    messages::Message,
+    profiles::Profile,
+    sessions::{Session, SessionId},
+    users::{User, UserId},
+};
+
+/// Time a finished archive can be downloaded.
+pub const EXPORT_LINK_LIFETIME: time::Duration = time::Duration::hours(24);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExportStatus {
+    Building,
+    Ready,
+    Failed,
+}
+
+/// Progress of the latest data export of a user.
+#[derive(Serialize, ToSchema)]
+pub struct GetExportResponse {
+    pub status: ExportStatus,
+    /// Percentage of the archive built so far.
+    pub progress: u8,
+    /// One-time link to the archive once it is ready.
+    pub download_url: Option<String>,
+    #[serde(with = "time::serde::iso8601::option")]
+    pub expires_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::iso8601")]
+    pub created_at: OffsetDateTime,
+}
+
+impl IntoResponse for GetExportResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// Returned when an export is started, the archive is built in the background.
+pub struct NewExportResponse(pub GetExportResponse);
+
+impl IntoResponse for NewExportResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::ACCEPTED, Json(self.0)).into_response()
+    }
+}
+
+/// Finished archive, served once.
+pub struct DownloadExportResponse {
+    pub filename: String,
+    pub data: Vec<u8>,
+}
+
+impl IntoResponse for DownloadExportResponse {
+    fn into_response(self) -> Response {
+        let disposition = format!("attachment; filename=\"{}\"", self.filename);
+        let Ok(disposition) = HeaderValue::from_str(&disposition) else {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/zip"));
+        headers.insert(header::CONTENT_DISPOSITION, disposition);
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+
+        (StatusCode::OK, headers, self.data).into_response()
+    }
+}
+
+/// `account.json` of the archive.
+#[derive(Serialize)]
+pub struct ExportedAccount {
+    pub id: UserId,
+    pub username: String,
+    pub is_bot: bool,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub status: Option<String>,
+    #[serde(with = "time::serde::iso8601")]
+    pub created_at: OffsetDateTime,
+}
+
+impl ExportedAccount {
+    pub fn new(user: User, profile: Option<Profile>) -> Self {
+        let (display_name, bio, status) = profile
+            .map(|profile| (profile.display_name, profile.bio, profile.status_text))
+            .unwrap_or_default();
+
+        Self {
+            id: user.id,
+            username: user.username,
+            is_bot: user.is_bot,
+            display_name,
+            bio,
+            status,
+            created_at: user.created_at,
+        }
+    }
+}
+
+/// Entry of `sessions.json`, the session secret is left out.
+#[derive(Serialize)]
+pub struct ExportedSession {
+    pub id: SessionId,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    #[serde(with = "time::serde::iso8601")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::iso8601")]
+    pub last_seen_at: OffsetDateTime,
+    #[serde(with = "time::serde::iso8601")]
+    pub expires_at: OffsetDateTime,
+}
+
+impl From<Session> for ExportedSession {
+    fn from(session: Session) -> Self {
+        Self {
+            id: session.id,
+            user_agent: session.user_agent,
+            ip: session.ip,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+            expires_at: session.expires_at,
+        }
+    }
+}
+
+/// Everything held about a user, written as one JSON file per field.
+pub struct ExportData {
+    pub account: ExportedAccount,
+    pub sessions: Vec<ExportedSession>,
+    pub chats: Vec<Chat>,
+    pub messages: Vec<Message>,
+}
@@ -0,0 +1,78 @@
+use time::Duration;
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use axum::{
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use crate::{
+    models::users::SESSION_LIFETIME,
+    services::cookies::{auth_state_cookie, auth_state_removal_cookie, session_cookie},
+};
+
+/// Time a user has to complete the login at an identity provider.
+pub const AUTH_REQUEST_LIFETIME: i64 = 60 * 10;
+
+/// External login started by this server and awaiting the provider callback.
+#[derive(Clone, Debug)]
+pub struct AuthRequest {
+    pub state: String,
+    pub provider: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    pub expires_at: time::OffsetDateTime,
+}
+
+/// Parameters the identity provider appends to the callback URL.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExternalCallbackParams {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    /// Set instead of `code` when the login was denied or failed at the provider.
+    pub error: Option<String>,
+}
+
+/// Redirect to the identity provider.
+#[derive(ToSchema)]
+pub struct ExternalLoginResponse {
+    pub location: String,
+    pub state: String,
+}
+
+impl IntoResponse for ExternalLoginResponse {
+    fn into_response(self) -> Response {
+        let Ok(location) = HeaderValue::from_str(&self.location) else {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::LOCATION, location);
+        headers.insert(
+            header::SET_COOKIE,
+            auth_state_cookie(&self.state, Duration::seconds(AUTH_REQUEST_LIFETIME)),
+        );
+
+        (StatusCode::SEE_OTHER, headers).into_response()
+    }
+}
+
+/// Redirect to the web app with a new session.
+#[derive(ToSchema)]
+pub struct ExternalCallbackResponse {
+    pub session: String,
+}
+
+impl IntoResponse for ExternalCallbackResponse {
+    fn into_response(self) -> Response {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::LOCATION, HeaderValue::from_static("/"));
+        headers.append(
+            header::SET_COOKIE,
+            session_cookie(&self.session, Duration::seconds(SESSION_LIFETIME)),
+        );
+        headers.append(header::SET_COOKIE, auth_state_removal_cookie());
+
+        (StatusCode::SEE_OTHER, headers).into_response()
+    }
+}
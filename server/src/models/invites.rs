@@ -0,0 +1,96 @@
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use serde::{Deserialize, Serialize};
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use crate::models::{
+    chats::{Chat, ChatId, ChatTitle},
+    users::UserId,
+};
+
+/// Largest accepted `max_uses` of an invite.
+pub const INVITE_MAX_USES: i32 = 10_000;
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct ChatInvite {
+    /// Secret part of the invite link.
+    pub code: String,
+    pub chat_id: ChatId,
+    /// Missing once the creator deleted their account.
+    pub created_by: Option<UserId>,
+    /// The invite works any number of times if missing.
+    pub max_uses: Option<i32>,
+    pub uses: i32,
+    #[serde(with = "time::serde::iso8601::option")]
+    pub expires_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::iso8601")]
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct NewInviteRequest {
+    /// Lifetime of the invite in hours, the invite never expires if omitted.
+    pub expires_in_hours: Option<u16>,
+    /// How many users can join with the invite, unlimited if omitted.
+    pub max_uses: Option<i32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct NewInviteResponse(pub ChatInvite);
+
+impl IntoResponse for NewInviteResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::CREATED, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GetInvitesResponse(pub Vec<ChatInvite>);
+
+impl IntoResponse for GetInvitesResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+/// What a user sees of the chat before joining it.
+#[derive(Serialize, ToSchema)]
+pub struct InvitePreview {
+    pub chat_id: ChatId,
+    pub title: ChatTitle,
+    pub description: Option<String>,
+    pub members_count: usize,
+    /// Whether the user already is a member of the chat.
+    pub is_member: bool,
+    #[serde(with = "time::serde::iso8601::option")]
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+impl IntoResponse for InvitePreview {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+/// The joined chat, also when the user already was a member.
+#[derive(Serialize, ToSchema)]
+pub struct JoinChatResponse(pub Chat);
+
+impl IntoResponse for JoinChatResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(ToSchema)]
+pub struct RemoveInviteResponse;
+
+impl IntoResponse for RemoveInviteResponse {
+    fn into_response(self) -> Response {
+        StatusCode::NO_CONTENT.into_response()
+    }
+}
+
@@ -10,6 +10,28 @@ pub struct Message {
     pub content: String,
     pub chat_id: ChatId,
     pub sender_id: Option<UserId>,
+    /// Whether the message was sent by a bot account.
+    pub is_bot: bool,
+    /// Whether the reader blocked the sender, clients may collapse such messages.
+    pub sender_blocked: bool,
+    #[serde(with = "time::serde::iso8601")]
+    pub created_at: time::OffsetDateTime,
+}
+
+/// Characters of the content kept in message previews.
+pub const MESSAGE_PREVIEW_LENGTH: i32 = 100;
+
+/// Latest message of a chat as shown in the chat list.
+#[derive(Serialize, Debug, ToSchema, Clone)]
+pub struct MessagePreview {
+    pub id: MessageId,
+    pub sender_id: Option<UserId>,
+    /// Whether the reader blocked the sender.
+    pub sender_blocked: bool,
+    /// First characters of the content.
+    pub content: String,
+    /// Whether the content was cut to fit the preview.
+    pub truncated: bool,
     #[serde(with = "time::serde::iso8601")]
     pub created_at: time::OffsetDateTime,
 }
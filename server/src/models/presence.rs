@@ -0,0 +1,158 @@
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use serde::{Deserialize, Serialize};
+use axum::response::IntoResponse;
+use crate::models::users::UserId;
+
+/// Most users asked about in one presence request.
+pub const PRESENCE_QUERY_MAX_USERS: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum PresenceStatus {
+    Online,
+    /// Connected, but every client reported the user as inactive.
+    Idle,
+    Offline,
+}
+
+/// Stored part of the presence, the live status is only known in memory.
+#[derive(Clone, Debug)]
+pub struct PresenceRecord {
+    pub user_id: UserId,
+    pub last_seen_at: Option<OffsetDateTime>,
+    pub hidden: bool,
+}
+
+/// Presence as shown to other users, hidden users always look offline and never seen.
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct UserPresence {
+    pub user_id: UserId,
+    pub status: PresenceStatus,
+    /// Last time the user was connected, missing while online.
+    #[serde(with = "time::serde::iso8601::option")]
+    pub last_seen_at: Option<OffsetDateTime>,
+}
+
+impl UserPresence {
+    pub fn new(user_id: UserId, status: PresenceStatus, record: Option<&PresenceRecord>) -> Self {
+        match record {
+            Some(record) if record.hidden => Self::hidden(user_id),
+            _ if status != PresenceStatus::Offline => Self {
+                user_id,
+                status,
+                last_seen_at: None,
+            },
+            _ => Self {
+                user_id,
+                status,
+                last_seen_at: record.and_then(|record| record.last_seen_at),
+            },
+        }
+    }
+
+    pub fn hidden(user_id: UserId) -> Self {
+        Self {
+            user_id,
+            status: PresenceStatus::Offline,
+            last_seen_at: None,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct GetPresenceQuery {
+    /// Comma separated users ids, e.g. `1,2,3`.
+    pub users_ids: String,
+}
+
+impl GetPresenceQuery {
+    pub fn users_ids(&self) -> Result<Vec<UserId>, Vec<String>> {
+        let mut errors = Vec::new();
+        let ids = self
+            .users_ids
+            .split(',')
+            .filter(|id| !id.trim().is_empty())
+            .filter_map(|id| match id.trim().parse() {
+                Ok(id) => Some(UserId::new(id)),
+                Err(_) => {
+                    errors.push(format!("Invalid user id: {}", id.trim()));
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if ids.len() > PRESENCE_QUERY_MAX_USERS {
+            errors.push(format!("At most {PRESENCE_QUERY_MAX_USERS} users are allowed"));
+        }
+
+        if errors.is_empty() { Ok(ids) } else { Err(errors) }
+    }
+}
+
+/// Presence of the requested users sharing a chat with the caller, others are left out.
+#[derive(Serialize, ToSchema)]
+pub struct GetPresenceResponse(pub Vec<UserPresence>);
+
+impl IntoResponse for GetPresenceResponse {
+    fn into_response(self) -> axum::response::Response {
+        axum::Json(self).into_response()
+    }
+}
+
+/// Activity reported by a client, applies to every event stream of the session.
+#[derive(Deserialize, ToSchema)]
+pub struct SetActivityRequest {
+    pub idle: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PresenceSettingsRequest {
+    /// Whether other users see the status and last seen time.
+    pub visible: bool,
+}
+
+#[derive(Serialize)]
+pub struct PresenceEvent {
+    #[serde(flatten)]
+    pub presence: UserPresence,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(hidden: bool) -> PresenceRecord {
+        PresenceRecord {
+            user_id: UserId::new(1),
+            last_seen_at: Some(OffsetDateTime::UNIX_EPOCH),
+            hidden,
+        }
+    }
+
+    #[test]
+    fn test_user_presence_last_seen_only_when_offline() {
+        let online = UserPresence::new(UserId::new(1), PresenceStatus::Online, Some(&record(false)));
+        assert_eq!(online.last_seen_at, None);
+
+        let offline = UserPresence::new(UserId::new(1), PresenceStatus::Offline, Some(&record(false)));
+        assert_eq!(offline.last_seen_at, Some(OffsetDateTime::UNIX_EPOCH));
+    }
+
+    #[test]
+    fn test_user_presence_hidden() {
+        let presence = UserPresence::new(UserId::new(1), PresenceStatus::Idle, Some(&record(true)));
+
+        assert_eq!(presence.status, PresenceStatus::Offline);
+        assert_eq!(presence.last_seen_at, None);
+    }
+
+    #[test]
+    fn test_get_presence_query_users_ids() {
+        let query = GetPresenceQuery { users_ids: "1, 2,,3".to_owned() };
+        assert_eq!(query.users_ids().unwrap(), vec![UserId::new(1), UserId::new(2), UserId::new(3)]);
+
+        let query = GetPresenceQuery { users_ids: "1,me".to_owned() };
+        assert_eq!(query.users_ids().unwrap_err(), vec!["Invalid user id: me".to_owned()]);
+    }
+}
@@ -0,0 +1,278 @@
+use std::ops::Deref;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use serde::{Deserialize, Serialize};
+use axum::{
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use crate::models::users::{PublicUser, UserId};
+
+pub const DISPLAY_NAME_MAX_LENGTH: usize = 64;
+pub const BIO_MAX_LENGTH: usize = 500;
+pub const STATUS_MAX_LENGTH: usize = 100;
+/// Largest accepted avatar image, in bytes.
+pub const AVATAR_MAX_SIZE: usize = 1024 * 1024;
+
+/// Profile of a user, every field is optional.
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub user_id: UserId,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub status_text: Option<String>,
+    pub status_expires_at: Option<OffsetDateTime>,
+    pub avatar_updated_at: Option<OffsetDateTime>,
+}
+
+impl Profile {
+    /// Status of the user, if it is set and not expired at `now`.
+    pub fn status(&self, now: OffsetDateTime) -> Option<UserStatus> {
+        let text = self.status_text.clone()?;
+        if self.status_expires_at.is_some_and(|expires_at| expires_at <= now) {
+            return None;
+        }
+
+        Some(UserStatus {
+            text,
+            expires_at: self.status_expires_at,
+        })
+    }
+
+    /// Path of the avatar image, versioned so clients can cache it forever.
+    pub fn avatar_url(&self) -> Option<String> {
+        self.avatar_updated_at.map(|updated_at| {
+            format!("/users/{}/avatar?v={}", self.user_id, updated_at.unix_timestamp_nanos() / 1_000_000)
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct UserStatus {
+    pub text: String,
+    #[serde(with = "time::serde::iso8601::option")]
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+/// Free-form name shown instead of the username, any script is allowed.
+#[derive(Deserialize, ToSchema)]
+pub struct DisplayName(String);
+
+impl DisplayName {
+    pub fn new<I: Into<String>>(name: I) -> Self {
+        Self(name.into())
+    }
+
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let trim = self.0.trim();
+
+        if trim.is_empty() {
+            errors.push("Display name is empty".to_owned());
+        }
+
+        if trim.chars().count() > DISPLAY_NAME_MAX_LENGTH {
+            errors.push(format!("Display name must be at most {DISPLAY_NAME_MAX_LENGTH} characters"));
+        }
+
+        if trim.chars().any(char::is_control) {
+            errors.push("Display name must not contain control characters".to_owned());
+        }
+
+        errors
+    }
+}
+
+impl Deref for DisplayName {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.trim()
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct Bio(String);
+
+impl Bio {
+    pub fn new<I: Into<String>>(bio: I) -> Self {
+        Self(bio.into())
+    }
+
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.0.trim().chars().count() > BIO_MAX_LENGTH {
+            errors.push(format!("Bio must be at most {BIO_MAX_LENGTH} characters"));
+        }
+
+        errors
+    }
+}
+
+impl Deref for Bio {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.trim()
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct StatusText(String);
+
+impl StatusText {
+    pub fn new<I: Into<String>>(text: I) -> Self {
+        Self(text.into())
+    }
+
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let trim = self.0.trim();
+
+        if trim.is_empty() {
+            errors.push("Status is empty".to_owned());
+        }
+
+        if trim.chars().count() > STATUS_MAX_LENGTH {
+            errors.push(format!("Status must be at most {STATUS_MAX_LENGTH} characters"));
+        }
+
+        if trim.chars().any(char::is_control) {
+            errors.push("Status must not contain control characters".to_owned());
+        }
+
+        errors
+    }
+}
+
+impl Deref for StatusText {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.trim()
+    }
+}
+
+/// Image formats accepted as avatars, recognized by their signature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AvatarFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+}
+
+impl AvatarFormat {
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        match data {
+            [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, ..] => Some(Self::Png),
+            [0xff, 0xd8, 0xff, ..] => Some(Self::Jpeg),
+            [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some(Self::Gif),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some(Self::Webp),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Gif => "image/gif",
+            Self::Webp => "image/webp",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Avatar {
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// Avatar image, immutable for a given `v` query parameter.
+pub struct GetAvatarResponse(pub Avatar);
+
+impl IntoResponse for GetAvatarResponse {
+    fn into_response(self) -> Response {
+        let Ok(content_type) = HeaderValue::from_str(&self.0.content_type) else {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, content_type);
+        headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("private, max-age=31536000, immutable"),
+        );
+        headers.insert(
+            header::X_CONTENT_TYPE_OPTIONS,
+            HeaderValue::from_static("nosniff"),
+        );
+
+        (StatusCode::OK, headers, self.0.data).into_response()
+    }
+}
+
+/// Replaces the whole profile text, missing fields are cleared.
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateProfileRequest {
+    pub display_name: Option<DisplayName>,
+    pub bio: Option<Bio>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SetStatusRequest {
+    pub text: StatusText,
+    /// When the status disappears, it is kept until cleared if missing.
+    #[serde(default, with = "time::serde::iso8601::option")]
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+#[derive(Serialize)]
+pub struct ProfileEvent {
+    pub user: PublicUser,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::Duration;
+
+    fn profile(status_expires_at: Option<OffsetDateTime>) -> Profile {
+        Profile {
+            user_id: UserId::new(1),
+            display_name: None,
+            bio: None,
+            status_text: Some("On vacation".to_owned()),
+            status_expires_at,
+            avatar_updated_at: None,
+        }
+    }
+
+    #[test]
+    fn test_display_name_counts_characters() {
+        assert!(DisplayName::new("Иван Петров").validate().is_empty());
+        assert!(DisplayName::new("名".repeat(DISPLAY_NAME_MAX_LENGTH)).validate().is_empty());
+        assert_eq!(DisplayName::new("名".repeat(DISPLAY_NAME_MAX_LENGTH + 1)).validate().len(), 1);
+        assert_eq!(DisplayName::new("  ").validate().len(), 1);
+        assert_eq!(DisplayName::new("a\u{0}b").validate().len(), 1);
+    }
+
+    #[test]
+    fn test_status_expiry() {
+        let now = OffsetDateTime::now_utc();
+
+        assert!(profile(None).status(now).is_some());
+        assert!(profile(Some(now + Duration::minutes(1))).status(now).is_some());
+        assert!(profile(Some(now)).status(now).is_none());
+    }
+
+    #[test]
+    fn test_avatar_format_detect() {
+        assert_eq!(AvatarFormat::detect(b"\x89PNG\r\n\x1a\n...."), Some(AvatarFormat::Png));
+        assert_eq!(AvatarFormat::detect(b"\xff\xd8\xff\xe0"), Some(AvatarFormat::Jpeg));
+        assert_eq!(AvatarFormat::detect(b"GIF89a"), Some(AvatarFormat::Gif));
+        assert_eq!(AvatarFormat::detect(b"RIFF\0\0\0\0WEBPVP8 "), Some(AvatarFormat::Webp));
+        assert_eq!(AvatarFormat::detect(b"<svg></svg>"), None);
+    }
+}
@@ -0,0 +1,49 @@
+use utoipa::ToSchema;
+use serde::{Deserialize, Serialize};
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use crate::models::{
+    chats::ChatId,
+    messages::MessageId,
+    users::UserId,
+};
+
+#[derive(Deserialize, ToSchema)]
+pub struct MarkReadRequest {
+    /// Latest message the user has seen, the messages before it are read too.
+    pub message_id: MessageId,
+}
+
+/// Read marker after the change, unchanged when it already was past the message.
+#[derive(Serialize, ToSchema)]
+pub struct ReadMarkerResponse {
+    pub chat_id: ChatId,
+    pub last_read_message_id: MessageId,
+}
+
+impl IntoResponse for ReadMarkerResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+/// Members other than the sender who read the message, leaving out those who disabled read receipts.
+#[derive(Serialize, ToSchema)]
+pub struct MessageReadersResponse {
+    pub users_ids: Vec<UserId>,
+}
+
+impl IntoResponse for MessageReadersResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ReadReceiptsSettingsRequest {
+    /// Whether other members see what the user has read.
+    pub enabled: bool,
+}
@@ -0,0 +1,122 @@
+use std::{convert::Infallible, net::SocketAddr, ops::Deref};
+use utoipa::ToSchema;
+use serde::{Deserialize, Serialize};
+use axum::{
+    Json,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{StatusCode, header, request::Parts},
+    response::{IntoResponse, Response},
+};
+use crate::models::users::UserId;
+
+#[derive(Debug, sqlx::Type, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, ToSchema)]
+#[sqlx(transparent)]
+pub struct SessionId(i32);
+
+impl SessionId {
+    pub fn new(id: i32) -> Self {
+        Self(id)
+    }
+}
+
+impl From<i32> for SessionId {
+    fn from(value: i32) -> Self {
+        Self(value)
+    }
+}
+
+impl std::fmt::Display for SessionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Deref for SessionId {
+    type Target = i32;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Session {
+    pub id: SessionId,
+    pub user_id: UserId,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: time::OffsetDateTime,
+    pub last_seen_at: time::OffsetDateTime,
+    pub expires_at: time::OffsetDateTime,
+    /// When an identity provider confirmed the user, set for sessions created by an external login.
+    pub external_auth_at: Option<time::OffsetDateTime>,
+}
+
+/// Device the session was created from.
+#[derive(Clone, Debug, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string());
+
+        Ok(Self { user_agent, ip })
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PublicSession {
+    pub id: SessionId,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    #[serde(with = "time::serde::iso8601")]
+    pub created_at: time::OffsetDateTime,
+    #[serde(with = "time::serde::iso8601")]
+    pub last_seen_at: time::OffsetDateTime,
+    pub current: bool,
+}
+
+impl PublicSession {
+    pub fn new(session: Session, current: SessionId) -> Self {
+        Self {
+            current: session.id == current,
+            id: session.id,
+            user_agent: session.user_agent,
+            ip: session.ip,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GetSessionsResponse(pub Vec<PublicSession>);
+
+impl IntoResponse for GetSessionsResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(ToSchema)]
+pub struct RemoveSessionsResponse;
+
+impl IntoResponse for RemoveSessionsResponse {
+    fn into_response(self) -> Response {
+        StatusCode::NO_CONTENT.into_response()
+    }
+}
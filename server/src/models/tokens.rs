@@ -0,0 +1,133 @@
+use std::ops::Deref;
+use utoipa::ToSchema;
+use strum::{AsRefStr, EnumString};
+use serde::{Deserialize, Serialize};
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use crate::models::users::UserId;
+
+pub const API_TOKEN_PREFIX: &str = "lt_";
+
+/// Permission granted to a personal API token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsRefStr, EnumString, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum Scope {
+    ReadMessages,
+    SendMessages,
+    ManageChats,
+}
+
+#[derive(Debug, sqlx::Type, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, ToSchema)]
+#[sqlx(transparent)]
+pub struct ApiTokenId(i32);
+
+impl ApiTokenId {
+    pub fn new(id: i32) -> Self {
+        Self(id)
+    }
+}
+
+impl std::fmt::Display for ApiTokenId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Deref for ApiTokenId {
+    type Target = i32;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct ApiToken {
+    pub id: ApiTokenId,
+    #[serde(skip)]
+    pub user_id: UserId,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    #[serde(with = "time::serde::iso8601")]
+    pub created_at: time::OffsetDateTime,
+    #[serde(with = "time::serde::iso8601::option")]
+    pub last_used_at: Option<time::OffsetDateTime>,
+    #[serde(with = "time::serde::iso8601::option")]
+    pub expires_at: Option<time::OffsetDateTime>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TokenName(String);
+
+impl TokenName {
+    pub fn new<I: Into<String>>(name: I) -> Self {
+        Self(name.into())
+    }
+
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let trim = self.0.trim();
+
+        if trim.is_empty() {
+            errors.push("Name is empty".to_owned());
+        }
+
+        if trim.chars().count() > 50 {
+            errors.push("Name must be less than 50 characters".to_owned());
+        }
+
+        errors
+    }
+}
+
+impl Deref for TokenName {
+    type Target = String;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct NewTokenRequest {
+    pub name: TokenName,
+    pub scopes: Vec<Scope>,
+    /// Lifetime of the token in days, the token never expires if omitted.
+    pub expires_in_days: Option<u16>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct NewTokenResponse {
+    /// Secret value, shown only once.
+    pub token: String,
+    #[serde(flatten)]
+    pub info: ApiToken,
+}
+
+impl IntoResponse for NewTokenResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::CREATED, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GetTokensResponse(pub Vec<ApiToken>);
+
+impl IntoResponse for GetTokensResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(ToSchema)]
+pub struct RemoveTokenResponse;
+
+impl IntoResponse for RemoveTokenResponse {
+    fn into_response(self) -> Response {
+        StatusCode::NO_CONTENT.into_response()
+    }
+}
@@ -0,0 +1,108 @@
+use utoipa::ToSchema;
+use serde::{Deserialize, Serialize};
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use crate::models::users::UserId;
+
+/// Lifetime of the token handed out between the password and the TOTP step of a login.
+pub const PENDING_LOGIN_LIFETIME: i64 = 60 * 5;
+/// Wrong codes accepted for a single pending login before it is dropped.
+pub const MAX_PENDING_ATTEMPTS: i32 = 5;
+pub const RECOVERY_CODES_COUNT: usize = 10;
+
+/// TOTP enrollment of a user, `enabled` is false until the first code is confirmed.
+#[derive(Clone, Debug)]
+pub struct Totp {
+    pub user_id: UserId,
+    /// Base32 encoded shared secret.
+    pub secret: String,
+    pub enabled: bool,
+    /// Last accepted time step, codes of this or earlier steps are rejected.
+    pub last_step: i64,
+}
+
+#[derive(Clone, Debug)]
+pub struct PendingLogin {
+    pub user_id: UserId,
+    pub attempts: i32,
+    pub expires_at: time::OffsetDateTime,
+}
+
+/// Six digit TOTP code or a recovery code.
+#[derive(Deserialize, ToSchema)]
+pub struct TotpCode(String);
+
+impl TotpCode {
+    pub fn new<I: Into<String>>(code: I) -> Self {
+        Self(code.into())
+    }
+
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.0.trim().is_empty() {
+            errors.push("Code is empty".to_owned());
+        }
+
+        errors
+    }
+}
+
+impl AsRef<str> for TotpCode {
+    fn as_ref(&self) -> &str {
+        self.0.trim()
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct EnrollTotpResponse {
+    /// Base32 secret for manual entry.
+    pub secret: String,
+    /// `otpauth://` URI, usually rendered as a QR code.
+    pub otpauth_uri: String,
+}
+
+impl IntoResponse for EnrollTotpResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::CREATED, Json(self)).into_response()
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ConfirmTotpRequest {
+    pub code: TotpCode,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ConfirmTotpResponse {
+    /// Single-use codes replacing the authenticator app, shown only once.
+    pub recovery_codes: Vec<String>,
+}
+
+impl IntoResponse for ConfirmTotpResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct DisableTotpRequest {
+    pub code: TotpCode,
+}
+
+#[derive(ToSchema)]
+pub struct DisableTotpResponse;
+
+impl IntoResponse for DisableTotpResponse {
+    fn into_response(self) -> Response {
+        StatusCode::NO_CONTENT.into_response()
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct LoginTotpRequest {
+    pub pending_token: String,
+    pub code: TotpCode,
+}
@@ -1,17 +1,25 @@
-use sha2::Digest;
 use time::Duration;
 use std::ops::Deref;
 use utoipa::ToSchema;
-use cookie::CookieBuilder;
 use serde::{Deserialize, Serialize};
 use axum::{
     Json,
     response::IntoResponse,
     http::{HeaderMap, HeaderValue, StatusCode, header},
 };
-use crate::services::auth::SESSION_COOKIE_NAME;
+use crate::{
+    models::profiles::{Profile, UserStatus},
+    services::cookies::{removal_cookie, session_cookie},
+};
 
+/// Idle lifetime of a session, extended while the session is in use.
 pub const SESSION_LIFETIME: i64 = 60 * 60 * 24 * 7;
+/// How long a session may be kept alive by renewals before a new login is required.
+pub const SESSION_MAX_LIFETIME: i64 = 60 * 60 * 24 * 30;
+/// Minimal time between two renewals of the same session.
+pub const SESSION_RENEWAL_INTERVAL: i64 = 60 * 60 * 24;
+/// How long an identity provider login confirms sensitive changes of an account without a password.
+pub const EXTERNAL_AUTH_MAX_AGE: i64 = 60 * 5;
 
 #[derive(Deserialize, ToSchema)]
 pub struct Username(String);
@@ -131,6 +139,10 @@ pub struct User {
     pub id: UserId,
     pub username: String,
     pub password: String,
+    pub is_bot: bool,
+    pub is_admin: bool,
+    /// Suspended users can't sign in and their sessions and tokens are rejected.
+    pub suspended_at: Option<time::OffsetDateTime>,
     pub created_at: time::OffsetDateTime,
 }
 
@@ -143,42 +155,57 @@ pub struct LoginUserRequest {
 #[derive(Serialize, ToSchema)]
 pub struct LoginUserResponse {
     pub user_id: UserId,
+    /// Whether a TOTP or recovery code must still be sent to `/login/totp`.
+    pub second_factor_required: bool,
+    /// Short-lived token identifying the login at the second step.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending_token: Option<String>,
     #[serde(skip)]
-    pub session: String,
+    pub session: Option<String>,
 }
 
 impl LoginUserResponse {
     pub fn new(user_id: UserId, session: String) -> Self {
-        Self { user_id, session }
+        Self {
+            user_id,
+            second_factor_required: false,
+            pending_token: None,
+            session: Some(session),
+        }
+    }
+
+    pub fn second_factor(user_id: UserId, pending_token: String) -> Self {
+        Self {
+            user_id,
+            second_factor_required: true,
+            pending_token: Some(pending_token),
+            session: None,
+        }
     }
 }
 
 impl IntoResponse for LoginUserResponse {
     fn into_response(self) -> axum::response::Response {
-        let cookie = CookieBuilder::new(SESSION_COOKIE_NAME, self.session)
-            .max_age(Duration::seconds(SESSION_LIFETIME))
-            .build();
+        let Some(session) = &self.session else {
+            return (StatusCode::OK, Json(self)).into_response();
+        };
 
         let mut headers = HeaderMap::new();
         headers.insert(
             header::SET_COOKIE,
-            HeaderValue::from_str(cookie.to_string().as_str()).unwrap(),
+            session_cookie(session, Duration::seconds(SESSION_LIFETIME)),
         );
 
-        (StatusCode::CREATED, headers, Json(self.user_id)).into_response()
+        (StatusCode::CREATED, headers, Json(self)).into_response()
     }
 }
 
-pub struct PasswordHash(String, String);
+/// Encoded Argon2 PHC string, as stored in `Users.Password`.
+pub struct PasswordHash(String);
 
 impl PasswordHash {
-    pub fn new(password: &str, salt: &str) -> Self {
-        let hash = sha2::Sha256::digest(format!("{password}{salt}").as_bytes());
-        Self(hex::encode(hash), salt.to_owned())
-    }
-
-    pub fn get_salt(&self) -> &str {
-        &self.1
+    pub fn new(hash: String) -> Self {
+        Self(hash)
     }
 }
 
@@ -195,15 +222,11 @@ pub struct LogoutUserResponse;
 
 impl IntoResponse for LogoutUserResponse {
     fn into_response(self) -> axum::response::Response {
-        (
-            StatusCode::TEMPORARY_REDIRECT,
-            [
-                ("Set-Cookie", format!("{SESSION_COOKIE_NAME}=_; Max-Age=0")),
-                ("Location", "/".to_owned()),
-            ],
-            (),
-        )
-            .into_response()
+        let mut headers = HeaderMap::new();
+        headers.insert(header::SET_COOKIE, removal_cookie());
+        headers.insert(header::LOCATION, HeaderValue::from_static("/"));
+
+        (StatusCode::SEE_OTHER, headers).into_response()
     }
 }
 
@@ -211,8 +234,23 @@ impl IntoResponse for LogoutUserResponse {
 pub struct PublicUser {
     pub id: UserId,
     pub username: String,
+    pub is_bot: bool,
     #[serde(with = "time::serde::iso8601")]
     pub created_at: time::OffsetDateTime,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    pub status: Option<UserStatus>,
+}
+
+impl PublicUser {
+    pub fn with_profile(mut self, profile: Profile, now: time::OffsetDateTime) -> Self {
+        self.status = profile.status(now);
+        self.avatar_url = profile.avatar_url();
+        self.display_name = profile.display_name;
+        self.bio = profile.bio;
+        self
+    }
 }
 
 impl From<User> for PublicUser {
@@ -220,7 +258,12 @@ impl From<User> for PublicUser {
         Self {
             id: user.id,
             username: user.username,
+            is_bot: user.is_bot,
             created_at: user.created_at,
+            display_name: None,
+            bio: None,
+            avatar_url: None,
+            status: None,
         }
     }
 }
@@ -235,4 +278,37 @@ impl IntoResponse for GetUserResponse {
     fn into_response(self) -> axum::response::Response {
         Json(self.user).into_response()
     }
-}
\ No newline at end of file
+}
+#[derive(Deserialize, ToSchema)]
+pub struct ChangePasswordRequest {
+    pub current_password: Password,
+    pub new_password: Password,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ChangeUsernameRequest {
+    pub username: Username,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct DeleteAccountRequest {
+    pub password: Password,
+}
+
+#[derive(ToSchema)]
+pub struct UpdateAccountResponse;
+
+impl IntoResponse for UpdateAccountResponse {
+    fn into_response(self) -> axum::response::Response {
+        StatusCode::NO_CONTENT.into_response()
+    }
+}
+
+#[derive(ToSchema)]
+pub struct DeleteAccountResponse;
+
+impl IntoResponse for DeleteAccountResponse {
+    fn into_response(self) -> axum::response::Response {
+        (StatusCode::NO_CONTENT, [(header::SET_COOKIE, removal_cookie())]).into_response()
+    }
+}
@@ -1,22 +1,160 @@
-use rand::{Rng, SeedableRng, rngs::SmallRng};
+use rand::{Rng, RngCore, SeedableRng, rngs::StdRng};
+use crate::services::totp::encode_secret;
 
-pub struct SmallRandom(SmallRng);
+const SALT_BYTES: usize = 16;
+const SESSION_UID_BYTES: usize = 32;
+const API_TOKEN_BYTES: usize = 32;
+const WEBHOOK_SECRET_BYTES: usize = 32;
+const EXPORT_TOKEN_BYTES: usize = 32;
+const INVITE_CODE_BYTES: usize = 12;
+const TOTP_SECRET_BYTES: usize = 20;
+const RECOVERY_CODE_BYTES: usize = 5;
 
+/// Cryptographically secure generator seeded from the operating system.
+pub struct SecureRandom(StdRng);
+
+impl SecureRandom {
+    pub fn new() -> Self {
+        SecureRandom(StdRng::from_os_rng())
+    }
+}
+
+impl Default for SecureRandom {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Deterministic generator, only meant for reproducible tests.
+#[cfg(test)]
+pub struct SmallRandom(rand::rngs::SmallRng);
+
+#[cfg(test)]
 impl SmallRandom {
     pub fn new(seed: u64) -> Self {
-        SmallRandom(SmallRng::seed_from_u64(seed))
+        SmallRandom(rand::rngs::SmallRng::seed_from_u64(seed))
     }
 }
 
 #[cfg_attr(test, mockall::automock)]
 pub trait RandomGenerator: Sync + Send {
     fn get_salt(&mut self) -> String;
+    fn get_session_uid(&mut self) -> String;
+    fn get_api_token(&mut self) -> String;
+    fn get_webhook_secret(&mut self) -> String;
+    fn get_export_token(&mut self) -> String;
+    fn get_invite_code(&mut self) -> String;
+    /// Base32 encoded TOTP secret.
+    fn get_totp_secret(&mut self) -> String;
+    /// Recovery code formatted as `xxxxx-xxxxx`.
+    fn get_recovery_code(&mut self) -> String;
+}
+
+impl RandomGenerator for SecureRandom {
+    fn get_salt(&mut self) -> String {
+        random_hex::<SALT_BYTES>(&mut self.0)
+    }
+
+    fn get_session_uid(&mut self) -> String {
+        random_hex::<SESSION_UID_BYTES>(&mut self.0)
+    }
+
+    fn get_api_token(&mut self) -> String {
+        random_hex::<API_TOKEN_BYTES>(&mut self.0)
+    }
+
+    fn get_webhook_secret(&mut self) -> String {
+        random_hex::<WEBHOOK_SECRET_BYTES>(&mut self.0)
+    }
+
+    fn get_export_token(&mut self) -> String {
+        random_hex::<EXPORT_TOKEN_BYTES>(&mut self.0)
+    }
+
+    fn get_invite_code(&mut self) -> String {
+        random_hex::<INVITE_CODE_BYTES>(&mut self.0)
+    }
+
+    fn get_totp_secret(&mut self) -> String {
+        encode_secret(&random_bytes::<TOTP_SECRET_BYTES>(&mut self.0))
+    }
+
+    fn get_recovery_code(&mut self) -> String {
+        recovery_code(&mut self.0)
+    }
 }
 
+#[cfg(test)]
 impl RandomGenerator for SmallRandom {
     fn get_salt(&mut self) -> String {
-        let mut result = [0u8; 16];
-        self.0.fill(&mut result);
-        hex::encode(result)
+        random_hex::<SALT_BYTES>(&mut self.0)
+    }
+
+    fn get_session_uid(&mut self) -> String {
+        random_hex::<SESSION_UID_BYTES>(&mut self.0)
     }
-}
\ No newline at end of file
+
+    fn get_api_token(&mut self) -> String {
+        random_hex::<API_TOKEN_BYTES>(&mut self.0)
+    }
+
+    fn get_webhook_secret(&mut self) -> String {
+        random_hex::<WEBHOOK_SECRET_BYTES>(&mut self.0)
+    }
+
+    fn get_export_token(&mut self) -> String {
+        random_hex::<EXPORT_TOKEN_BYTES>(&mut self.0)
+    }
+
+    fn get_invite_code(&mut self) -> String {
+        random_hex::<INVITE_CODE_BYTES>(&mut self.0)
+    }
+
+    fn get_totp_secret(&mut self) -> String {
+        encode_secret(&random_bytes::<TOTP_SECRET_BYTES>(&mut self.0))
+    }
+
+    fn get_recovery_code(&mut self) -> String {
+        recovery_code(&mut self.0)
+    }
+}
+
+fn random_bytes<const N: usize>(rng: &mut impl RngCore) -> [u8; N] {
+    let mut result = [0u8; N];
+    rng.fill(&mut result);
+    result
+}
+
+fn random_hex<const N: usize>(rng: &mut impl RngCore) -> String {
+    hex::encode(random_bytes::<N>(rng))
+}
+
+fn recovery_code(rng: &mut impl RngCore) -> String {
+    let code = random_hex::<RECOVERY_CODE_BYTES>(rng);
+    format!("{}-{}", &code[..5], &code[5..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_random_is_deterministic() {
+        let mut first = SmallRandom::new(42);
+        let mut second = SmallRandom::new(42);
+
+        assert_eq!(first.get_salt(), second.get_salt());
+        assert_eq!(first.get_session_uid(), second.get_session_uid());
+    }
+
+    #[test]
+    fn test_secure_random_lengths() {
+        let mut random = SecureRandom::new();
+
+        assert_eq!(random.get_salt().len(), SALT_BYTES * 2);
+        assert_eq!(random.get_session_uid().len(), SESSION_UID_BYTES * 2);
+        assert_ne!(random.get_session_uid(), random.get_session_uid());
+        assert_eq!(random.get_totp_secret().len(), 32);
+        assert_eq!(random.get_recovery_code().len(), 11);
+    }
+}
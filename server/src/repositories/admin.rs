@@ -0,0 +1,177 @@
+use sqlx::{PgPool, query, query_as, query_scalar};
+use time::OffsetDateTime;
+use crate::{
+    error::RepositoryError,
+    repositories::escape_like,
+    models::{
+        admin::{AdminAction, AdminActionKind, AdminUser},
+        users::UserId,
+    },
+};
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait AdminRepository: Send + Sync {
+    /// Users ordered by id, optionally filtered by a part of the username.
+    async fn get_users(
+        &self,
+        username: Option<String>,
+        after: Option<UserId>,
+        limit: i64,
+    ) -> Result<Vec<AdminUser>, RepositoryError>;
+    async fn get_user(&self, user_id: UserId) -> Result<AdminUser, RepositoryError>;
+    /// Grants or revokes the administrator role, fails with `NotFound` for unknown usernames.
+    async fn set_admin(&self, username: &str, is_admin: bool) -> Result<UserId, RepositoryError>;
+    /// Suspends or restores the user together with the bots it owns.
+    async fn set_suspended(&self, user_id: UserId, suspended: bool) -> Result<(), RepositoryError>;
+    async fn record_action(
+        &self,
+        admin_id: UserId,
+        action: AdminActionKind,
+        target_id: UserId,
+        target_name: &str,
+    ) -> Result<(), RepositoryError>;
+    /// Most recent actions first.
+    async fn get_actions(&self, before: Option<i32>, limit: i64) -> Result<Vec<AdminAction>, RepositoryError>;
+}
+
+pub struct PgAdminRepository(PgPool);
+
+impl PgAdminRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self(pool)
+    }
+}
+
+struct AdminActionRow {
+    id: i32,
+    admin_id: Option<UserId>,
+    action: String,
+    target_id: UserId,
+    target_name: String,
+    created_at: OffsetDateTime,
+}
+
+impl AdminActionRow {
+    fn into_action(self) -> Option<AdminAction> {
+        Some(AdminAction {
+            id: self.id,
+            admin_id: self.admin_id,
+            action: self.action.parse().ok()?,
+            target_id: self.target_id,
+            target_name: self.target_name,
+            created_at: self.created_at,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl AdminRepository for PgAdminRepository {
+    async fn get_users(
+        &self,
+        username: Option<String>,
+        after: Option<UserId>,
+        limit: i64,
+    ) -> Result<Vec<AdminUser>, RepositoryError> {
+        let pattern = username.map(|username| format!("%{}%", escape_like(&username)));
+        let users = query_as!(
+            AdminUser,
+            "SELECT Id as \"id: _\", Name as username, IsBot as is_bot, IsAdmin as is_admin,
+                SuspendedAt as suspended_at, CreatedAt as created_at
+            FROM Users
+            WHERE ($1::TEXT IS NULL OR Name ILIKE $1) AND ($2::INT IS NULL OR Id > $2)
+            ORDER BY Id
+            LIMIT $3",
+            pattern,
+            after as _,
+            limit
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(users)
+    }
+
+    async fn get_user(&self, user_id: UserId) -> Result<AdminUser, RepositoryError> {
+        let user = query_as!(
+            AdminUser,
+            "SELECT Id as \"id: _\", Name as username, IsBot as is_bot, IsAdmin as is_admin,
+                SuspendedAt as suspended_at, CreatedAt as created_at
+            FROM Users WHERE Id = $1",
+            user_id as _
+        )
+        .fetch_one(&self.0)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn set_admin(&self, username: &str, is_admin: bool) -> Result<UserId, RepositoryError> {
+        let user_id = query_scalar!(
+            "UPDATE Users SET IsAdmin = $2 WHERE Name = $1 AND NOT IsBot RETURNING Id as \"id: _\"",
+            username,
+            is_admin
+        )
+        .fetch_one(&self.0)
+        .await?;
+
+        Ok(user_id)
+    }
+
+    async fn set_suspended(&self, user_id: UserId, suspended: bool) -> Result<(), RepositoryError> {
+        let result = query!(
+            "UPDATE Users
+            SET SuspendedAt = CASE WHEN $2 THEN COALESCE(SuspendedAt, NOW()) ELSE NULL END
+            WHERE Id = $1 OR Id IN (SELECT UserId FROM Bots WHERE OwnerId = $1)",
+            user_id as _,
+            suspended
+        )
+        .execute(&self.0)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn record_action(
+        &self,
+        admin_id: UserId,
+        action: AdminActionKind,
+        target_id: UserId,
+        target_name: &str,
+    ) -> Result<(), RepositoryError> {
+        query!(
+            "INSERT INTO AdminActions (AdminId, Action, TargetId, TargetName) VALUES ($1, $2, $3, $4)",
+            admin_id as _,
+            action.as_ref(),
+            target_id as _,
+            target_name
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_actions(&self, before: Option<i32>, limit: i64) -> Result<Vec<AdminAction>, RepositoryError> {
+        let rows = query_as!(
+            AdminActionRow,
+            "SELECT Id, AdminId as \"admin_id: _\", Action, TargetId as \"target_id: _\",
+                TargetName as target_name, CreatedAt as created_at
+            FROM AdminActions
+            WHERE $1::INT IS NULL OR Id < $1
+            ORDER BY Id DESC
+            LIMIT $2",
+            before,
+            limit
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(rows.into_iter().filter_map(AdminActionRow::into_action).collect())
+    }
+}
+
@@ -0,0 +1,94 @@
+use sqlx::{PgPool, query, query_as, query_scalar};
+use crate::{
+    error::RepositoryError,
+    models::{blocks::BlockedUser, users::UserId},
+};
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait BlocksRepository: Send + Sync {
+    /// Fails with `NotFound` when the blocked user does not exist, blocking twice is fine.
+    async fn block_user(&self, blocker_id: UserId, blocked_id: UserId) -> Result<(), RepositoryError>;
+    async fn unblock_user(&self, blocker_id: UserId, blocked_id: UserId) -> Result<(), RepositoryError>;
+    async fn get_blocked_users(&self, blocker_id: UserId) -> Result<Vec<BlockedUser>, RepositoryError>;
+    /// Those of `users_ids` who blocked the user.
+    async fn get_blockers(&self, blocked_id: UserId, users_ids: &[UserId]) -> Result<Vec<UserId>, RepositoryError>;
+}
+
+pub struct PgBlocksRepository(PgPool);
+
+impl PgBlocksRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self(pool)
+    }
+}
+
+#[async_trait::async_trait]
+impl BlocksRepository for PgBlocksRepository {
+    async fn block_user(&self, blocker_id: UserId, blocked_id: UserId) -> Result<(), RepositoryError> {
+        let exists = query_scalar!(
+            "SELECT EXISTS (SELECT 1 FROM Users WHERE Id = $1) as \"exists!\"",
+            blocked_id as _
+        )
+        .fetch_one(&self.0)
+        .await?;
+
+        if !exists {
+            return Err(RepositoryError::NotFound);
+        }
+
+        query!(
+            "INSERT INTO Blocks (BlockerId, BlockedId) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            blocker_id as _,
+            blocked_id as _
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn unblock_user(&self, blocker_id: UserId, blocked_id: UserId) -> Result<(), RepositoryError> {
+        let result = query!(
+            "DELETE FROM Blocks WHERE BlockerId = $1 AND BlockedId = $2",
+            blocker_id as _,
+            blocked_id as _
+        )
+        .execute(&self.0)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn get_blocked_users(&self, blocker_id: UserId) -> Result<Vec<BlockedUser>, RepositoryError> {
+        let users = query_as!(
+            BlockedUser,
+            "SELECT b.BlockedId as \"user_id: _\", u.Name as username, b.CreatedAt as blocked_at
+            FROM Blocks b JOIN Users u ON u.Id = b.BlockedId
+            WHERE b.BlockerId = $1
+            ORDER BY b.CreatedAt DESC",
+            blocker_id as _
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(users)
+    }
+
+    async fn get_blockers(&self, blocked_id: UserId, users_ids: &[UserId]) -> Result<Vec<UserId>, RepositoryError> {
+        let blockers = query_scalar!(
+            "SELECT BlockerId as \"blocker_id: _\" FROM Blocks
+            WHERE BlockedId = $1 AND BlockerId = ANY($2::int[])",
+            blocked_id as _,
+            users_ids as _
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(blockers)
+    }
+}
@@ -0,0 +1,143 @@
+use sqlx::{PgPool, query, query_as};
+use crate::{
+    error::RepositoryError,
+    models::{
+        bots::{Bot, BotWebhook},
+        tokens::Scope,
+        users::UserId,
+    },
+};
+
+/// Name of the API token issued together with a bot.
+const BOT_TOKEN_NAME: &str = "bot";
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait BotsRepository: Send + Sync {
+    /// Creates the bot user together with its API token, bots never get a password.
+    async fn create_bot(
+        &self,
+        owner_id: UserId,
+        username: &str,
+        webhook_url: Option<String>,
+        webhook_secret: &str,
+        token_hash: &str,
+    ) -> Result<Bot, RepositoryError>;
+
+    async fn get_owner_bots(&self, owner_id: UserId) -> Result<Vec<Bot>, RepositoryError>;
+    async fn remove_bot(&self, owner_id: UserId, bot_id: UserId) -> Result<(), RepositoryError>;
+    async fn get_webhooks(&self, users: &[UserId]) -> Result<Vec<BotWebhook>, RepositoryError>;
+}
+
+pub struct PgBotsRepository(PgPool);
+
+impl PgBotsRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self(pool)
+    }
+}
+
+#[async_trait::async_trait]
+impl BotsRepository for PgBotsRepository {
+    async fn create_bot(
+        &self,
+        owner_id: UserId,
+        username: &str,
+        webhook_url: Option<String>,
+        webhook_secret: &str,
+        token_hash: &str,
+    ) -> Result<Bot, RepositoryError> {
+        let scopes = [Scope::ReadMessages, Scope::SendMessages, Scope::ManageChats]
+            .iter()
+            .map(|scope| scope.as_ref().to_owned())
+            .collect::<Vec<_>>();
+
+        let mut tn = self.0.begin().await?;
+
+        let (id, created_at) = query!(
+            "INSERT INTO Users (Name, Password, IsBot) VALUES ($1, '', TRUE)
+            RETURNING Id, CreatedAt as created_at",
+            username,
+        )
+        .fetch_one(&mut *tn)
+        .await
+        .map(|row| (UserId::new(row.id), row.created_at))?;
+
+        query!(
+            "INSERT INTO Bots (UserId, OwnerId, WebhookUrl, WebhookSecret) VALUES ($1, $2, $3, $4)",
+            id as _,
+            owner_id as _,
+            webhook_url.as_deref(),
+            webhook_secret,
+        )
+        .execute(&mut *tn)
+        .await?;
+
+        query!(
+            "INSERT INTO ApiTokens (UserId, Name, TokenHash, Scopes) VALUES ($1, $2, $3, $4)",
+            id as _,
+            BOT_TOKEN_NAME,
+            token_hash,
+            &scopes,
+        )
+        .execute(&mut *tn)
+        .await?;
+
+        tn.commit().await?;
+
+        Ok(Bot {
+            id,
+            username: username.to_owned(),
+            owner_id,
+            webhook_url,
+            created_at,
+        })
+    }
+
+    async fn get_owner_bots(&self, owner_id: UserId) -> Result<Vec<Bot>, RepositoryError> {
+        let bots = query_as!(
+            Bot,
+            "SELECT u.Id as \"id: _\", u.Name as username, b.OwnerId as \"owner_id: _\",
+                b.WebhookUrl as webhook_url, u.CreatedAt as created_at
+            FROM Bots b JOIN Users u ON u.Id = b.UserId
+            WHERE b.OwnerId = $1
+            ORDER BY u.CreatedAt",
+            owner_id as _
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(bots)
+    }
+
+    async fn remove_bot(&self, owner_id: UserId, bot_id: UserId) -> Result<(), RepositoryError> {
+        let result = query!(
+            "DELETE FROM Users
+            WHERE Id = $2 AND Id IN (SELECT UserId FROM Bots WHERE OwnerId = $1)",
+            owner_id as _,
+            bot_id as _
+        )
+        .execute(&self.0)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn get_webhooks(&self, users: &[UserId]) -> Result<Vec<BotWebhook>, RepositoryError> {
+        let webhooks = query_as!(
+            BotWebhook,
+            "SELECT UserId as \"bot_id: _\", WebhookUrl as \"url!\", WebhookSecret as secret
+            FROM Bots
+            WHERE UserId = ANY($1::int[]) AND WebhookUrl IS NOT NULL",
+            users as _
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(webhooks)
+    }
+}
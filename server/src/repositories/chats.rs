@@ -1,10 +1,25 @@
-use sqlx::{PgPool, query, query_as, query_scalar};
+use sqlx::{PgConnection, PgPool, query, query_as, query_scalar};
 use std::collections::HashSet;
-use futures_util::StreamExt;
+use time::OffsetDateTime;
 use crate::{
     error::RepositoryError,
+    repositories::escape_like,
     models::{
-        chats::{Chat, ChatId, ChatTitle},
+        chats::{
+            UNREAD_COUNT_LIMIT,
+            Channel,
+            Chat,
+            ChatCursor,
+            ChatId,
+            ChatKind,
+            ChatMember,
+            ChatRole,
+            ChatSummary,
+            ChatTitle,
+            ChatVisibility,
+        },
+        messages::{MESSAGE_PREVIEW_LENGTH, MessageId, MessagePreview},
+        profiles::Avatar,
         users::UserId,
     },
 };
@@ -12,17 +27,118 @@ use crate::{
 #[cfg_attr(test, mockall::automock)]
 #[async_trait::async_trait]
 pub trait ChatsRepository: Send + Sync {
+    /// Creates the chat with `owner_id` as its owner, the other users join as members.
     async fn create_chat(
         &self,
         title: &ChatTitle,
         users: &[UserId],
+        owner_id: UserId,
+        visibility: ChatVisibility,
     ) -> Result<ChatId, RepositoryError>;
 
     async fn remove_chat(&self, chat_id: ChatId) -> Result<(), RepositoryError>;
     async fn get_user_chats(&self, user_id: UserId) -> Result<Vec<Chat>, RepositoryError>;
-    async fn get_user_chats_ids(&self, user_id: UserId)
-    -> Result<HashSet<ChatId>, RepositoryError>;
+    /// Chats of the user with their latest message and unread count, most recently active first.
+    ///
+    /// Only chats ordered after `before` are returned.
+    async fn get_chat_list(
+        &self,
+        user_id: UserId,
+        before: Option<ChatCursor>,
+        limit: i64,
+    ) -> Result<Vec<ChatSummary>, RepositoryError>;
+    /// Direct chats are titled as seen by `viewer_id`.
+    async fn get_chat(&self, chat_id: ChatId, viewer_id: UserId) -> Result<Chat, RepositoryError>;
+    /// Returns the direct chat of the two users, creating it if needed, and who was added to it.
+    ///
+    /// Participants who left are added back. Fails with `NotFound` if `peer_id` does not exist.
+    async fn get_or_create_direct_chat(
+        &self,
+        user_id: UserId,
+        peer_id: UserId,
+    ) -> Result<(ChatId, Vec<UserId>), RepositoryError>;
     async fn get_chat_members(&self, chat_id: ChatId) -> Result<Vec<UserId>, RepositoryError>;
+    /// Members with their roles, in the order they joined.
+    async fn get_members(&self, chat_id: ChatId) -> Result<Vec<ChatMember>, RepositoryError>;
+    /// Fails with `NotFound` when the user is not a member of the chat.
+    async fn get_member_role(&self, chat_id: ChatId, user_id: UserId) -> Result<ChatRole, RepositoryError>;
+    /// Sets the role of a member other than the owner, use `transfer_ownership` to change owners.
+    async fn set_member_role(&self, chat_id: ChatId, user_id: UserId, role: ChatRole) -> Result<(), RepositoryError>;
+    /// Makes the member the owner, the previous owner becomes an admin.
+    async fn transfer_ownership(&self, chat_id: ChatId, owner_id: UserId, user_id: UserId) -> Result<(), RepositoryError>;
+    /// Adds the users to the chat and returns those who were not members yet.
+    ///
+    /// New members start with the earlier messages read. Fails with `NotFound` without adding anyone if one of the users does not exist.
+    async fn add_chat_members(&self, chat_id: ChatId, users_ids: &[UserId]) -> Result<Vec<UserId>, RepositoryError>;
+    /// Removes the member and returns the remaining ones, the chat is deleted once nobody is left.
+    ///
+    /// When the owner of a group leaves, the earliest admin or else the earliest member becomes the owner.
+    async fn remove_chat_member(&self, chat_id: ChatId, user_id: UserId) -> Result<Vec<UserId>, RepositoryError>;
+    /// Users sharing at least one chat with the user, the user excluded.
+    async fn get_chat_peers(&self, user_id: UserId) -> Result<Vec<UserId>, RepositoryError>;
+    /// Changes the given fields and bumps the version, `Some(None)` clears the description.
+    ///
+    /// Fails with `Conflict` when `expected_version` is given and no longer current.
+    async fn update_chat<'a>(
+        &self,
+        chat_id: ChatId,
+        title: Option<&'a ChatTitle>,
+        description: Option<Option<&'a str>>,
+        visibility: Option<ChatVisibility>,
+        expected_version: Option<i32>,
+    ) -> Result<Chat, RepositoryError>;
+    /// Same version check as `update_chat`.
+    async fn set_chat_image(
+        &self,
+        chat_id: ChatId,
+        content_type: &str,
+        data: &[u8],
+        expected_version: Option<i32>,
+    ) -> Result<Chat, RepositoryError>;
+    /// Same version check as `update_chat`, fails with `NotFound` when the chat has no image.
+    async fn remove_chat_image(&self, chat_id: ChatId, expected_version: Option<i32>) -> Result<Chat, RepositoryError>;
+    async fn get_chat_image(&self, chat_id: ChatId) -> Result<Avatar, RepositoryError>;
+    /// Public chats matching `query` by title or description, newest first, as seen by `user_id`.
+    async fn get_public_chats(
+        &self,
+        user_id: UserId,
+        query: Option<String>,
+        before: Option<ChatId>,
+        limit: i64,
+    ) -> Result<Vec<Channel>, RepositoryError>;
+    /// Adds the user to a public chat, returns whether they were not a member yet.
+    ///
+    /// Fails with `NotFound` when the chat does not exist or is not public.
+    async fn join_public_chat(&self, chat_id: ChatId, user_id: UserId) -> Result<bool, RepositoryError>;
+}
+
+/// Stored title of direct chats, only shown once the other participant deleted their account.
+const DIRECT_CHAT_TITLE: &str = "Direct chat";
+
+struct ChatRow {
+    id: ChatId,
+    kind: String,
+    visibility: String,
+    title: ChatTitle,
+    users_ids: Vec<UserId>,
+    description: Option<String>,
+    image_updated_at: Option<OffsetDateTime>,
+    version: i32,
+}
+
+impl From<ChatRow> for Chat {
+    fn from(row: ChatRow) -> Self {
+        Chat {
+            image_url: Chat::image_url(row.id, row.image_updated_at),
+            id: row.id,
+            kind: row.kind.parse().unwrap_or(ChatKind::Group),
+            visibility: row.visibility.parse().unwrap_or_default(),
+            title: row.title,
+            users_ids: row.users_ids,
+            description: row.description,
+            version: row.version,
+        }
+    }
 }
 
 pub struct PgChatsRepository(PgPool);
@@ -39,22 +155,27 @@ impl ChatsRepository for PgChatsRepository {
         &self,
         title: &ChatTitle,
         users: &[UserId],
+        owner_id: UserId,
+        visibility: ChatVisibility,
     ) -> Result<ChatId, RepositoryError> {
         let mut tn = self.0.begin().await?;
 
         let chat_id = ChatId::new(
             sqlx::query_scalar!(
-                "INSERT INTO Chats (Title) VALUES ($1) RETURNING Id",
-                title as _
+                "INSERT INTO Chats (Title, Visibility) VALUES ($1, $2) RETURNING Id",
+                title as _,
+                visibility.as_ref()
             )
             .fetch_one(&mut *tn)
             .await?,
         );
 
         query!(
-            "INSERT INTO ChatMembers (ChatId, UserId) SELECT $1, UNNEST($2::int[])",
+            "INSERT INTO ChatMembers (ChatId, UserId, Role)
+            SELECT $1, u, CASE WHEN u = $3 THEN 'owner' ELSE 'member' END FROM UNNEST($2::int[]) u",
             chat_id as _,
             users as _,
+            owner_id as _,
         )
         .execute(&mut *tn)
         .await?;
@@ -72,35 +193,190 @@ impl ChatsRepository for PgChatsRepository {
         Ok(())
     }
 
-    async fn get_user_chats_ids(
-        &self,
-        user_id: UserId,
-    ) -> Result<HashSet<ChatId>, RepositoryError> {
-        let chat_ids = query_scalar!(
-            "SELECT ChatId FROM ChatMembers WHERE UserId = $1",
-            user_id as _
-        )
-        .fetch(&self.0)
-        .filter_map(|id| async { id.map(ChatId::from).ok() })
-        .collect::<HashSet<ChatId>>()
-        .await;
-
-        Ok(chat_ids)
-    }
-
     async fn get_user_chats(&self, user_id: UserId) -> Result<Vec<Chat>, RepositoryError> {
         let chats = query_as!(
-            Chat,
-            "SELECT c.Id, c.Title as \"title: _\", array_agg(cm.UserId) AS \"users_ids!: _\"
+            ChatRow,
+            "SELECT c.Id, c.Kind, c.Visibility, COALESCE(peer.Title, c.Title) as \"title!: _\",
+                array_agg(cm.UserId) AS \"users_ids!: _\",
+                c.Description, c.ImageUpdatedAt as image_updated_at, c.Version
             FROM Chats c JOIN ChatMembers cm ON c.Id = cm.ChatId
+            LEFT JOIN LATERAL (
+                SELECT COALESCE(p.DisplayName, u.Name) AS Title FROM DirectChats d
+                JOIN Users u ON u.Id = CASE WHEN d.FirstUserId = $1 THEN d.SecondUserId ELSE d.FirstUserId END
+                LEFT JOIN Profiles p ON p.UserId = u.Id
+                WHERE d.ChatId = c.Id AND $1 IN (d.FirstUserId, d.SecondUserId)
+            ) peer ON TRUE
             WHERE c.Id IN (SELECT ChatId FROM ChatMembers WHERE UserId = $1)
-            GROUP BY c.Id",
+            GROUP BY c.Id, peer.Title",
             user_id as _
         )
         .fetch_all(&self.0)
         .await?;
 
-        Ok(chats)
+        Ok(chats.into_iter().map(Into::into).collect())
+    }
+
+    async fn get_chat_list(
+        &self,
+        user_id: UserId,
+        before: Option<ChatCursor>,
+        limit: i64,
+    ) -> Result<Vec<ChatSummary>, RepositoryError> {
+        // the latest message is denormalized on the chat and unread messages are counted
+        // on the (ChatId, Id) index up to the limit, so the cost does not grow with the history
+        let rows = query!(
+            "SELECT c.Id as \"id: ChatId\", c.Kind, c.Visibility, COALESCE(peer.Title, c.Title) as \"title!: ChatTitle\",
+                (SELECT array_agg(m.UserId) FROM ChatMembers m WHERE m.ChatId = c.Id) as \"users_ids!: Vec<UserId>\",
+                c.Description, c.ImageUpdatedAt as image_updated_at, c.Version, c.LastActivityAt as last_activity_at,
+                me.LastReadMessageId as \"last_read_message_id: MessageId\",
+                lm.Id as \"last_message_id?: MessageId\", lm.UserId as \"last_sender_id?: UserId\",
+                LEFT(lm.Content, $4) as \"last_content?\", char_length(lm.Content) > $4 as \"last_truncated?\",
+                lm.CreatedAt as \"last_created_at?\",
+                EXISTS (SELECT 1 FROM Blocks b WHERE b.BlockerId = $1 AND b.BlockedId = lm.UserId) as \"last_sender_blocked!\",
+                (SELECT COUNT(*) FROM (
+                    SELECT 1 FROM Messages u
+                    WHERE u.ChatId = c.Id AND u.Id > COALESCE(me.LastReadMessageId, 0)
+                        AND u.UserId IS DISTINCT FROM $1
+                        AND NOT EXISTS (SELECT 1 FROM Blocks b WHERE b.BlockerId = $1 AND b.BlockedId = u.UserId)
+                    LIMIT $5
+                ) unread) as \"unread_count!\"
+            FROM ChatMembers me
+            JOIN Chats c ON c.Id = me.ChatId
+            LEFT JOIN Messages lm ON lm.Id = c.LastMessageId
+            LEFT JOIN LATERAL (
+                SELECT COALESCE(p.DisplayName, u.Name) AS Title FROM DirectChats d
+                JOIN Users u ON u.Id = CASE WHEN d.FirstUserId = $1 THEN d.SecondUserId ELSE d.FirstUserId END
+                LEFT JOIN Profiles p ON p.UserId = u.Id
+                WHERE d.ChatId = c.Id AND $1 IN (d.FirstUserId, d.SecondUserId)
+            ) peer ON TRUE
+            WHERE me.UserId = $1
+                AND ($2::timestamptz IS NULL OR (c.LastActivityAt, c.Id) < ($2, $3::int))
+            ORDER BY c.LastActivityAt DESC, c.Id DESC
+            LIMIT $6",
+            user_id as _,
+            before.map(|cursor| cursor.last_activity_at),
+            before.map(|cursor| cursor.chat_id) as _,
+            MESSAGE_PREVIEW_LENGTH,
+            UNREAD_COUNT_LIMIT,
+            limit
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let last_message = match (row.last_message_id, row.last_content, row.last_created_at) {
+                    (Some(id), Some(content), Some(created_at)) => Some(MessagePreview {
+                        id,
+                        sender_id: row.last_sender_id,
+                        sender_blocked: row.last_sender_blocked,
+                        content,
+                        truncated: row.last_truncated.unwrap_or_default(),
+                        created_at,
+                    }),
+                    _ => None,
+                };
+
+                ChatSummary {
+                    chat: ChatRow {
+                        id: row.id,
+                        kind: row.kind,
+                        visibility: row.visibility,
+                        title: row.title,
+                        users_ids: row.users_ids,
+                        description: row.description,
+                        image_updated_at: row.image_updated_at,
+                        version: row.version,
+                    }
+                    .into(),
+                    last_message,
+                    last_read_message_id: row.last_read_message_id,
+                    unread_count: row.unread_count,
+                    last_activity_at: row.last_activity_at,
+                }
+            })
+            .collect())
+    }
+
+    async fn get_chat(&self, chat_id: ChatId, viewer_id: UserId) -> Result<Chat, RepositoryError> {
+        let mut conn = self.0.acquire().await?;
+        fetch_chat(&mut conn, chat_id, Some(viewer_id)).await
+    }
+
+    async fn get_or_create_direct_chat(
+        &self,
+        user_id: UserId,
+        peer_id: UserId,
+    ) -> Result<(ChatId, Vec<UserId>), RepositoryError> {
+        let (first, second) = if *user_id < *peer_id { (user_id, peer_id) } else { (peer_id, user_id) };
+        let mut tn = self.0.begin().await?;
+
+        query_scalar!("SELECT Id FROM Users WHERE Id = $1", peer_id as _)
+            .fetch_one(&mut *tn)
+            .await?;
+
+        let existing = query_scalar!(
+            "SELECT ChatId as \"chat_id: ChatId\" FROM DirectChats WHERE FirstUserId = $1 AND SecondUserId = $2",
+            first as _,
+            second as _
+        )
+        .fetch_optional(&mut *tn)
+        .await?;
+
+        let chat_id = match existing {
+            Some(chat_id) => chat_id,
+            None => {
+                let chat_id = query_scalar!(
+                    "INSERT INTO Chats (Title, Kind) VALUES ($1, 'direct') RETURNING Id as \"id: ChatId\"",
+                    DIRECT_CHAT_TITLE
+                )
+                .fetch_one(&mut *tn)
+                .await?;
+
+                // waits for a concurrent call creating the same chat, and then uses that chat
+                let inserted = query!(
+                    "INSERT INTO DirectChats (ChatId, FirstUserId, SecondUserId) VALUES ($1, $2, $3)
+                    ON CONFLICT (FirstUserId, SecondUserId) DO NOTHING",
+                    chat_id as _,
+                    first as _,
+                    second as _
+                )
+                .execute(&mut *tn)
+                .await?;
+
+                if inserted.rows_affected() == 0 {
+                    query!("DELETE FROM Chats WHERE Id = $1", chat_id as _)
+                        .execute(&mut *tn)
+                        .await?;
+
+                    query_scalar!(
+                        "SELECT ChatId as \"chat_id: ChatId\" FROM DirectChats WHERE FirstUserId = $1 AND SecondUserId = $2",
+                        first as _,
+                        second as _
+                    )
+                    .fetch_one(&mut *tn)
+                    .await?
+                } else {
+                    chat_id
+                }
+            }
+        };
+
+        let added = query_scalar!(
+            "INSERT INTO ChatMembers (ChatId, UserId, LastReadMessageId)
+            SELECT $1, UNNEST($2::int[]), (SELECT LastMessageId FROM Chats WHERE Id = $1)
+            ON CONFLICT DO NOTHING
+            RETURNING UserId as \"user_id!: _\"",
+            chat_id as _,
+            &[first, second] as _
+        )
+        .fetch_all(&mut *tn)
+        .await?;
+
+        tn.commit().await?;
+
+        Ok((chat_id, added))
     }
 
     async fn get_chat_members(&self, chat_id: ChatId) -> Result<Vec<UserId>, RepositoryError> {
@@ -113,4 +389,440 @@ impl ChatsRepository for PgChatsRepository {
 
         Ok(members)
     }
+
+    async fn get_members(&self, chat_id: ChatId) -> Result<Vec<ChatMember>, RepositoryError> {
+        let rows = query!(
+            "SELECT UserId as \"user_id!: UserId\", Role, JoinedAt FROM ChatMembers
+            WHERE ChatId = $1
+            ORDER BY JoinedAt, UserId",
+            chat_id as _
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| ChatMember {
+                user_id: row.user_id,
+                role: parse_role(&row.role),
+                joined_at: row.joinedat,
+            })
+            .collect())
+    }
+
+    async fn get_member_role(&self, chat_id: ChatId, user_id: UserId) -> Result<ChatRole, RepositoryError> {
+        let role = query_scalar!(
+            "SELECT Role FROM ChatMembers WHERE ChatId = $1 AND UserId = $2",
+            chat_id as _,
+            user_id as _
+        )
+        .fetch_one(&self.0)
+        .await?;
+
+        Ok(parse_role(&role))
+    }
+
+    async fn set_member_role(&self, chat_id: ChatId, user_id: UserId, role: ChatRole) -> Result<(), RepositoryError> {
+        let result = query!(
+            "UPDATE ChatMembers SET Role = $3 WHERE ChatId = $1 AND UserId = $2 AND Role <> 'owner'",
+            chat_id as _,
+            user_id as _,
+            role.as_ref()
+        )
+        .execute(&self.0)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn transfer_ownership(&self, chat_id: ChatId, owner_id: UserId, user_id: UserId) -> Result<(), RepositoryError> {
+        let mut tn = self.0.begin().await?;
+
+        let demoted = query!(
+            "UPDATE ChatMembers SET Role = 'admin' WHERE ChatId = $1 AND UserId = $2 AND Role = 'owner'",
+            chat_id as _,
+            owner_id as _
+        )
+        .execute(&mut *tn)
+        .await?;
+
+        let promoted = query!(
+            "UPDATE ChatMembers SET Role = 'owner' WHERE ChatId = $1 AND UserId = $2",
+            chat_id as _,
+            user_id as _
+        )
+        .execute(&mut *tn)
+        .await?;
+
+        if demoted.rows_affected() == 0 || promoted.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+
+        tn.commit().await?;
+
+        Ok(())
+    }
+
+    async fn add_chat_members(&self, chat_id: ChatId, users_ids: &[UserId]) -> Result<Vec<UserId>, RepositoryError> {
+        let mut tn = self.0.begin().await?;
+
+        let existing = query_scalar!(
+            "SELECT COUNT(DISTINCT Id) as \"count!\" FROM Users WHERE Id = ANY($1::int[])",
+            users_ids as _
+        )
+        .fetch_one(&mut *tn)
+        .await?;
+
+        let requested = users_ids.iter().collect::<HashSet<_>>().len();
+        if existing as usize != requested {
+            return Err(RepositoryError::NotFound);
+        }
+
+        let added = query_scalar!(
+            "INSERT INTO ChatMembers (ChatId, UserId, LastReadMessageId)
+            SELECT $1, UNNEST($2::int[]), (SELECT LastMessageId FROM Chats WHERE Id = $1)
+            ON CONFLICT DO NOTHING
+            RETURNING UserId as \"user_id!: _\"",
+            chat_id as _,
+            users_ids as _
+        )
+        .fetch_all(&mut *tn)
+        .await?;
+
+        tn.commit().await?;
+
+        Ok(added)
+    }
+
+    async fn remove_chat_member(&self, chat_id: ChatId, user_id: UserId) -> Result<Vec<UserId>, RepositoryError> {
+        let mut tn = self.0.begin().await?;
+
+        let removed = query!(
+            "DELETE FROM ChatMembers WHERE ChatId = $1 AND UserId = $2",
+            chat_id as _,
+            user_id as _
+        )
+        .execute(&mut *tn)
+        .await?;
+
+        if removed.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+
+        let remaining = query_scalar!(
+            "SELECT UserId as \"user_id!: _\" FROM ChatMembers WHERE ChatId = $1",
+            chat_id as _
+        )
+        .fetch_all(&mut *tn)
+        .await?;
+
+        if remaining.is_empty() {
+            query!("DELETE FROM Chats WHERE Id = $1", chat_id as _)
+                .execute(&mut *tn)
+                .await?;
+        } else {
+            query!(
+                "UPDATE ChatMembers cm SET Role = 'owner'
+                FROM (
+                    SELECT UserId FROM ChatMembers
+                    WHERE ChatId = $1
+                        AND EXISTS (SELECT 1 FROM Chats WHERE Id = $1 AND Kind = 'group')
+                        AND NOT EXISTS (SELECT 1 FROM ChatMembers WHERE ChatId = $1 AND Role = 'owner')
+                    ORDER BY Role = 'admin' DESC, JoinedAt, UserId
+                    LIMIT 1
+                ) successor
+                WHERE cm.ChatId = $1 AND cm.UserId = successor.UserId",
+                chat_id as _
+            )
+            .execute(&mut *tn)
+            .await?;
+        }
+
+        tn.commit().await?;
+
+        Ok(remaining)
+    }
+
+    async fn get_chat_peers(&self, user_id: UserId) -> Result<Vec<UserId>, RepositoryError> {
+        let peers = query_scalar!(
+            "SELECT DISTINCT peer.UserId as \"user_id!: _\"
+            FROM ChatMembers own JOIN ChatMembers peer ON own.ChatId = peer.ChatId
+            WHERE own.UserId = $1 AND peer.UserId <> $1",
+            user_id as _
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(peers)
+    }
+
+    async fn update_chat<'a>(
+        &self,
+        chat_id: ChatId,
+        title: Option<&'a ChatTitle>,
+        description: Option<Option<&'a str>>,
+        visibility: Option<ChatVisibility>,
+        expected_version: Option<i32>,
+    ) -> Result<Chat, RepositoryError> {
+        let mut tn = self.0.begin().await?;
+        lock_version(&mut tn, chat_id, expected_version).await?;
+
+        query!(
+            "UPDATE Chats SET
+                Title = COALESCE($2, Title),
+                Description = CASE WHEN $3 THEN $4 ELSE Description END,
+                Visibility = COALESCE($5, Visibility),
+                Version = Version + 1
+            WHERE Id = $1",
+            chat_id as _,
+            title as _,
+            description.is_some(),
+            description.flatten(),
+            visibility.as_ref().map(AsRef::<str>::as_ref)
+        )
+        .execute(&mut *tn)
+        .await?;
+
+        let chat = fetch_chat(&mut tn, chat_id, None).await?;
+        tn.commit().await?;
+
+        Ok(chat)
+    }
+
+    async fn set_chat_image(
+        &self,
+        chat_id: ChatId,
+        content_type: &str,
+        data: &[u8],
+        expected_version: Option<i32>,
+    ) -> Result<Chat, RepositoryError> {
+        let mut tn = self.0.begin().await?;
+        lock_version(&mut tn, chat_id, expected_version).await?;
+
+        query!(
+            "UPDATE Chats SET ImageType = $2, Image = $3, ImageUpdatedAt = NOW(), Version = Version + 1
+            WHERE Id = $1",
+            chat_id as _,
+            content_type,
+            data
+        )
+        .execute(&mut *tn)
+        .await?;
+
+        let chat = fetch_chat(&mut tn, chat_id, None).await?;
+        tn.commit().await?;
+
+        Ok(chat)
+    }
+
+    async fn remove_chat_image(&self, chat_id: ChatId, expected_version: Option<i32>) -> Result<Chat, RepositoryError> {
+        let mut tn = self.0.begin().await?;
+        lock_version(&mut tn, chat_id, expected_version).await?;
+
+        let result = query!(
+            "UPDATE Chats SET ImageType = NULL, Image = NULL, ImageUpdatedAt = NULL, Version = Version + 1
+            WHERE Id = $1 AND Image IS NOT NULL",
+            chat_id as _
+        )
+        .execute(&mut *tn)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+
+        let chat = fetch_chat(&mut tn, chat_id, None).await?;
+        tn.commit().await?;
+
+        Ok(chat)
+    }
+
+    async fn get_chat_image(&self, chat_id: ChatId) -> Result<Avatar, RepositoryError> {
+        let image = query_as!(
+            Avatar,
+            "SELECT ImageType as \"content_type!\", Image as \"data!\"
+            FROM Chats WHERE Id = $1 AND Image IS NOT NULL",
+            chat_id as _
+        )
+        .fetch_one(&self.0)
+        .await?;
+
+        Ok(image)
+    }
+
+    async fn get_public_chats(
+        &self,
+        user_id: UserId,
+        query: Option<String>,
+        before: Option<ChatId>,
+        limit: i64,
+    ) -> Result<Vec<Channel>, RepositoryError> {
+        let pattern = query.map(|query| format!("%{}%", escape_like(&query)));
+        let rows = query!(
+            "SELECT c.Id as \"id: ChatId\", c.Title as \"title: ChatTitle\", c.Description,
+                c.ImageUpdatedAt as image_updated_at,
+                (SELECT COUNT(*) FROM ChatMembers cm WHERE cm.ChatId = c.Id) as \"members_count!\",
+                EXISTS (SELECT 1 FROM ChatMembers cm WHERE cm.ChatId = c.Id AND cm.UserId = $1) as \"is_member!\"
+            FROM Chats c
+            WHERE c.Visibility = 'public'
+                AND ($2::text IS NULL OR c.Title ILIKE $2 OR c.Description ILIKE $2)
+                AND ($3::int IS NULL OR c.Id < $3)
+            ORDER BY c.Id DESC
+            LIMIT $4",
+            user_id as _,
+            pattern,
+            before as _,
+            limit
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Channel {
+                image_url: Chat::image_url(row.id, row.image_updated_at),
+                id: row.id,
+                title: row.title,
+                description: row.description,
+                members_count: row.members_count,
+                is_member: row.is_member,
+            })
+            .collect())
+    }
+
+    async fn join_public_chat(&self, chat_id: ChatId, user_id: UserId) -> Result<bool, RepositoryError> {
+        let mut tn = self.0.begin().await?;
+
+        // the lock keeps the chat from turning private while joining
+        query_scalar!(
+            "SELECT Id FROM Chats WHERE Id = $1 AND Visibility = 'public' FOR SHARE",
+            chat_id as _
+        )
+        .fetch_one(&mut *tn)
+        .await?;
+
+        let joined = query!(
+            "INSERT INTO ChatMembers (ChatId, UserId, LastReadMessageId)
+            VALUES ($1, $2, (SELECT LastMessageId FROM Chats WHERE Id = $1))
+            ON CONFLICT DO NOTHING",
+            chat_id as _,
+            user_id as _
+        )
+        .execute(&mut *tn)
+        .await?
+        .rows_affected()
+            > 0;
+
+        tn.commit().await?;
+
+        Ok(joined)
+    }
+}
+
+/// Without a viewer, direct chats keep their placeholder title.
+async fn fetch_chat(conn: &mut PgConnection, chat_id: ChatId, viewer_id: Option<UserId>) -> Result<Chat, RepositoryError> {
+    let chat = query_as!(
+        ChatRow,
+        "SELECT c.Id, c.Kind, c.Visibility, COALESCE(peer.Title, c.Title) as \"title!: _\",
+            array_agg(cm.UserId) AS \"users_ids!: _\",
+            c.Description, c.ImageUpdatedAt as image_updated_at, c.Version
+        FROM Chats c JOIN ChatMembers cm ON c.Id = cm.ChatId
+        LEFT JOIN LATERAL (
+            SELECT COALESCE(p.DisplayName, u.Name) AS Title FROM DirectChats d
+            JOIN Users u ON u.Id = CASE WHEN d.FirstUserId = $2 THEN d.SecondUserId ELSE d.FirstUserId END
+            LEFT JOIN Profiles p ON p.UserId = u.Id
+            WHERE d.ChatId = c.Id AND $2 IN (d.FirstUserId, d.SecondUserId)
+        ) peer ON TRUE
+        WHERE c.Id = $1
+        GROUP BY c.Id, peer.Title",
+        chat_id as _,
+        viewer_id as _
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(chat.into())
+}
+
+/// Locks the chat row until the transaction ends, fails with `Conflict` on a version mismatch.
+async fn lock_version(
+    conn: &mut PgConnection,
+    chat_id: ChatId,
+    expected_version: Option<i32>,
+) -> Result<(), RepositoryError> {
+    let version = query_scalar!("SELECT Version FROM Chats WHERE Id = $1 FOR UPDATE", chat_id as _)
+        .fetch_one(conn)
+        .await?;
+
+    match expected_version {
+        Some(expected) if expected != version => Err(RepositoryError::Conflict),
+        _ => Ok(()),
+    }
+}
+
+/// Roles are only written from `ChatRole`, anything else is treated as a plain member.
+fn parse_role(role: &str) -> ChatRole {
+    role.parse().unwrap_or(ChatRole::Member)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::users::PasswordHash,
+        repositories::{
+            invites::{InvitesRepository, PgInvitesRepository},
+            messages::{MessagesRepository, PgMessagesRepository},
+            users::{PgUsersRepository, UsersRepository},
+        },
+    };
+
+    async fn unread_count(chats: &PgChatsRepository, user_id: UserId, chat_id: ChatId) -> i64 {
+        let list = chats.get_chat_list(user_id, None, 10).await.unwrap();
+        list.into_iter().find(|summary| summary.chat.id == chat_id).unwrap().unread_count
+    }
+
+    #[ignore = "needs a database in DATABASE_URL"]
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_new_members_have_no_unread_history(pool: PgPool) {
+        let users = PgUsersRepository::new(pool.clone());
+        let chats = PgChatsRepository::new(pool.clone());
+        let invites = PgInvitesRepository::new(pool.clone());
+        let messages = PgMessagesRepository::new(pool);
+
+        let mut ids = Vec::new();
+        for name in ["alice", "bob", "carol", "dave"] {
+            ids.push(users.create_user(name, PasswordHash::new(String::new())).await.unwrap());
+        }
+        let (alice, bob, carol, dave) = (ids[0], ids[1], ids[2], ids[3]);
+
+        let title = ChatTitle::new("history".to_owned());
+        let chat_id = chats.create_chat(&title, &[alice], alice, ChatVisibility::Public).await.unwrap();
+        for content in ["one", "two", "three"] {
+            messages.create_message(chat_id, alice, content).await.unwrap();
+        }
+
+        chats.add_chat_members(chat_id, &[bob]).await.unwrap();
+        chats.join_public_chat(chat_id, carol).await.unwrap();
+        invites.create_invite("invite", chat_id, alice, None, None).await.unwrap();
+        invites.use_invite("invite", dave).await.unwrap();
+        for member in [bob, carol, dave] {
+            assert_eq!(unread_count(&chats, member, chat_id).await, 0);
+        }
+
+        messages.create_message(chat_id, alice, "four").await.unwrap();
+        for member in [bob, carol, dave] {
+            assert_eq!(unread_count(&chats, member, chat_id).await, 1);
+        }
+
+        let (direct_id, _) = chats.get_or_create_direct_chat(alice, bob).await.unwrap();
+        messages.create_message(direct_id, alice, "hi").await.unwrap();
+        chats.remove_chat_member(direct_id, bob).await.unwrap();
+        chats.get_or_create_direct_chat(alice, bob).await.unwrap();
+        assert_eq!(unread_count(&chats, bob, direct_id).await, 0);
+    }
 }
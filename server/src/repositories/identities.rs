@@ -0,0 +1,104 @@
+use sqlx::{PgPool, query, query_as, query_scalar};
+use crate::{
+    error::RepositoryError,
+    models::{identity::AuthRequest, users::UserId},
+};
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait IdentitiesRepository: Send + Sync {
+    async fn create_auth_request(&self, request: &AuthRequest) -> Result<(), RepositoryError>;
+    /// Removes and returns the request, so every state can be redeemed only once.
+    async fn take_auth_request(&self, state: &str) -> Result<AuthRequest, RepositoryError>;
+    /// Finds the user linked to the subject at the provider.
+    async fn get_linked_user(&self, provider: &str, subject: &str) -> Result<UserId, RepositoryError>;
+    /// Creates a user without a password linked to the subject at the provider,
+    /// fails with `Conflict` when the username is taken.
+    async fn create_linked_user(
+        &self,
+        provider: &str,
+        subject: &str,
+        username: &str,
+    ) -> Result<UserId, RepositoryError>;
+}
+
+pub struct PgIdentitiesRepository(PgPool);
+
+impl PgIdentitiesRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self(pool)
+    }
+}
+
+#[async_trait::async_trait]
+impl IdentitiesRepository for PgIdentitiesRepository {
+    async fn create_auth_request(&self, request: &AuthRequest) -> Result<(), RepositoryError> {
+        query!(
+            "INSERT INTO AuthRequests (State, Provider, Nonce, CodeVerifier, ExpiresAt)
+            VALUES ($1, $2, $3, $4, $5)",
+            request.state,
+            request.provider,
+            request.nonce,
+            request.code_verifier,
+            request.expires_at
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn take_auth_request(&self, state: &str) -> Result<AuthRequest, RepositoryError> {
+        let request = query_as!(
+            AuthRequest,
+            "DELETE FROM AuthRequests WHERE State = $1
+            RETURNING State, Provider, Nonce, CodeVerifier as code_verifier, ExpiresAt as expires_at",
+            state
+        )
+        .fetch_one(&self.0)
+        .await?;
+
+        Ok(request)
+    }
+
+    async fn get_linked_user(&self, provider: &str, subject: &str) -> Result<UserId, RepositoryError> {
+        let id = query_scalar!(
+            "SELECT UserId FROM ExternalIdentities WHERE Provider = $1 AND Subject = $2",
+            provider,
+            subject
+        )
+        .fetch_one(&self.0)
+        .await?;
+
+        Ok(UserId::new(id))
+    }
+
+    async fn create_linked_user(
+        &self,
+        provider: &str,
+        subject: &str,
+        username: &str,
+    ) -> Result<UserId, RepositoryError> {
+        let mut tn = self.0.begin().await?;
+
+        let id = query_scalar!(
+            "INSERT INTO Users (Name, Password) VALUES ($1, '') RETURNING Id",
+            username
+        )
+        .fetch_one(&mut *tn)
+        .await?;
+
+        query!(
+            "INSERT INTO ExternalIdentities (Provider, Subject, UserId) VALUES ($1, $2, $3)",
+            provider,
+            subject,
+            id
+        )
+        .execute(&mut *tn)
+        .await?;
+
+        tn.commit().await?;
+
+        Ok(UserId::new(id))
+    }
+}
@@ -0,0 +1,152 @@
+use sqlx::{PgPool, query, query_as, query_scalar};
+use time::OffsetDateTime;
+use crate::{
+    error::RepositoryError,
+    models::{
+        chats::ChatId,
+        invites::ChatInvite,
+        users::UserId,
+    },
+};
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait InvitesRepository: Send + Sync {
+    async fn create_invite(
+        &self,
+        code: &str,
+        chat_id: ChatId,
+        created_by: UserId,
+        max_uses: Option<i32>,
+        expires_at: Option<OffsetDateTime>,
+    ) -> Result<ChatInvite, RepositoryError>;
+    /// Invites of the chat, newest first, including expired and used up ones.
+    async fn get_chat_invites(&self, chat_id: ChatId) -> Result<Vec<ChatInvite>, RepositoryError>;
+    /// Fails with `NotFound` unless the invite exists and is still usable.
+    async fn get_invite(&self, code: &str) -> Result<ChatInvite, RepositoryError>;
+    async fn remove_invite(&self, chat_id: ChatId, code: &str) -> Result<(), RepositoryError>;
+    /// Adds the user to the chat of the invite, returns the chat and whether the user joined it.
+    ///
+    /// A use is only counted when the user was not a member yet. Fails with `NotFound`
+    /// unless the invite exists and is still usable.
+    async fn use_invite(&self, code: &str, user_id: UserId) -> Result<(ChatId, bool), RepositoryError>;
+}
+
+pub struct PgInvitesRepository(PgPool);
+
+impl PgInvitesRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self(pool)
+    }
+}
+
+#[async_trait::async_trait]
+impl InvitesRepository for PgInvitesRepository {
+    async fn create_invite(
+        &self,
+        code: &str,
+        chat_id: ChatId,
+        created_by: UserId,
+        max_uses: Option<i32>,
+        expires_at: Option<OffsetDateTime>,
+    ) -> Result<ChatInvite, RepositoryError> {
+        let invite = query_as!(
+            ChatInvite,
+            "INSERT INTO ChatInvites (Code, ChatId, CreatedBy, MaxUses, ExpiresAt)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING Code, ChatId as \"chat_id: _\", CreatedBy as \"created_by: _\",
+                MaxUses as max_uses, Uses, ExpiresAt as expires_at, CreatedAt as created_at",
+            code,
+            chat_id as _,
+            created_by as _,
+            max_uses,
+            expires_at
+        )
+        .fetch_one(&self.0)
+        .await?;
+
+        Ok(invite)
+    }
+
+    async fn get_chat_invites(&self, chat_id: ChatId) -> Result<Vec<ChatInvite>, RepositoryError> {
+        let invites = query_as!(
+            ChatInvite,
+            "SELECT Code, ChatId as \"chat_id: _\", CreatedBy as \"created_by: _\",
+                MaxUses as max_uses, Uses, ExpiresAt as expires_at, CreatedAt as created_at
+            FROM ChatInvites WHERE ChatId = $1
+            ORDER BY CreatedAt DESC",
+            chat_id as _
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(invites)
+    }
+
+    async fn get_invite(&self, code: &str) -> Result<ChatInvite, RepositoryError> {
+        let invite = query_as!(
+            ChatInvite,
+            "SELECT Code, ChatId as \"chat_id: _\", CreatedBy as \"created_by: _\",
+                MaxUses as max_uses, Uses, ExpiresAt as expires_at, CreatedAt as created_at
+            FROM ChatInvites
+            WHERE Code = $1 AND (ExpiresAt IS NULL OR ExpiresAt > NOW()) AND (MaxUses IS NULL OR Uses < MaxUses)",
+            code
+        )
+        .fetch_one(&self.0)
+        .await?;
+
+        Ok(invite)
+    }
+
+    async fn remove_invite(&self, chat_id: ChatId, code: &str) -> Result<(), RepositoryError> {
+        let result = query!(
+            "DELETE FROM ChatInvites WHERE ChatId = $1 AND Code = $2",
+            chat_id as _,
+            code
+        )
+        .execute(&self.0)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn use_invite(&self, code: &str, user_id: UserId) -> Result<(ChatId, bool), RepositoryError> {
+        let mut tn = self.0.begin().await?;
+
+        // the lock makes concurrent joins and revocations wait, the conditions are checked again after it
+        let chat_id = query_scalar!(
+            "SELECT ChatId as \"chat_id: ChatId\" FROM ChatInvites
+            WHERE Code = $1 AND (ExpiresAt IS NULL OR ExpiresAt > NOW()) AND (MaxUses IS NULL OR Uses < MaxUses)
+            FOR UPDATE",
+            code
+        )
+        .fetch_one(&mut *tn)
+        .await?;
+
+        let joined = query!(
+            "INSERT INTO ChatMembers (ChatId, UserId, LastReadMessageId)
+            VALUES ($1, $2, (SELECT LastMessageId FROM Chats WHERE Id = $1))
+            ON CONFLICT DO NOTHING",
+            chat_id as _,
+            user_id as _
+        )
+        .execute(&mut *tn)
+        .await?
+        .rows_affected()
+            > 0;
+
+        if joined {
+            query!("UPDATE ChatInvites SET Uses = Uses + 1 WHERE Code = $1", code)
+                .execute(&mut *tn)
+                .await?;
+        }
+
+        tn.commit().await?;
+
+        Ok((chat_id, joined))
+    }
+}
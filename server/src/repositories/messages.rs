@@ -1,4 +1,4 @@
-use sqlx::{PgPool, query_as};
+use sqlx::{PgPool, query, query_as, query_scalar};
 use crate::{
     error::RepositoryError,
     models::{
@@ -11,19 +11,31 @@ use crate::{
 #[cfg_attr(test, mockall::automock)]
 #[async_trait::async_trait]
 pub trait MessagesRepository: Send + Sync {
+    /// Messages flagged for `reader_id`, newest first.
     async fn get_messages(
         &self,
         chat_id: ChatId,
+        reader_id: UserId,
         limit: i64,
         last_message_id: Option<MessageId>,
     ) -> Result<Vec<Message>, RepositoryError>;
 
+    /// Also makes it the latest message of the chat and marks the chat read for the sender.
     async fn create_message(
         &self,
         chat_id: ChatId,
         user_id: UserId,
         content: &str,
     ) -> Result<Message, RepositoryError>;
+
+    async fn count_user_messages(&self, user_id: UserId) -> Result<i64, RepositoryError>;
+    /// Messages sent by the user across all chats, oldest first.
+    async fn get_user_messages(
+        &self,
+        user_id: UserId,
+        after: Option<MessageId>,
+        limit: i64,
+    ) -> Result<Vec<Message>, RepositoryError>;
 }
 
 pub struct PgMessagesRepository(PgPool);
@@ -39,19 +51,23 @@ impl MessagesRepository for PgMessagesRepository {
     async fn get_messages(
         &self,
         chat_id: ChatId,
+        reader_id: UserId,
         limit: i64,
         last_message_id: Option<MessageId>,
     ) -> Result<Vec<Message>, RepositoryError> {
         let result = query_as!(
                 Message,
-                "SELECT Id, UserId as \"sender_id: _\", ChatId as chat_id, Content, CreatedAt as created_at
-                FROM messages
-                WHERE chatid = $1 AND ($2::BIGINT IS NULL OR Id < $2)
-                ORDER BY CreatedAt DESC
+                "SELECT m.Id, m.UserId as \"sender_id: _\", m.ChatId as chat_id, m.Content, m.IsBot as is_bot,
+                    EXISTS (SELECT 1 FROM Blocks b WHERE b.BlockerId = $4 AND b.BlockedId = m.UserId) as \"sender_blocked!\",
+                    m.CreatedAt as created_at
+                FROM messages m
+                WHERE m.chatid = $1 AND ($2::BIGINT IS NULL OR m.Id < $2)
+                ORDER BY m.CreatedAt DESC
                 LIMIT $3",
                 chat_id as _,
                 last_message_id as _,
                 limit,
+                reader_id as _,
             ).fetch_all(&self.0)
             .await?;
 
@@ -64,17 +80,77 @@ impl MessagesRepository for PgMessagesRepository {
         user_id: UserId,
         content: &str,
     ) -> Result<Message, RepositoryError> {
+        let mut tn = self.0.begin().await?;
         let message = query_as!(Message,
-            "INSERT INTO Messages (ChatId, UserId, Content) 
-            VALUES ($1, $2, $3) 
-            RETURNING Id, UserId as \"sender_id: _\", ChatId as chat_id, Content, CreatedAt as created_at",
+            "INSERT INTO Messages (ChatId, UserId, Content, IsBot)
+            SELECT $1, Id, $3, IsBot FROM Users WHERE Id = $2
+            RETURNING Id, UserId as \"sender_id: _\", ChatId as chat_id, Content, IsBot as is_bot,
+                FALSE as \"sender_blocked!\", CreatedAt as created_at",
             chat_id as _,
             user_id as _,
             content
         )
-        .fetch_one(&self.0)
+        .fetch_one(&mut *tn)
         .await?;
 
+        // concurrent messages may commit out of order, the pointers only move forward
+        query!(
+            "UPDATE Chats SET LastMessageId = $2, LastActivityAt = $3
+            WHERE Id = $1 AND (LastMessageId IS NULL OR LastMessageId < $2)",
+            chat_id as _,
+            message.id as _,
+            message.created_at
+        )
+        .execute(&mut *tn)
+        .await?;
+
+        query!(
+            "UPDATE ChatMembers SET LastReadMessageId = GREATEST(LastReadMessageId, $3)
+            WHERE ChatId = $1 AND UserId = $2",
+            chat_id as _,
+            user_id as _,
+            message.id as _
+        )
+        .execute(&mut *tn)
+        .await?;
+
+        tn.commit().await?;
+
         Ok(message)
     }
-}
\ No newline at end of file
+
+    async fn count_user_messages(&self, user_id: UserId) -> Result<i64, RepositoryError> {
+        let count = query_scalar!(
+            "SELECT COUNT(*) as \"count!\" FROM Messages WHERE UserId = $1",
+            user_id as _
+        )
+        .fetch_one(&self.0)
+        .await?;
+
+        Ok(count)
+    }
+
+    async fn get_user_messages(
+        &self,
+        user_id: UserId,
+        after: Option<MessageId>,
+        limit: i64,
+    ) -> Result<Vec<Message>, RepositoryError> {
+        let messages = query_as!(
+            Message,
+            "SELECT Id, UserId as \"sender_id: _\", ChatId as chat_id, Content, IsBot as is_bot,
+                FALSE as \"sender_blocked!\", CreatedAt as created_at
+            FROM Messages
+            WHERE UserId = $1 AND ($2::BIGINT IS NULL OR Id > $2)
+            ORDER BY Id
+            LIMIT $3",
+            user_id as _,
+            after as _,
+            limit
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(messages)
+    }
+}
@@ -1,4 +1,29 @@
 pub mod chats;
 pub mod users;
 pub mod sessions;
-pub mod messages;
\ No newline at end of file
+pub mod tokens;
+pub mod bots;
+pub mod totp;
+pub mod profiles;
+pub mod presence;
+pub mod blocks;
+pub mod identities;
+pub mod messages;
+pub mod admin;
+pub mod invites;
+pub mod receipts;
+/// Escapes `LIKE` wildcards, so the pattern matches literally.
+pub(crate) fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like("50%_off\\"), "50\\%\\_off\\\\");
+        assert_eq!(escape_like("alice"), "alice");
+    }
+}
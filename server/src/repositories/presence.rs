@@ -0,0 +1,65 @@
+use sqlx::{PgPool, query, query_as};
+use time::OffsetDateTime;
+use crate::{
+    error::RepositoryError,
+    models::{presence::PresenceRecord, users::UserId},
+};
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait PresenceRepository: Send + Sync {
+    /// Records of the users that have one, in no particular order.
+    async fn get_presences(&self, users_ids: &[UserId]) -> Result<Vec<PresenceRecord>, RepositoryError>;
+    async fn set_last_seen(&self, user_id: UserId, last_seen_at: OffsetDateTime) -> Result<(), RepositoryError>;
+    async fn set_hidden(&self, user_id: UserId, hidden: bool) -> Result<(), RepositoryError>;
+}
+
+pub struct PgPresenceRepository(PgPool);
+
+impl PgPresenceRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self(pool)
+    }
+}
+
+#[async_trait::async_trait]
+impl PresenceRepository for PgPresenceRepository {
+    async fn get_presences(&self, users_ids: &[UserId]) -> Result<Vec<PresenceRecord>, RepositoryError> {
+        let records = query_as!(
+            PresenceRecord,
+            "SELECT UserId as \"user_id: _\", LastSeenAt as last_seen_at, Hidden
+            FROM Presence WHERE UserId = ANY($1::int[])",
+            users_ids as _
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(records)
+    }
+
+    async fn set_last_seen(&self, user_id: UserId, last_seen_at: OffsetDateTime) -> Result<(), RepositoryError> {
+        query!(
+            "INSERT INTO Presence (UserId, LastSeenAt) VALUES ($1, $2)
+            ON CONFLICT (UserId) DO UPDATE SET LastSeenAt = $2",
+            user_id as _,
+            last_seen_at
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn set_hidden(&self, user_id: UserId, hidden: bool) -> Result<(), RepositoryError> {
+        query!(
+            "INSERT INTO Presence (UserId, Hidden) VALUES ($1, $2)
+            ON CONFLICT (UserId) DO UPDATE SET Hidden = $2",
+            user_id as _,
+            hidden
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+}
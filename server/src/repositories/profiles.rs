@@ -0,0 +1,157 @@
+use sqlx::{PgPool, query, query_as};
+use time::OffsetDateTime;
+use crate::{
+    error::RepositoryError,
+    models::{
+        profiles::{Avatar, Profile},
+        users::UserId,
+    },
+};
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait ProfilesRepository: Send + Sync {
+    /// Fails with `NotFound` when the user never edited the profile.
+    async fn get_profile(&self, user_id: UserId) -> Result<Profile, RepositoryError>;
+    /// Profiles of the users that have one, in no particular order.
+    async fn get_profiles(&self, users_ids: &[UserId]) -> Result<Vec<Profile>, RepositoryError>;
+    async fn update_profile(
+        &self,
+        user_id: UserId,
+        display_name: Option<String>,
+        bio: Option<String>,
+    ) -> Result<(), RepositoryError>;
+    /// Sets the status, or clears it when `text` is `None`.
+    async fn set_status(
+        &self,
+        user_id: UserId,
+        text: Option<String>,
+        expires_at: Option<OffsetDateTime>,
+    ) -> Result<(), RepositoryError>;
+    async fn get_avatar(&self, user_id: UserId) -> Result<Avatar, RepositoryError>;
+    async fn set_avatar(&self, user_id: UserId, content_type: &str, data: &[u8]) -> Result<(), RepositoryError>;
+    async fn remove_avatar(&self, user_id: UserId) -> Result<(), RepositoryError>;
+}
+
+pub struct PgProfilesRepository(PgPool);
+
+impl PgProfilesRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self(pool)
+    }
+}
+
+#[async_trait::async_trait]
+impl ProfilesRepository for PgProfilesRepository {
+    async fn get_profile(&self, user_id: UserId) -> Result<Profile, RepositoryError> {
+        let profile = query_as!(
+            Profile,
+            "SELECT UserId as \"user_id: _\", DisplayName as display_name, Bio,
+                StatusText as status_text, StatusExpiresAt as status_expires_at,
+                AvatarUpdatedAt as avatar_updated_at
+            FROM Profiles WHERE UserId = $1",
+            user_id as _
+        )
+        .fetch_one(&self.0)
+        .await?;
+
+        Ok(profile)
+    }
+
+    async fn get_profiles(&self, users_ids: &[UserId]) -> Result<Vec<Profile>, RepositoryError> {
+        let profiles = query_as!(
+            Profile,
+            "SELECT UserId as \"user_id: _\", DisplayName as display_name, Bio,
+                StatusText as status_text, StatusExpiresAt as status_expires_at,
+                AvatarUpdatedAt as avatar_updated_at
+            FROM Profiles WHERE UserId = ANY($1::int[])",
+            users_ids as _
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(profiles)
+    }
+
+    async fn update_profile(
+        &self,
+        user_id: UserId,
+        display_name: Option<String>,
+        bio: Option<String>,
+    ) -> Result<(), RepositoryError> {
+        query!(
+            "INSERT INTO Profiles (UserId, DisplayName, Bio) VALUES ($1, $2, $3)
+            ON CONFLICT (UserId) DO UPDATE SET DisplayName = $2, Bio = $3, UpdatedAt = NOW()",
+            user_id as _,
+            display_name,
+            bio
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn set_status(
+        &self,
+        user_id: UserId,
+        text: Option<String>,
+        expires_at: Option<OffsetDateTime>,
+    ) -> Result<(), RepositoryError> {
+        query!(
+            "INSERT INTO Profiles (UserId, StatusText, StatusExpiresAt) VALUES ($1, $2, $3)
+            ON CONFLICT (UserId) DO UPDATE SET StatusText = $2, StatusExpiresAt = $3, UpdatedAt = NOW()",
+            user_id as _,
+            text,
+            expires_at
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_avatar(&self, user_id: UserId) -> Result<Avatar, RepositoryError> {
+        let avatar = query_as!(
+            Avatar,
+            "SELECT AvatarType as \"content_type!\", Avatar as \"data!\"
+            FROM Profiles WHERE UserId = $1 AND Avatar IS NOT NULL",
+            user_id as _
+        )
+        .fetch_one(&self.0)
+        .await?;
+
+        Ok(avatar)
+    }
+
+    async fn set_avatar(&self, user_id: UserId, content_type: &str, data: &[u8]) -> Result<(), RepositoryError> {
+        query!(
+            "INSERT INTO Profiles (UserId, AvatarType, Avatar, AvatarUpdatedAt) VALUES ($1, $2, $3, NOW())
+            ON CONFLICT (UserId) DO UPDATE
+            SET AvatarType = $2, Avatar = $3, AvatarUpdatedAt = NOW(), UpdatedAt = NOW()",
+            user_id as _,
+            content_type,
+            data
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn remove_avatar(&self, user_id: UserId) -> Result<(), RepositoryError> {
+        let result = query!(
+            "UPDATE Profiles SET AvatarType = NULL, Avatar = NULL, AvatarUpdatedAt = NULL, UpdatedAt = NOW()
+            WHERE UserId = $1 AND Avatar IS NOT NULL",
+            user_id as _
+        )
+        .execute(&self.0)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use sqlx::{PgPool, query, query_scalar};
+use crate::{
+    error::RepositoryError,
+    models::{
+        chats::ChatId,
+        messages::MessageId,
+        users::UserId,
+    },
+};
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait ReceiptsRepository: Send + Sync {
+    /// Moves the read marker of the member forward to the message, it never moves back.
+    ///
+    /// Returns the marker after the change and whether it moved. Fails with `NotFound` when
+    /// the message is not in the chat or the user is not a member.
+    async fn mark_read(
+        &self,
+        chat_id: ChatId,
+        user_id: UserId,
+        message_id: MessageId,
+    ) -> Result<(MessageId, bool), RepositoryError>;
+    /// Members other than the sender who read the message and send read receipts.
+    ///
+    /// Fails with `NotFound` when the message is not in the chat.
+    async fn get_readers(&self, chat_id: ChatId, message_id: MessageId) -> Result<Vec<UserId>, RepositoryError>;
+    async fn get_read_receipts(&self, user_id: UserId) -> Result<bool, RepositoryError>;
+    async fn set_read_receipts(&self, user_id: UserId, enabled: bool) -> Result<(), RepositoryError>;
+}
+
+pub struct PgReceiptsRepository(PgPool);
+
+impl PgReceiptsRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self(pool)
+    }
+}
+
+#[async_trait::async_trait]
+impl ReceiptsRepository for PgReceiptsRepository {
+    async fn mark_read(
+        &self,
+        chat_id: ChatId,
+        user_id: UserId,
+        message_id: MessageId,
+    ) -> Result<(MessageId, bool), RepositoryError> {
+        let mut tn = self.0.begin().await?;
+
+        query_scalar!(
+            "SELECT Id FROM Messages WHERE Id = $1 AND ChatId = $2",
+            message_id as _,
+            chat_id as _
+        )
+        .fetch_one(&mut *tn)
+        .await?;
+
+        // the lock orders concurrent marks from several devices, so only one of them reports the move
+        let current = query_scalar!(
+            "SELECT LastReadMessageId as \"last_read_message_id: MessageId\" FROM ChatMembers
+            WHERE ChatId = $1 AND UserId = $2
+            FOR UPDATE",
+            chat_id as _,
+            user_id as _
+        )
+        .fetch_one(&mut *tn)
+        .await?;
+
+        if let Some(current) = current
+            && *current >= *message_id
+        {
+            return Ok((current, false));
+        }
+
+        query!(
+            "UPDATE ChatMembers SET LastReadMessageId = $3 WHERE ChatId = $1 AND UserId = $2",
+            chat_id as _,
+            user_id as _,
+            message_id as _
+        )
+        .execute(&mut *tn)
+        .await?;
+
+        tn.commit().await?;
+
+        Ok((message_id, true))
+    }
+
+    async fn get_readers(&self, chat_id: ChatId, message_id: MessageId) -> Result<Vec<UserId>, RepositoryError> {
+        let sender_id = query_scalar!(
+            "SELECT UserId as \"user_id: UserId\" FROM Messages WHERE Id = $1 AND ChatId = $2",
+            message_id as _,
+            chat_id as _
+        )
+        .fetch_one(&self.0)
+        .await?;
+
+        let readers = query_scalar!(
+            "SELECT cm.UserId as \"user_id!: UserId\" FROM ChatMembers cm
+            JOIN Users u ON u.Id = cm.UserId
+            WHERE cm.ChatId = $1 AND cm.LastReadMessageId >= $2 AND u.ReadReceipts
+                AND cm.UserId IS DISTINCT FROM $3
+            ORDER BY cm.UserId",
+            chat_id as _,
+            message_id as _,
+            sender_id as _
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(readers)
+    }
+
+    async fn get_read_receipts(&self, user_id: UserId) -> Result<bool, RepositoryError> {
+        let enabled = query_scalar!("SELECT ReadReceipts FROM Users WHERE Id = $1", user_id as _)
+            .fetch_one(&self.0)
+            .await?;
+
+        Ok(enabled)
+    }
+
+    async fn set_read_receipts(&self, user_id: UserId, enabled: bool) -> Result<(), RepositoryError> {
+        query!(
+            "UPDATE Users SET ReadReceipts = $2 WHERE Id = $1",
+            user_id as _,
+            enabled
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -1,4 +1,10 @@
-use crate::{error::RepositoryError, models::users::UserId};
+use crate::{
+    error::RepositoryError,
+    models::{
+        users::UserId,
+        sessions::{ClientInfo, Session, SessionId},
+    },
+};
 use time::OffsetDateTime;
 
 #[cfg_attr(test, mockall::automock)]
@@ -9,10 +15,25 @@ pub trait SessionsRepository: Send + Sync {
         uid: &str,
         user_id: UserId,
         expires_at: OffsetDateTime,
-    ) -> Result<(), RepositoryError>;
-    async fn get_session(&self, uid: &str) -> Result<i32, RepositoryError>;
-    async fn get_session_by_user_id(&self, user_id: UserId) -> Result<String, RepositoryError>;
+        client: &ClientInfo,
+        external_auth_at: Option<OffsetDateTime>,
+    ) -> Result<SessionId, RepositoryError>;
+    async fn get_session(&self, uid: &str) -> Result<Session, RepositoryError>;
+    async fn get_user_sessions(&self, user_id: UserId) -> Result<Vec<Session>, RepositoryError>;
+    async fn touch_session(&self, uid: &str) -> Result<(), RepositoryError>;
+    async fn renew_session(&self, uid: &str, expires_at: OffsetDateTime) -> Result<(), RepositoryError>;
     async fn remove_session(&self, uid: &str) -> Result<(), RepositoryError>;
+    async fn remove_user_session(
+        &self,
+        user_id: UserId,
+        session_id: SessionId,
+    ) -> Result<(), RepositoryError>;
+    async fn remove_other_sessions(
+        &self,
+        user_id: UserId,
+        keep: SessionId,
+    ) -> Result<Vec<SessionId>, RepositoryError>;
+    async fn remove_user_sessions(&self, user_id: UserId) -> Result<Vec<SessionId>, RepositoryError>;
 }
 
 pub struct PgSessionsRepository(sqlx::PgPool);
@@ -30,33 +51,75 @@ impl SessionsRepository for PgSessionsRepository {
         uid: &str,
         user_id: UserId,
         expires_at: OffsetDateTime,
-    ) -> Result<(), RepositoryError> {
-        sqlx::query!(
-            "INSERT INTO Sessions (Uid, UserId, ExpiresAt) VALUES ($1, $2, $3)",
+        client: &ClientInfo,
+        external_auth_at: Option<OffsetDateTime>,
+    ) -> Result<SessionId, RepositoryError> {
+        let id = sqlx::query_scalar!(
+            "INSERT INTO Sessions (Uid, UserId, ExpiresAt, UserAgent, Ip, ExternalAuthAt)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING Id",
             uid,
             user_id as _,
-            expires_at
+            expires_at,
+            client.user_agent,
+            client.ip,
+            external_auth_at,
         )
-        .execute(&self.0)
+        .fetch_one(&self.0)
         .await?;
 
-        Ok(())
+        Ok(SessionId::new(id))
     }
 
-    async fn get_session(&self, uid: &str) -> Result<i32, RepositoryError> {
-        let row = sqlx::query!("SELECT UserId FROM Sessions WHERE Uid = $1", uid)
-            .fetch_one(&self.0)
-            .await?;
+    async fn get_session(&self, uid: &str) -> Result<Session, RepositoryError> {
+        let session = sqlx::query_as!(
+            Session,
+            "SELECT Id, UserId as \"user_id: _\", UserAgent as user_agent, Ip,
+                CreatedAt as created_at, LastSeenAt as last_seen_at, ExpiresAt as expires_at,
+                ExternalAuthAt as external_auth_at
+            FROM Sessions WHERE Uid = $1",
+            uid
+        )
+        .fetch_one(&self.0)
+        .await?;
 
-        Ok(row.userid)
+        Ok(session)
     }
 
-        async fn get_session_by_user_id(&self, user_id: UserId) -> Result<String, RepositoryError> {
-        let uid = sqlx::query_scalar!("SELECT Uid FROM Sessions WHERE UserId = $1", user_id as _)
-            .fetch_one(&self.0)
+    async fn get_user_sessions(&self, user_id: UserId) -> Result<Vec<Session>, RepositoryError> {
+        let sessions = sqlx::query_as!(
+            Session,
+            "SELECT Id, UserId as \"user_id: _\", UserAgent as user_agent, Ip,
+                CreatedAt as created_at, LastSeenAt as last_seen_at, ExpiresAt as expires_at,
+                ExternalAuthAt as external_auth_at
+            FROM Sessions WHERE UserId = $1
+            ORDER BY LastSeenAt DESC",
+            user_id as _
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    async fn touch_session(&self, uid: &str) -> Result<(), RepositoryError> {
+        sqlx::query!("UPDATE Sessions SET LastSeenAt = NOW() WHERE Uid = $1", uid)
+            .execute(&self.0)
             .await?;
 
-        Ok(uid)
+        Ok(())
+    }
+
+    async fn renew_session(&self, uid: &str, expires_at: OffsetDateTime) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            "UPDATE Sessions SET ExpiresAt = $2, LastSeenAt = NOW() WHERE Uid = $1",
+            uid,
+            expires_at
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
     }
 
     async fn remove_session(&self, uid: &str) -> Result<(), RepositoryError> {
@@ -66,4 +129,51 @@ impl SessionsRepository for PgSessionsRepository {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    async fn remove_user_session(
+        &self,
+        user_id: UserId,
+        session_id: SessionId,
+    ) -> Result<(), RepositoryError> {
+        let result = sqlx::query!(
+            "DELETE FROM Sessions WHERE UserId = $1 AND Id = $2",
+            user_id as _,
+            session_id as _
+        )
+        .execute(&self.0)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn remove_other_sessions(
+        &self,
+        user_id: UserId,
+        keep: SessionId,
+    ) -> Result<Vec<SessionId>, RepositoryError> {
+        let removed = sqlx::query_scalar!(
+            "DELETE FROM Sessions WHERE UserId = $1 AND Id <> $2 RETURNING Id as \"id: _\"",
+            user_id as _,
+            keep as _
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(removed)
+    }
+
+    async fn remove_user_sessions(&self, user_id: UserId) -> Result<Vec<SessionId>, RepositoryError> {
+        let removed = sqlx::query_scalar!(
+            "DELETE FROM Sessions WHERE UserId = $1 RETURNING Id as \"id: _\"",
+            user_id as _
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(removed)
+    }
+}
@@ -0,0 +1,148 @@
+use time::OffsetDateTime;
+use crate::{
+    error::RepositoryError,
+    models::{
+        users::UserId,
+        tokens::{ApiToken, ApiTokenId, Scope},
+    },
+};
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait TokensRepository: Send + Sync {
+    async fn create_token(
+        &self,
+        user_id: UserId,
+        name: &str,
+        token_hash: &str,
+        scopes: &[Scope],
+        expires_at: Option<OffsetDateTime>,
+    ) -> Result<ApiToken, RepositoryError>;
+    async fn get_token_by_hash(&self, token_hash: &str) -> Result<ApiToken, RepositoryError>;
+    async fn get_user_tokens(&self, user_id: UserId) -> Result<Vec<ApiToken>, RepositoryError>;
+    async fn touch_token(&self, id: ApiTokenId) -> Result<(), RepositoryError>;
+    async fn remove_token(&self, user_id: UserId, id: ApiTokenId) -> Result<(), RepositoryError>;
+}
+
+pub struct PgTokensRepository(sqlx::PgPool);
+
+impl PgTokensRepository {
+    pub fn new(db: sqlx::PgPool) -> Self {
+        Self(db)
+    }
+}
+
+struct ApiTokenRow {
+    id: ApiTokenId,
+    user_id: UserId,
+    name: String,
+    scopes: Vec<String>,
+    created_at: OffsetDateTime,
+    last_used_at: Option<OffsetDateTime>,
+    expires_at: Option<OffsetDateTime>,
+}
+
+impl From<ApiTokenRow> for ApiToken {
+    fn from(row: ApiTokenRow) -> Self {
+        Self {
+            id: row.id,
+            user_id: row.user_id,
+            name: row.name,
+            scopes: row
+                .scopes
+                .iter()
+                .filter_map(|scope| scope.parse().ok())
+                .collect(),
+            created_at: row.created_at,
+            last_used_at: row.last_used_at,
+            expires_at: row.expires_at,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl TokensRepository for PgTokensRepository {
+    async fn create_token(
+        &self,
+        user_id: UserId,
+        name: &str,
+        token_hash: &str,
+        scopes: &[Scope],
+        expires_at: Option<OffsetDateTime>,
+    ) -> Result<ApiToken, RepositoryError> {
+        let scopes = scopes
+            .iter()
+            .map(|scope| scope.as_ref().to_owned())
+            .collect::<Vec<_>>();
+
+        let row = sqlx::query_as!(
+            ApiTokenRow,
+            "INSERT INTO ApiTokens (UserId, Name, TokenHash, Scopes, ExpiresAt)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING Id as \"id: _\", UserId as \"user_id: _\", Name, Scopes, CreatedAt as created_at,
+                LastUsedAt as last_used_at, ExpiresAt as expires_at",
+            user_id as _,
+            name,
+            token_hash,
+            &scopes,
+            expires_at,
+        )
+        .fetch_one(&self.0)
+        .await?;
+
+        Ok(row.into())
+    }
+
+    async fn get_token_by_hash(&self, token_hash: &str) -> Result<ApiToken, RepositoryError> {
+        let row = sqlx::query_as!(
+            ApiTokenRow,
+            "SELECT Id as \"id: _\", UserId as \"user_id: _\", Name, Scopes, CreatedAt as created_at,
+                LastUsedAt as last_used_at, ExpiresAt as expires_at
+            FROM ApiTokens WHERE TokenHash = $1",
+            token_hash
+        )
+        .fetch_one(&self.0)
+        .await?;
+
+        Ok(row.into())
+    }
+
+    async fn get_user_tokens(&self, user_id: UserId) -> Result<Vec<ApiToken>, RepositoryError> {
+        let rows = sqlx::query_as!(
+            ApiTokenRow,
+            "SELECT Id as \"id: _\", UserId as \"user_id: _\", Name, Scopes, CreatedAt as created_at,
+                LastUsedAt as last_used_at, ExpiresAt as expires_at
+            FROM ApiTokens WHERE UserId = $1
+            ORDER BY CreatedAt DESC",
+            user_id as _
+        )
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(rows.into_iter().map(ApiToken::from).collect())
+    }
+
+    async fn touch_token(&self, id: ApiTokenId) -> Result<(), RepositoryError> {
+        sqlx::query!("UPDATE ApiTokens SET LastUsedAt = NOW() WHERE Id = $1", id as _)
+            .execute(&self.0)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn remove_token(&self, user_id: UserId, id: ApiTokenId) -> Result<(), RepositoryError> {
+        let result = sqlx::query!(
+            "DELETE FROM ApiTokens WHERE UserId = $1 AND Id = $2",
+            user_id as _,
+            id as _
+        )
+        .execute(&self.0)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+use sqlx::{PgPool, query, query_as};
+use time::OffsetDateTime;
+use crate::{
+    error::RepositoryError,
+    models::{
+        totp::{PendingLogin, Totp, MAX_PENDING_ATTEMPTS},
+        users::UserId,
+    },
+};
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait TotpRepository: Send + Sync {
+    /// Stores a new unconfirmed secret, fails with `Conflict` when TOTP is already enabled.
+    async fn set_secret(&self, user_id: UserId, secret: &str) -> Result<(), RepositoryError>;
+    async fn get_totp(&self, user_id: UserId) -> Result<Totp, RepositoryError>;
+    /// Enables TOTP and replaces the recovery codes of the user.
+    async fn enable_totp(
+        &self,
+        user_id: UserId,
+        step: i64,
+        recovery_codes_hashes: &[String],
+    ) -> Result<(), RepositoryError>;
+    async fn disable_totp(&self, user_id: UserId) -> Result<(), RepositoryError>;
+    /// Records `step` as used, returns false if it or a later step was already used.
+    async fn use_step(&self, user_id: UserId, step: i64) -> Result<bool, RepositoryError>;
+    /// Marks the recovery code as used, returns false if there is no such unused code.
+    async fn use_recovery_code(&self, user_id: UserId, code_hash: &str) -> Result<bool, RepositoryError>;
+
+    async fn create_pending_login(
+        &self,
+        uid: &str,
+        user_id: UserId,
+        expires_at: OffsetDateTime,
+    ) -> Result<(), RepositoryError>;
+    async fn get_pending_login(&self, uid: &str) -> Result<PendingLogin, RepositoryError>;
+    /// Counts a wrong code, the pending login is dropped once it runs out of attempts.
+    async fn fail_pending_login(&self, uid: &str) -> Result<(), RepositoryError>;
+    async fn remove_pending_login(&self, uid: &str) -> Result<(), RepositoryError>;
+}
+
+pub struct PgTotpRepository(PgPool);
+
+impl PgTotpRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self(pool)
+    }
+}
+
+#[async_trait::async_trait]
+impl TotpRepository for PgTotpRepository {
+    async fn set_secret(&self, user_id: UserId, secret: &str) -> Result<(), RepositoryError> {
+        let result = query!(
+            "INSERT INTO UserTotp (UserId, Secret) VALUES ($1, $2)
+            ON CONFLICT (UserId) DO UPDATE SET Secret = $2, LastStep = 0, CreatedAt = NOW()
+            WHERE NOT UserTotp.Enabled",
+            user_id as _,
+            secret
+        )
+        .execute(&self.0)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::Conflict);
+        }
+
+        Ok(())
+    }
+
+    async fn get_totp(&self, user_id: UserId) -> Result<Totp, RepositoryError> {
+        let totp = query_as!(
+            Totp,
+            "SELECT UserId as \"user_id: _\", Secret, Enabled, LastStep as last_step
+            FROM UserTotp WHERE UserId = $1",
+            user_id as _
+        )
+        .fetch_one(&self.0)
+        .await?;
+
+        Ok(totp)
+    }
+
+    async fn enable_totp(
+        &self,
+        user_id: UserId,
+        step: i64,
+        recovery_codes_hashes: &[String],
+    ) -> Result<(), RepositoryError> {
+        let mut tn = self.0.begin().await?;
+
+        let result = query!(
+            "UPDATE UserTotp SET Enabled = TRUE, LastStep = $2 WHERE UserId = $1 AND NOT Enabled",
+            user_id as _,
+            step
+        )
+        .execute(&mut *tn)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::Conflict);
+        }
+
+        query!("DELETE FROM RecoveryCodes WHERE UserId = $1", user_id as _)
+            .execute(&mut *tn)
+            .await?;
+
+        query!(
+            "INSERT INTO RecoveryCodes (UserId, CodeHash) SELECT $1, UNNEST($2::text[])",
+            user_id as _,
+            recovery_codes_hashes
+        )
+        .execute(&mut *tn)
+        .await?;
+
+        tn.commit().await?;
+
+        Ok(())
+    }
+
+    async fn disable_totp(&self, user_id: UserId) -> Result<(), RepositoryError> {
+        let mut tn = self.0.begin().await?;
+
+        query!("DELETE FROM UserTotp WHERE UserId = $1", user_id as _)
+            .execute(&mut *tn)
+            .await?;
+
+        query!("DELETE FROM RecoveryCodes WHERE UserId = $1", user_id as _)
+            .execute(&mut *tn)
+            .await?;
+
+        query!("DELETE FROM PendingLogins WHERE UserId = $1", user_id as _)
+            .execute(&mut *tn)
+            .await?;
+
+        tn.commit().await?;
+
+        Ok(())
+    }
+
+    async fn use_step(&self, user_id: UserId, step: i64) -> Result<bool, RepositoryError> {
+        let result = query!(
+            "UPDATE UserTotp SET LastStep = $2 WHERE UserId = $1 AND LastStep < $2",
+            user_id as _,
+            step
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn use_recovery_code(&self, user_id: UserId, code_hash: &str) -> Result<bool, RepositoryError> {
+        let result = query!(
+            "UPDATE RecoveryCodes SET UsedAt = NOW()
+            WHERE Id = (
+                SELECT Id FROM RecoveryCodes
+                WHERE UserId = $1 AND CodeHash = $2 AND UsedAt IS NULL
+                LIMIT 1
+            ) AND UsedAt IS NULL",
+            user_id as _,
+            code_hash
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn create_pending_login(
+        &self,
+        uid: &str,
+        user_id: UserId,
+        expires_at: OffsetDateTime,
+    ) -> Result<(), RepositoryError> {
+        query!(
+            "INSERT INTO PendingLogins (Uid, UserId, ExpiresAt) VALUES ($1, $2, $3)",
+            uid,
+            user_id as _,
+            expires_at
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_pending_login(&self, uid: &str) -> Result<PendingLogin, RepositoryError> {
+        let pending = query_as!(
+            PendingLogin,
+            "SELECT UserId as \"user_id: _\", Attempts, ExpiresAt as expires_at
+            FROM PendingLogins WHERE Uid = $1",
+            uid
+        )
+        .fetch_one(&self.0)
+        .await?;
+
+        Ok(pending)
+    }
+
+    async fn fail_pending_login(&self, uid: &str) -> Result<(), RepositoryError> {
+        let mut tn = self.0.begin().await?;
+
+        query!(
+            "UPDATE PendingLogins SET Attempts = Attempts + 1 WHERE Uid = $1",
+            uid
+        )
+        .execute(&mut *tn)
+        .await?;
+
+        query!(
+            "DELETE FROM PendingLogins WHERE Uid = $1 AND Attempts >= $2",
+            uid,
+            MAX_PENDING_ATTEMPTS
+        )
+        .execute(&mut *tn)
+        .await?;
+
+        tn.commit().await?;
+
+        Ok(())
+    }
+
+    async fn remove_pending_login(&self, uid: &str) -> Result<(), RepositoryError> {
+        let result = query!("DELETE FROM PendingLogins WHERE Uid = $1", uid)
+            .execute(&self.0)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+
+        Ok(())
+    }
+}
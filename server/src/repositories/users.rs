@@ -5,11 +5,20 @@ use crate::{error::RepositoryError, models::users::{PasswordHash, User, UserId}}
 #[async_trait::async_trait]
 pub trait UsersRepository: Send + Sync {
     async fn create_user(&self, username: &str, password: PasswordHash) -> Result<UserId, RepositoryError>;
-    async fn get_user(&self, username: &str, password: PasswordHash) -> Result<User, RepositoryError>;
+    async fn get_user_by_name(&self, username: &str) -> Result<User, RepositoryError>;
     async fn get_user_by_id(&self, id: &UserId) -> Result<User, RepositoryError>;
-    async fn get_user_by_session(&self, session_id: &str) -> Result<User, RepositoryError>;
     async fn get_user_salt(&self, username: &str) -> Result<String, RepositoryError>;
-    async fn search_users_by_username(&self, username: &str) -> Result<Vec<User>, RepositoryError>;
+    async fn update_password(&self, id: UserId, password: PasswordHash) -> Result<(), RepositoryError>;
+    async fn update_username(&self, id: UserId, username: &str) -> Result<(), RepositoryError>;
+    /// Removes the user and the bots it owns, authored messages are kept without a sender
+    /// and chats left without members are removed together with their messages.
+    async fn delete_user(&self, id: UserId) -> Result<(), RepositoryError>;
+    /// Best matches first, leaving out users blocked by `searcher_id` before the limit applies.
+    async fn search_users_by_username(
+        &self,
+        username: &str,
+        searcher_id: Option<UserId>,
+    ) -> Result<Vec<User>, RepositoryError>;
 }
 
 pub struct PgUsersRepository(sqlx::PgPool);
@@ -24,10 +33,9 @@ impl PgUsersRepository {
 impl UsersRepository for PgUsersRepository {
     async fn create_user(&self, username: &str, password: PasswordHash) -> Result<UserId, RepositoryError> {
         let result = sqlx::query_scalar!(
-            "INSERT INTO Users (Name, Password, Salt) VALUES ($1, $2, $3) RETURNING Id",
+            "INSERT INTO Users (Name, Password) VALUES ($1, $2) RETURNING Id",
             username,
             *password,
-            password.get_salt(),
         )
         .fetch_one(&self.0)
         .await?;
@@ -35,23 +43,12 @@ impl UsersRepository for PgUsersRepository {
         Ok(UserId::new(result))
     }
 
-    async fn get_user(&self, username: &str, password: PasswordHash) -> Result<User, RepositoryError> {
+    async fn get_user_by_name(&self, username: &str) -> Result<User, RepositoryError> {
         let result = sqlx::query_as!(
             User,
-            "SELECT Id, Name as username, Password, CreatedAt as created_at FROM Users WHERE Name = $1 AND Password = $2",
-            username,
-            *password
-        )
-        .fetch_one(&self.0)
-        .await?;
-
-        Ok(result)
-    }
-
-    async fn get_user_by_session(&self, session_id: &str) -> Result<User, RepositoryError> {
-        let result = sqlx::query_as!(User, 
-            "SELECT u.Id, u.Name as username, u.Password, u.CreatedAt as created_at FROM Users u LEFT JOIN Sessions s ON u.Id = s.UserId WHERE s.Uid = $1", 
-            session_id
+            "SELECT Id, Name as username, Password, IsBot as is_bot, IsAdmin as is_admin, SuspendedAt as suspended_at, CreatedAt as created_at
+            FROM Users WHERE Name = $1",
+            username
         )
         .fetch_one(&self.0)
         .await?;
@@ -68,21 +65,96 @@ impl UsersRepository for PgUsersRepository {
         Ok(salt)
     }
 
+    async fn update_password(&self, id: UserId, password: PasswordHash) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            "UPDATE Users SET Password = $1, Salt = NULL WHERE Id = $2",
+            *password,
+            id as _
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn update_username(&self, id: UserId, username: &str) -> Result<(), RepositoryError> {
+        sqlx::query!("UPDATE Users SET Name = $1 WHERE Id = $2", username, id as _)
+            .execute(&self.0)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete_user(&self, id: UserId) -> Result<(), RepositoryError> {
+        let mut tn = self.0.begin().await?;
+
+        let users = sqlx::query_scalar!(
+            "SELECT $1::int as \"id!\" UNION SELECT UserId FROM Bots WHERE OwnerId = $1",
+            id as _
+        )
+        .fetch_all(&mut *tn)
+        .await?;
+
+        let chats = sqlx::query_scalar!(
+            "SELECT DISTINCT ChatId FROM ChatMembers WHERE UserId = ANY($1)",
+            &users
+        )
+        .fetch_all(&mut *tn)
+        .await?;
+
+        sqlx::query!("DELETE FROM Users WHERE Id = ANY($1)", &users)
+            .execute(&mut *tn)
+            .await?;
+
+        sqlx::query!(
+            "DELETE FROM Chats c
+            WHERE c.Id = ANY($1) AND NOT EXISTS (SELECT 1 FROM ChatMembers cm WHERE cm.ChatId = c.Id)",
+            &chats
+        )
+        .execute(&mut *tn)
+        .await?;
+
+        // groups the user owned go to their earliest admin, or else their earliest member
+        sqlx::query!(
+            "UPDATE ChatMembers cm SET Role = 'owner'
+            FROM (
+                SELECT DISTINCT ON (ChatId) ChatId, UserId FROM ChatMembers m
+                WHERE ChatId = ANY($1)
+                    AND EXISTS (SELECT 1 FROM Chats c WHERE c.Id = m.ChatId AND c.Kind = 'group')
+                    AND NOT EXISTS (SELECT 1 FROM ChatMembers o WHERE o.ChatId = m.ChatId AND o.Role = 'owner')
+                ORDER BY ChatId, Role = 'admin' DESC, JoinedAt, UserId
+            ) successor
+            WHERE cm.ChatId = successor.ChatId AND cm.UserId = successor.UserId",
+            &chats
+        )
+        .execute(&mut *tn)
+        .await?;
+
+        tn.commit().await?;
+
+        Ok(())
+    }
+
     async fn get_user_by_id(&self, id: &UserId) -> Result<User, RepositoryError> {
-        let result = sqlx::query_as!(User, "SELECT Id, Name as username, Password, CreatedAt as created_at FROM Users WHERE Id = $1", **id)
+        let result = sqlx::query_as!(User, "SELECT Id, Name as username, Password, IsBot as is_bot, IsAdmin as is_admin, SuspendedAt as suspended_at, CreatedAt as created_at FROM Users WHERE Id = $1", **id)
             .fetch_one(&self.0)
             .await?;
 
         Ok(result)
     }
 
-    async fn search_users_by_username(&self, username: &str) -> Result<Vec<User>, RepositoryError> {
+    async fn search_users_by_username(
+        &self,
+        username: &str,
+        searcher_id: Option<UserId>,
+    ) -> Result<Vec<User>, RepositoryError> {
         let result = sqlx::query!( 
                 "SELECT *, similarity(name, $1) AS sim 
                 FROM Users 
                 WHERE Name % $1
+                    AND NOT EXISTS (SELECT 1 FROM Blocks b WHERE b.BlockerId = $2 AND b.BlockedId = Users.Id)
                 ORDER BY sim DESC
-                LIMIT 5", username)
+                LIMIT 5", username, searcher_id as _)
             .fetch(&self.0)
             .filter_map(|row| {
                 match row {
@@ -90,6 +162,9 @@ impl UsersRepository for PgUsersRepository {
                         id: UserId::new(row.id),
                         username: row.name,
                         password: row.password,
+                        is_bot: row.isbot,
+                        is_admin: row.isadmin,
+                        suspended_at: row.suspendedat,
                         created_at: row.createdat,
                     }),
                     Err(err) => {
@@ -1,43 +1,250 @@
 use std::sync::Arc;
+use sha2::Digest;
 use axum::{
     body::Body,
-    http::Request,
+    http::{HeaderMap, HeaderValue, Request, header},
     extract::State,
     middleware::Next,
     response::{Response, IntoResponse},
 };
+use time::{Duration, OffsetDateTime};
 use crate::{
     AppState,
     error::ApiError,
-    models::users::User,
-    services::trace::TraceId,
+    models::{
+        users::User,
+        sessions::SessionId,
+        tokens::{API_TOKEN_PREFIX, ApiTokenId, Scope},
+    },
+    services::{
+        trace::TraceId,
+        cookies::session_cookie,
+        session::{self, SessionState},
+    },
 };
 
 pub const SESSION_COOKIE_NAME: &str = "session";
 
+/// How often the session `LastSeenAt` timestamp is refreshed.
+const LAST_SEEN_INTERVAL: Duration = Duration::minutes(1);
+
+/// How the request was authenticated.
+pub enum Credentials {
+    Session {
+        uid: String,
+        id: SessionId,
+        external_auth_at: Option<OffsetDateTime>,
+    },
+    Token { id: ApiTokenId, scopes: Vec<Scope> },
+}
+
 pub struct Auth {
-    pub session: String,
     pub user: User,
+    pub credentials: Credentials,
+}
+
+impl Auth {
+    /// Ensures the request may act within `scope`.
+    ///
+    /// Browser sessions carry every scope, API tokens only the ones they were minted with.
+    pub fn require_scope(&self, scope: Scope, trace_id: &TraceId) -> Result<(), ApiError> {
+        match &self.credentials {
+            Credentials::Session { .. } => Ok(()),
+            Credentials::Token { scopes, .. } if scopes.contains(&scope) => Ok(()),
+            Credentials::Token { id, .. } => {
+                tracing::warn!("token {id} lacks scope {}", scope.as_ref());
+                Err(ApiError::Forbidden {
+                    trace_id: trace_id.clone(),
+                })
+            }
+        }
+    }
+
+    /// Returns the session uid and id, API tokens are rejected.
+    pub fn require_session(&self, trace_id: &TraceId) -> Result<(&str, SessionId), ApiError> {
+        match &self.credentials {
+            Credentials::Session { uid, id, .. } => Ok((uid, *id)),
+            Credentials::Token { id, .. } => {
+                tracing::warn!("token {id} used on a session only route");
+                Err(ApiError::Forbidden {
+                    trace_id: trace_id.clone(),
+                })
+            }
+        }
+    }
+
+    pub fn session_id(&self) -> Option<SessionId> {
+        match &self.credentials {
+            Credentials::Session { id, .. } => Some(*id),
+            Credentials::Token { .. } => None,
+        }
+    }
+
+    /// Whether the session comes from an identity provider login made within `max_age`.
+    ///
+    /// Accounts without a password confirm sensitive changes this way instead.
+    pub fn has_recent_external_auth(&self, max_age: Duration, now: OffsetDateTime) -> bool {
+        match &self.credentials {
+            Credentials::Session {
+                external_auth_at: Some(external_auth_at),
+                ..
+            } => now - *external_auth_at <= max_age,
+            _ => false,
+        }
+    }
+
+    /// Administrators act only through sessions, their API tokens get no extra privileges.
+    pub fn is_admin_session(&self) -> bool {
+        self.user.is_admin && self.session_id().is_some()
+    }
+}
+
+/// Hashes an API token for storage, tokens are random so a single SHA-256 is enough.
+pub fn hash_api_token(token: &str) -> String {
+    hex::encode(sha2::Sha256::digest(token.as_bytes()))
 }
 
 fn unauthorized(trace_id: TraceId) -> Response {
     ApiError::Unauthorized { trace_id }.into_response()
 }
 
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
 pub async fn auth(
     State(state): State<Arc<AppState>>,
     mut req: Request<Body>,
     next: Next,
 ) -> Response {
     tracing::info!("authenticating user...");
-    let Some(trace_id) = req.extensions().get::<TraceId>() else {
+    let Some(trace_id) = req.extensions().get::<TraceId>().cloned() else {
+        tracing::warn!("trace id not found");
+        return ApiError::Internal.into_response();
+    };
+
+    let result = match bearer_token(req.headers()) {
+        Some(token) => authenticate_token(&state, token).await.map(|auth| (auth, None)),
+        None => authenticate_session(&state, req.headers()).await,
+    };
+
+    let Some((auth_state, renewed_cookie)) = result else {
+        return unauthorized(trace_id);
+    };
+
+    req.extensions_mut().insert(Arc::new(auth_state));
+    let mut response = next.run(req).await;
+    if let Some(cookie) = renewed_cookie {
+        response.headers_mut().append(header::SET_COOKIE, cookie);
+    }
+
+    response
+}
+
+/// Lets only administrators signed in with a session through, must run inside `auth`.
+pub async fn require_admin(req: Request<Body>, next: Next) -> Response {
+    let Some(trace_id) = req.extensions().get::<TraceId>().cloned() else {
         tracing::warn!("trace id not found");
         return ApiError::Internal.into_response();
     };
 
+    let allowed = req
+        .extensions()
+        .get::<Arc<Auth>>()
+        .is_some_and(|auth| auth.is_admin_session());
+
+    if !allowed {
+        tracing::warn!("admin route denied");
+        return ApiError::Forbidden { trace_id }.into_response();
+    }
+
+    next.run(req).await
+}
+
+/// Authenticates the request when it carries credentials, but lets anonymous requests through.
+///
+/// Invalid credentials are treated as none, a renewed session cookie is not sent.
+pub async fn optional_auth(
+    State(state): State<Arc<AppState>>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    let auth = match bearer_token(req.headers()) {
+        Some(token) => authenticate_token(&state, token).await,
+        None if req.headers().contains_key(header::COOKIE) => authenticate_session(&state, req.headers())
+            .await
+            .map(|(auth, _)| auth),
+        None => None,
+    };
+
+    if let Some(auth) = auth {
+        req.extensions_mut().insert(Arc::new(auth));
+    }
+
+    next.run(req).await
+}
+
+async fn authenticate_token(state: &AppState, token: &str) -> Option<Auth> {
+    tracing::trace!("authenticating API token...");
+    if !token.starts_with(API_TOKEN_PREFIX) {
+        tracing::warn!("malformed API token");
+        return None;
+    }
+
+    let token = match state.tokens.get_token_by_hash(&hash_api_token(token)).await {
+        Ok(token) => token,
+        Err(err) => {
+            tracing::error!("failed to get API token: {}", err);
+            return None;
+        }
+    };
+
+    let now = OffsetDateTime::now_utc();
+    if token.expires_at.is_some_and(|expires_at| expires_at <= now) {
+        tracing::warn!("API token {} expired", token.id);
+        return None;
+    }
+
+    let user = match state.users.get_user_by_id(&token.user_id).await {
+        Ok(user) => user,
+        Err(err) => {
+            tracing::error!("failed to get user: {}", err);
+            return None;
+        }
+    };
+
+    if user.suspended_at.is_some() {
+        tracing::warn!("token {} belongs to suspended user {}", token.id, user.id);
+        return None;
+    }
+
+    if token
+        .last_used_at
+        .is_none_or(|last_used_at| now - last_used_at > LAST_SEEN_INTERVAL)
+        && let Err(err) = state.tokens.touch_token(token.id).await {
+            tracing::error!("failed to update token last used time: {}", err);
+        }
+
+    Some(Auth {
+        user,
+        credentials: Credentials::Token {
+            id: token.id,
+            scopes: token.scopes,
+        },
+    })
+}
+
+async fn authenticate_session(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Option<(Auth, Option<HeaderValue>)> {
     tracing::trace!("parsing cookie...");
-    let cookie = req
-        .headers()
+    let cookie = headers
         .get_all("Cookie")
         .into_iter()
         .filter_map(|c| c.to_str().ok())
@@ -49,23 +256,158 @@ pub async fn auth(
         Some(c) => c.value().to_owned(),
         None => {
             tracing::warn!("session cookie not found");
-            return unauthorized(trace_id.clone());
+            return None;
         }
     };
 
-    let user = match state.users.get_user_by_session(&session_uid).await {
+    let session = match state.sessions.get_session(&session_uid).await {
+        Ok(session) => session,
+        Err(err) => {
+            tracing::error!("failed to get session: {}", err);
+            return None;
+        }
+    };
+
+    let user = match state.users.get_user_by_id(&session.user_id).await {
         Ok(user) => user,
         Err(err) => {
             tracing::error!("failed to get user: {}", err);
-            return unauthorized(trace_id.clone());
+            return None;
+        }
+    };
+
+    if user.suspended_at.is_some() {
+        tracing::warn!("session {} belongs to suspended user {}", session.id, user.id);
+        return None;
+    }
+
+    let now = OffsetDateTime::now_utc();
+    let renewed_cookie = match session::check_session(&session, now) {
+        SessionState::Expired => {
+            tracing::warn!("session {} expired", session.id);
+            return None;
+        }
+
+        SessionState::Active => {
+            if now - session.last_seen_at > LAST_SEEN_INTERVAL
+                && let Err(err) = state.sessions.touch_session(&session_uid).await {
+                    tracing::error!("failed to update session last seen time: {}", err);
+                }
+
+            None
+        }
+
+        SessionState::Renew(expires_at) => {
+            tracing::trace!("renewing session {} until {expires_at}", session.id);
+            match state.sessions.renew_session(&session_uid, expires_at).await {
+                Ok(_) => Some(session_cookie(&session_uid, expires_at - now)),
+                Err(err) => {
+                    tracing::error!("failed to renew session: {}", err);
+                    None
+                }
+            }
         }
     };
 
-    let auth_state = Arc::new(Auth {
-        session: session_uid.to_owned(),
+    let auth_state = Auth {
         user,
-    });
+        credentials: Credentials::Session {
+            uid: session_uid,
+            id: session.id,
+            external_auth_at: session.external_auth_at,
+        },
+    };
 
-    req.extensions_mut().insert(auth_state);
-    next.run(req).await
-}
\ No newline at end of file
+    Some((auth_state, renewed_cookie))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::users::UserId;
+
+    fn token_auth(scopes: Vec<Scope>) -> Auth {
+        Auth {
+            user: User {
+                id: UserId::new(1),
+                username: "bot".to_owned(),
+                password: String::new(),
+                is_bot: false,
+                is_admin: false,
+                suspended_at: None,
+                created_at: OffsetDateTime::now_utc(),
+            },
+            credentials: Credentials::Token {
+                id: ApiTokenId::new(1),
+                scopes,
+            },
+        }
+    }
+
+    #[test]
+    fn test_require_scope() {
+        let auth = token_auth(vec![Scope::ReadMessages]);
+        let trace_id = TraceId::new();
+
+        assert!(auth.require_scope(Scope::ReadMessages, &trace_id).is_ok());
+        assert!(matches!(
+            auth.require_scope(Scope::SendMessages, &trace_id),
+            Err(ApiError::Forbidden { .. })
+        ));
+        assert!(matches!(
+            auth.require_session(&trace_id),
+            Err(ApiError::Forbidden { .. })
+        ));
+    }
+
+    #[test]
+    fn test_is_admin_session() {
+        let mut auth = token_auth(Vec::new());
+        auth.user.is_admin = true;
+        assert!(!auth.is_admin_session());
+
+        auth.credentials = Credentials::Session {
+            uid: "uid".to_owned(),
+            id: SessionId::new(1),
+            external_auth_at: None,
+        };
+        assert!(auth.is_admin_session());
+
+        auth.user.is_admin = false;
+        assert!(!auth.is_admin_session());
+    }
+
+    #[test]
+    fn test_has_recent_external_auth() {
+        let now = OffsetDateTime::now_utc();
+        let mut auth = token_auth(Vec::new());
+        assert!(!auth.has_recent_external_auth(Duration::minutes(5), now));
+
+        auth.credentials = Credentials::Session {
+            uid: "uid".to_owned(),
+            id: SessionId::new(1),
+            external_auth_at: None,
+        };
+        assert!(!auth.has_recent_external_auth(Duration::minutes(5), now));
+
+        auth.credentials = Credentials::Session {
+            uid: "uid".to_owned(),
+            id: SessionId::new(1),
+            external_auth_at: Some(now - Duration::minutes(2)),
+        };
+        assert!(auth.has_recent_external_auth(Duration::minutes(5), now));
+        assert!(!auth.has_recent_external_auth(Duration::minutes(1), now));
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(bearer_token(&headers), None);
+
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer lt_abc"));
+        assert_eq!(bearer_token(&headers), Some("lt_abc"));
+
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Basic abc"));
+        assert_eq!(bearer_token(&headers), None);
+    }
+}
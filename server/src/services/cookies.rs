@@ -0,0 +1,155 @@
+use std::sync::OnceLock;
+use time::Duration;
+use axum::http::HeaderValue;
+use cookie::{Cookie, CookieBuilder, SameSite};
+use crate::services::auth::SESSION_COOKIE_NAME;
+
+static CONFIG: OnceLock<CookieConfig> = OnceLock::new();
+
+/// Attributes applied to every cookie the server sets or clears.
+#[derive(Clone, Debug)]
+pub struct CookieConfig {
+    pub secure: bool,
+    pub same_site: SameSite,
+    pub domain: Option<String>,
+}
+
+impl Default for CookieConfig {
+    fn default() -> Self {
+        Self {
+            secure: true,
+            same_site: SameSite::Lax,
+            domain: None,
+        }
+    }
+}
+
+impl CookieConfig {
+    /// Reads `COOKIE_SECURE` (`true`/`false`), `COOKIE_SAME_SITE` (`strict`/`lax`/`none`)
+    /// and `COOKIE_DOMAIN`, falling back to secure defaults.
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let secure = match std::env::var("COOKIE_SECURE") {
+            Ok(value) => value.parse().expect("COOKIE_SECURE must be true or false"),
+            Err(_) => default.secure,
+        };
+
+        let same_site = match std::env::var("COOKIE_SAME_SITE") {
+            Ok(value) => match value.to_ascii_lowercase().as_str() {
+                "strict" => SameSite::Strict,
+                "lax" => SameSite::Lax,
+                "none" => SameSite::None,
+                _ => panic!("COOKIE_SAME_SITE must be strict, lax or none"),
+            },
+            Err(_) => default.same_site,
+        };
+
+        Self {
+            secure,
+            same_site,
+            domain: std::env::var("COOKIE_DOMAIN").ok(),
+        }
+    }
+
+    /// Makes this configuration the one used by [`session_cookie`] and [`removal_cookie`].
+    ///
+    /// Must be called once at startup, before the first request is served.
+    pub fn init(self) {
+        if CONFIG.set(self).is_err() {
+            tracing::warn!("cookie config is already initialized");
+        }
+    }
+}
+
+fn config() -> &'static CookieConfig {
+    CONFIG.get_or_init(CookieConfig::default)
+}
+
+/// Name of the cookie binding an external login to the browser that started it.
+pub const AUTH_STATE_COOKIE_NAME: &str = "auth_state";
+/// Path of the login and callback endpoints of identity providers.
+const AUTH_STATE_COOKIE_PATH: &str = "/auth";
+
+fn build(
+    name: &'static str,
+    value: &str,
+    max_age: Duration,
+    path: &'static str,
+    same_site: SameSite,
+) -> HeaderValue {
+    let config = config();
+    let mut cookie: Cookie = CookieBuilder::new(name, value.to_owned())
+        .max_age(max_age)
+        .path(path)
+        .http_only(true)
+        .secure(config.secure)
+        .same_site(same_site)
+        .build();
+
+    if let Some(domain) = &config.domain {
+        cookie.set_domain(domain.clone());
+    }
+
+    HeaderValue::from_str(&cookie.to_string()).expect("invalid cookie")
+}
+
+/// Builds the `Set-Cookie` value carrying the session uid.
+pub fn session_cookie(uid: &str, max_age: Duration) -> HeaderValue {
+    build(SESSION_COOKIE_NAME, uid, max_age, "/", config().same_site)
+}
+
+/// Builds the `Set-Cookie` value that removes the session cookie.
+pub fn removal_cookie() -> HeaderValue {
+    build(SESSION_COOKIE_NAME, "_", Duration::ZERO, "/", config().same_site)
+}
+
+/// Builds the `Set-Cookie` value carrying the state of an external login.
+///
+/// Always `Lax`, the callback is a cross-site navigation from the provider.
+pub fn auth_state_cookie(state: &str, max_age: Duration) -> HeaderValue {
+    build(AUTH_STATE_COOKIE_NAME, state, max_age, AUTH_STATE_COOKIE_PATH, SameSite::Lax)
+}
+
+/// Builds the `Set-Cookie` value that removes the external login state cookie.
+pub fn auth_state_removal_cookie() -> HeaderValue {
+    build(AUTH_STATE_COOKIE_NAME, "_", Duration::ZERO, AUTH_STATE_COOKIE_PATH, SameSite::Lax)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_cookie_attributes() {
+        let cookie = session_cookie("uid", Duration::seconds(60));
+        let cookie = cookie.to_str().unwrap();
+
+        assert!(cookie.starts_with("session=uid"));
+        assert!(cookie.contains("HttpOnly"));
+        assert!(cookie.contains("SameSite=Lax"));
+        assert!(cookie.contains("Secure"));
+        assert!(cookie.contains("Path=/"));
+        assert!(cookie.contains("Max-Age=60"));
+    }
+
+    #[test]
+    fn test_removal_cookie_expires() {
+        let cookie = removal_cookie();
+        let cookie = cookie.to_str().unwrap();
+
+        assert!(cookie.contains("Max-Age=0"));
+        assert!(cookie.contains("HttpOnly"));
+    }
+
+    #[test]
+    fn test_auth_state_cookie_attributes() {
+        let cookie = auth_state_cookie("state", Duration::seconds(600));
+        let cookie = cookie.to_str().unwrap();
+
+        assert!(cookie.starts_with("auth_state=state"));
+        assert!(cookie.contains("HttpOnly"));
+        assert!(cookie.contains("SameSite=Lax"));
+        assert!(cookie.contains("Path=/auth"));
+        assert!(cookie.contains("Max-Age=600"));
+    }
+}
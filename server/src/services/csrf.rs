@@ -0,0 +1,102 @@
+use axum::{
+    extract::Request,
+    middleware::Next,
+    http::{HeaderMap, Method, header},
+    response::{IntoResponse, Response},
+};
+use crate::{error::ApiError, services::trace::TraceId};
+
+/// Rejects cross-site state-changing requests.
+///
+/// Browsers send `Sec-Fetch-Site` and/or `Origin` with every non-safe request,
+/// so a request is allowed when it comes from the same origin. Requests carrying
+/// neither header do not come from a browser and cannot ride a user's cookie.
+pub async fn csrf(req: Request, next: Next) -> Response {
+    if is_allowed(req.method(), req.headers()) {
+        return next.run(req).await;
+    }
+
+    tracing::warn!("cross-site request rejected");
+    match req.extensions().get::<TraceId>() {
+        Some(trace_id) => ApiError::Forbidden {
+            trace_id: trace_id.clone(),
+        }
+        .into_response(),
+        None => ApiError::Internal.into_response(),
+    }
+}
+
+fn is_allowed(method: &Method, headers: &HeaderMap) -> bool {
+    if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return true;
+    }
+
+    if let Some(site) = headers.get("Sec-Fetch-Site") {
+        return matches!(site.to_str(), Ok("same-origin" | "none"));
+    }
+
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        return true;
+    };
+
+    let (Ok(origin), Some(Ok(host))) = (
+        origin.to_str(),
+        headers.get(header::HOST).map(|host| host.to_str()),
+    ) else {
+        return false;
+    };
+
+    origin
+        .split_once("://")
+        .is_some_and(|(_, origin_host)| origin_host.eq_ignore_ascii_case(host))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_static(value));
+        }
+
+        headers
+    }
+
+    #[test]
+    fn test_safe_method_allowed() {
+        let headers = headers(&[("Sec-Fetch-Site", "cross-site")]);
+        assert!(is_allowed(&Method::GET, &headers));
+    }
+
+    #[test]
+    fn test_same_origin_allowed() {
+        let headers = headers(&[("Sec-Fetch-Site", "same-origin")]);
+        assert!(is_allowed(&Method::POST, &headers));
+    }
+
+    #[test]
+    fn test_cross_site_rejected() {
+        let headers = headers(&[("Sec-Fetch-Site", "cross-site"), ("Host", "chat.local")]);
+        assert!(!is_allowed(&Method::DELETE, &headers));
+    }
+
+    #[test]
+    fn test_origin_matching_host_allowed() {
+        let headers = headers(&[("Origin", "https://chat.local:4000"), ("Host", "chat.local:4000")]);
+        assert!(is_allowed(&Method::POST, &headers));
+    }
+
+    #[test]
+    fn test_foreign_origin_rejected() {
+        let headers = headers(&[("Origin", "https://evil.example"), ("Host", "chat.local")]);
+        assert!(!is_allowed(&Method::POST, &headers));
+    }
+
+    #[test]
+    fn test_non_browser_client_allowed() {
+        assert!(is_allowed(&Method::POST, &HeaderMap::new()));
+    }
+}
@@ -0,0 +1,393 @@
+use std::{
+    fs::{DirBuilder, File, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use dashmap::DashMap;
+use subtle::ConstantTimeEq;
+use time::OffsetDateTime;
+use zip::{ZipWriter, write::SimpleFileOptions};
+use crate::models::{
+    export::{EXPORT_LINK_LIFETIME, ExportData, ExportStatus, GetExportResponse},
+    users::UserId,
+};
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Clone, Debug)]
+struct ExportJob {
+    /// Secret part of the download link, also names the archive on disk.
+    token: String,
+    status: ExportStatus,
+    progress: u8,
+    created_at: OffsetDateTime,
+    expires_at: Option<OffsetDateTime>,
+}
+
+/// Data exports of the users, at most one per user, kept in process memory.
+///
+/// Archives are written to `dir` and deleted once downloaded or expired.
+pub struct ExportTracker {
+    jobs: DashMap<UserId, ExportJob>,
+    dir: PathBuf,
+}
+
+impl ExportTracker {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            jobs: DashMap::new(),
+            dir,
+        }
+    }
+
+    /// Reads `EXPORT_DIR`, falling back to `justice-exports` in the temporary directory.
+    ///
+    /// Panics unless the directory is private to the server user, see [`prepare_dir`].
+    pub fn from_env() -> Self {
+        let dir = std::env::var_os("EXPORT_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| std::env::temp_dir().join("justice-exports"));
+        if let Err(err) = prepare_dir(&dir) {
+            panic!("refusing to use export directory {}: {err}", dir.display());
+        }
+
+        Self::new(dir)
+    }
+
+    pub fn path(&self, token: &str) -> PathBuf {
+        self.dir.join(format!("{token}.zip"))
+    }
+
+    pub fn get(&self, user_id: UserId) -> Option<GetExportResponse> {
+        self.jobs.get(&user_id).map(|job| job.response())
+    }
+
+    /// Starts a new export unless one is still building, returns its state and whether it was started.
+    ///
+    /// A previous archive that was not downloaded is replaced, the returned path should be deleted.
+    pub fn start(
+        &self,
+        user_id: UserId,
+        token: String,
+        now: OffsetDateTime,
+    ) -> (GetExportResponse, bool, Option<PathBuf>) {
+        let mut replaced = None;
+        let mut job = self.jobs.entry(user_id).or_insert_with(|| ExportJob::new(token.clone(), now));
+        if job.token != token {
+            if job.status == ExportStatus::Building {
+                return (job.response(), false, None);
+            }
+
+            if job.status == ExportStatus::Ready {
+                replaced = Some(self.path(&job.token));
+            }
+
+            *job = ExportJob::new(token, now);
+        }
+
+        (job.response(), true, replaced)
+    }
+
+    pub fn set_progress(&self, user_id: UserId, token: &str, progress: u8) {
+        if let Some(mut job) = self.jobs.get_mut(&user_id)
+            && job.token == token
+        {
+            job.progress = progress.min(99);
+        }
+    }
+
+    /// Marks the archive as ready, returns `false` if the export was replaced or removed meanwhile.
+    pub fn finish(&self, user_id: UserId, token: &str, now: OffsetDateTime) -> bool {
+        match self.jobs.get_mut(&user_id) {
+            Some(mut job) if job.token == token => {
+                job.status = ExportStatus::Ready;
+                job.progress = 100;
+                job.expires_at = Some(now + EXPORT_LINK_LIFETIME);
+                true
+            }
+
+            _ => false,
+        }
+    }
+
+    pub fn fail(&self, user_id: UserId, token: &str) {
+        if let Some(mut job) = self.jobs.get_mut(&user_id)
+            && job.token == token
+        {
+            job.status = ExportStatus::Failed;
+        }
+    }
+
+    /// Consumes the download link, returns the archive path if the link is valid.
+    pub fn take(&self, user_id: UserId, token: &str, now: OffsetDateTime) -> Option<PathBuf> {
+        let (_, job) = self.jobs.remove_if(&user_id, |_, job| {
+            bool::from(job.token.as_bytes().ct_eq(token.as_bytes()))
+                && job.status == ExportStatus::Ready
+                && job.expires_at.is_some_and(|expires_at| expires_at > now)
+        })?;
+
+        Some(self.path(&job.token))
+    }
+
+    /// Forgets the export of the user, returns the archive to delete if any.
+    pub fn remove(&self, user_id: UserId) -> Option<PathBuf> {
+        let (_, job) = self.jobs.remove(&user_id)?;
+        (job.status == ExportStatus::Ready).then(|| self.path(&job.token))
+    }
+
+    /// Forgets expired exports, returns the archives to delete.
+    pub fn remove_expired(&self, now: OffsetDateTime) -> Vec<PathBuf> {
+        let mut expired = Vec::new();
+        self.jobs.retain(|_, job| {
+            let alive = job.expires_at.is_none_or(|expires_at| expires_at > now);
+            if !alive {
+                expired.push(self.path(&job.token));
+            }
+
+            alive
+        });
+
+        expired
+    }
+}
+
+impl ExportJob {
+    fn new(token: String, now: OffsetDateTime) -> Self {
+        Self {
+            token,
+            status: ExportStatus::Building,
+            progress: 0,
+            created_at: now,
+            expires_at: None,
+        }
+    }
+
+    fn response(&self) -> GetExportResponse {
+        GetExportResponse {
+            status: self.status,
+            progress: self.progress,
+            download_url: (self.status == ExportStatus::Ready).then(|| format!("/account/export/{}", self.token)),
+            expires_at: self.expires_at,
+            created_at: self.created_at,
+        }
+    }
+}
+
+/// Creates the export directory accessible by the server user only, or checks an existing one.
+///
+/// Archives hold all personal data of a user, so an existing directory that is a symlink,
+/// belongs to another user or is open to group or others is rejected rather than fixed,
+/// it may have been planted by another local account.
+pub fn prepare_dir(dir: &Path) -> io::Result<()> {
+    if let Some(parent) = dir.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    match DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(err) => return Err(err),
+    }
+
+    let metadata = std::fs::symlink_metadata(dir)?;
+    if !metadata.file_type().is_dir() {
+        return Err(io::Error::other("not a directory"));
+    }
+
+    // SAFETY: geteuid has no preconditions and always succeeds
+    let uid = unsafe { libc::geteuid() };
+    if metadata.uid() != uid {
+        return Err(io::Error::other(format!("owned by uid {} instead of {uid}", metadata.uid())));
+    }
+
+    if metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::other(format!("mode {:o} is open to other users, expected 700", metadata.mode() & 0o777)));
+    }
+
+    Ok(())
+}
+
+/// Creates a new archive file readable by the server user only, an existing file is never reused.
+pub fn create_archive(path: &Path) -> io::Result<File> {
+    OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)
+}
+
+/// Starts a task that periodically deletes expired archives.
+///
+/// Exports do not survive a restart, so archives left by a previous run are deleted first.
+pub fn start_cleanup_task(exports: Arc<ExportTracker>) {
+    tokio::spawn(async move {
+        if let Err(err) = remove_archives(&exports.dir).await {
+            tracing::error!("failed to remove previous export archives: {err}");
+        }
+
+        loop {
+            tokio::time::sleep(CLEANUP_INTERVAL).await;
+            for path in exports.remove_expired(OffsetDateTime::now_utc()) {
+                if let Err(err) = tokio::fs::remove_file(&path).await {
+                    tracing::warn!("failed to remove expired export archive {}: {err}", path.display());
+                }
+            }
+        }
+    });
+}
+
+async fn remove_archives(dir: &Path) -> io::Result<()> {
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        tokio::fs::remove_file(entry.path()).await?;
+    }
+
+    Ok(())
+}
+
+/// Writes the export as a ZIP archive with one JSON file per kind of data.
+pub fn write_archive(writer: impl Write + io::Seek, data: &ExportData) -> zip::result::ZipResult<()> {
+    let mut zip = ZipWriter::new(writer);
+    let options = SimpleFileOptions::default();
+
+    zip.start_file("account.json", options)?;
+    serde_json::to_writer_pretty(&mut zip, &data.account).map_err(std::io::Error::from)?;
+    zip.start_file("sessions.json", options)?;
+    serde_json::to_writer_pretty(&mut zip, &data.sessions).map_err(std::io::Error::from)?;
+    zip.start_file("chats.json", options)?;
+    serde_json::to_writer_pretty(&mut zip, &data.chats).map_err(std::io::Error::from)?;
+    zip.start_file("messages.json", options)?;
+    serde_json::to_writer_pretty(&mut zip, &data.messages).map_err(std::io::Error::from)?;
+
+    zip.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::export::ExportedAccount;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("justice-test-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_prepare_dir_creates_private_dir() {
+        let dir = scratch_dir("private");
+        prepare_dir(&dir).unwrap();
+        assert_eq!(std::fs::metadata(&dir).unwrap().mode() & 0o777, 0o700);
+        // an existing private directory is accepted
+        prepare_dir(&dir).unwrap();
+
+        let path = dir.join("a.zip");
+        create_archive(&path).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().mode() & 0o777, 0o600);
+        assert!(create_archive(&path).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_prepare_dir_rejects_open_or_linked_dir() {
+        let dir = scratch_dir("open");
+        DirBuilder::new().mode(0o755).create(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+        assert!(prepare_dir(&dir).is_err());
+
+        let link = scratch_dir("link");
+        std::fs::set_permissions(&dir, std::os::unix::fs::PermissionsExt::from_mode(0o700)).unwrap();
+        std::os::unix::fs::symlink(&dir, &link).unwrap();
+        assert!(prepare_dir(&link).is_err());
+
+        std::fs::remove_file(&link).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_export_lifecycle() {
+        let tracker = ExportTracker::new(PathBuf::from("/tmp"));
+        let user_id = UserId::new(1);
+        let now = OffsetDateTime::now_utc();
+
+        let (export, started, _) = tracker.start(user_id, "a".to_owned(), now);
+        assert!(started);
+        assert_eq!(export.status, ExportStatus::Building);
+
+        // a second request while building returns the running export
+        let (_, started, _) = tracker.start(user_id, "b".to_owned(), now);
+        assert!(!started);
+
+        tracker.set_progress(user_id, "a", 50);
+        assert_eq!(tracker.get(user_id).unwrap().progress, 50);
+        assert!(tracker.finish(user_id, "a", now));
+        assert_eq!(tracker.get(user_id).unwrap().download_url.as_deref(), Some("/account/export/a"));
+
+        assert_eq!(tracker.take(user_id, "b", now), None);
+        assert_eq!(tracker.take(user_id, "a", now + EXPORT_LINK_LIFETIME), None);
+        assert_eq!(tracker.take(user_id, "a", now), Some(PathBuf::from("/tmp/a.zip")));
+        // the link works once
+        assert_eq!(tracker.take(user_id, "a", now), None);
+        assert!(tracker.get(user_id).is_none());
+    }
+
+    #[test]
+    fn test_replaced_export() {
+        let tracker = ExportTracker::new(PathBuf::from("/tmp"));
+        let user_id = UserId::new(1);
+        let now = OffsetDateTime::now_utc();
+
+        tracker.start(user_id, "a".to_owned(), now);
+        tracker.finish(user_id, "a", now);
+        let (_, started, replaced) = tracker.start(user_id, "b".to_owned(), now);
+        assert!(started);
+        assert_eq!(replaced, Some(PathBuf::from("/tmp/a.zip")));
+
+        // the old build finishing late does not touch the new export
+        assert!(!tracker.finish(user_id, "a", now));
+        assert_eq!(tracker.get(user_id).unwrap().status, ExportStatus::Building);
+    }
+
+    #[test]
+    fn test_remove_expired() {
+        let tracker = ExportTracker::new(PathBuf::from("/tmp"));
+        let now = OffsetDateTime::now_utc();
+
+        tracker.start(UserId::new(1), "a".to_owned(), now);
+        tracker.finish(UserId::new(1), "a", now);
+        tracker.start(UserId::new(2), "b".to_owned(), now);
+
+        let expired = tracker.remove_expired(now + EXPORT_LINK_LIFETIME);
+        assert_eq!(expired, vec![PathBuf::from("/tmp/a.zip")]);
+        assert!(tracker.get(UserId::new(1)).is_none());
+        assert!(tracker.get(UserId::new(2)).is_some());
+    }
+
+    #[test]
+    fn test_write_archive() {
+        let data = ExportData {
+            account: ExportedAccount {
+                id: UserId::new(1),
+                username: "alice".to_owned(),
+                is_bot: false,
+                display_name: None,
+                bio: None,
+                status: None,
+                created_at: OffsetDateTime::now_utc(),
+            },
+            sessions: Vec::new(),
+            chats: Vec::new(),
+            messages: Vec::new(),
+        };
+
+        let mut buffer = std::io::Cursor::new(Vec::new());
+        write_archive(&mut buffer, &data).unwrap();
+
+        let archive = zip::ZipArchive::new(buffer).unwrap();
+        let names: Vec<_> = archive.file_names().collect();
+        assert_eq!(names.len(), 4);
+        assert!(names.contains(&"account.json"));
+        assert!(names.contains(&"messages.json"));
+    }
+}
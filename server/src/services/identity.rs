@@ -0,0 +1,149 @@
+use std::{collections::HashMap, sync::Arc};
+use sha2::Digest;
+use data_encoding::BASE64URL_NOPAD;
+use crate::error::IdentityError;
+
+/// Shortest and longest username accepted by `Username::validate`.
+const USERNAME_MIN_LENGTH: usize = 3;
+const USERNAME_MAX_LENGTH: usize = 30;
+
+/// Identity asserted by an external provider after a successful login.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExternalIdentity {
+    /// Stable identifier of the account at the provider, never reassigned.
+    pub subject: String,
+    pub preferred_username: Option<String>,
+    pub email: Option<String>,
+}
+
+/// External login method based on the authorization code flow with PKCE.
+///
+/// The server keeps the state, nonce and code verifier of every started login
+/// and hands them back when the provider redirects the browser to the callback.
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait IdentityProvider: Send + Sync {
+    /// Name used in the login and callback paths and stored with linked identities.
+    fn name(&self) -> &str;
+
+    /// Whether unknown identities get a new account instead of being rejected.
+    fn auto_provision(&self) -> bool;
+
+    /// URL the browser is sent to in order to log in at the provider.
+    fn authorization_url(&self, state: &str, nonce: &str, code_challenge: &str) -> String;
+
+    /// Redeems the authorization code and validates the returned identity.
+    async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<ExternalIdentity, IdentityError>;
+}
+
+/// Configured identity providers by name.
+#[derive(Clone, Default)]
+pub struct IdentityProviders {
+    providers: HashMap<String, Arc<dyn IdentityProvider>>,
+    /// Whether accounts created by a provider may set a password and log in without it.
+    local_passwords: bool,
+}
+
+impl IdentityProviders {
+    /// Reads `SSO_LOCAL_PASSWORDS` (`true`/`false`), off unless set.
+    pub fn from_env() -> Self {
+        let local_passwords = match std::env::var("SSO_LOCAL_PASSWORDS") {
+            Ok(value) => value.parse().expect("SSO_LOCAL_PASSWORDS must be true or false"),
+            Err(_) => false,
+        };
+
+        Self {
+            providers: HashMap::new(),
+            local_passwords,
+        }
+    }
+
+    pub fn add(&mut self, provider: Arc<dyn IdentityProvider>) {
+        self.providers.insert(provider.name().to_owned(), provider);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn IdentityProvider>> {
+        self.providers.get(name).cloned()
+    }
+
+    pub fn allows_local_passwords(&self) -> bool {
+        self.local_passwords
+    }
+}
+
+/// S256 code challenge (RFC 7636) of a code verifier.
+pub fn code_challenge(code_verifier: &str) -> String {
+    BASE64URL_NOPAD.encode(&sha2::Sha256::digest(code_verifier.as_bytes()))
+}
+
+/// Derives a valid username for a new account from the provider claims.
+///
+/// `suffix` is appended to resolve collisions with existing usernames.
+pub fn username_candidate(identity: &ExternalIdentity, suffix: Option<&str>) -> String {
+    let claim = identity
+        .preferred_username
+        .as_deref()
+        .or_else(|| identity.email.as_deref().and_then(|email| email.split('@').next()))
+        .unwrap_or_default();
+
+    let mut username = claim
+        .chars()
+        .filter(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '_' | '-' | '.'))
+        .collect::<String>();
+
+    if username.len() < USERNAME_MIN_LENGTH {
+        username = format!("user{username}");
+    }
+
+    let Some(suffix) = suffix else {
+        username.truncate(USERNAME_MAX_LENGTH);
+        return username;
+    };
+
+    username.truncate(USERNAME_MAX_LENGTH - suffix.len() - 1);
+    format!("{username}-{suffix}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(preferred_username: Option<&str>, email: Option<&str>) -> ExternalIdentity {
+        ExternalIdentity {
+            subject: "subject".to_owned(),
+            preferred_username: preferred_username.map(str::to_owned),
+            email: email.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn test_code_challenge_rfc_vector() {
+        // RFC 7636 appendix B
+        assert_eq!(
+            code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn test_username_candidate_from_claims() {
+        assert_eq!(username_candidate(&identity(Some("alice"), None), None), "alice");
+        assert_eq!(username_candidate(&identity(None, Some("bob.b@example.com")), None), "bob.b");
+        assert_eq!(username_candidate(&identity(Some("Иван ok"), None), None), "userok");
+        assert_eq!(username_candidate(&identity(None, None), None), "user");
+    }
+
+    #[test]
+    fn test_username_candidate_with_suffix_fits() {
+        let long = "a".repeat(40);
+        let username = username_candidate(&identity(Some(&long), None), Some("1f2e"));
+
+        assert_eq!(username.len(), USERNAME_MAX_LENGTH);
+        assert!(username.ends_with("-1f2e"));
+    }
+}
@@ -1,3 +1,14 @@
 pub mod auth;
+pub mod csrf;
+pub mod cookies;
 pub mod trace;
-pub mod session;
\ No newline at end of file
+pub mod password;
+pub mod session;
+pub mod webhooks;
+pub mod totp;
+pub mod identity;
+pub mod oidc;
+pub mod throttle;
+pub mod rate_limit;
+pub mod presence;
+pub mod export;
\ No newline at end of file
@@ -0,0 +1,417 @@
+use std::time::Duration;
+use serde::Deserialize;
+use reqwest::{Client, Url};
+use time::OffsetDateTime;
+use data_encoding::BASE64URL_NOPAD;
+use crate::{
+    error::IdentityError,
+    services::identity::{ExternalIdentity, IdentityProvider},
+};
+
+const DEFAULT_NAME: &str = "oidc";
+const DEFAULT_SCOPES: &str = "openid profile email";
+const REQUEST_TIMEOUT: u64 = 10;
+
+/// Client registration at an OpenID Connect provider.
+#[derive(Clone, Debug)]
+pub struct OidcConfig {
+    pub name: String,
+    pub issuer: String,
+    pub client_id: String,
+    /// Sent with `client_secret_post`, public clients rely on PKCE alone.
+    pub client_secret: Option<String>,
+    pub redirect_uri: String,
+    pub scopes: String,
+    pub auto_provision: bool,
+}
+
+impl OidcConfig {
+    /// Reads `OIDC_ISSUER`, `OIDC_CLIENT_ID`, `OIDC_CLIENT_SECRET`, `OIDC_REDIRECT_URI`,
+    /// `OIDC_NAME`, `OIDC_SCOPES` and `OIDC_AUTO_PROVISION` (`true`/`false`).
+    ///
+    /// Returns `None` when no issuer is configured.
+    pub fn from_env() -> Option<Self> {
+        let issuer = std::env::var("OIDC_ISSUER").ok()?;
+        let auto_provision = match std::env::var("OIDC_AUTO_PROVISION") {
+            Ok(value) => value.parse().expect("OIDC_AUTO_PROVISION must be true or false"),
+            Err(_) => true,
+        };
+
+        Some(Self {
+            name: std::env::var("OIDC_NAME").unwrap_or_else(|_| DEFAULT_NAME.to_owned()),
+            issuer,
+            client_id: std::env::var("OIDC_CLIENT_ID").expect("OIDC_CLIENT_ID must be set"),
+            client_secret: std::env::var("OIDC_CLIENT_SECRET").ok(),
+            redirect_uri: std::env::var("OIDC_REDIRECT_URI").expect("OIDC_REDIRECT_URI must be set"),
+            scopes: std::env::var("OIDC_SCOPES").unwrap_or_else(|_| DEFAULT_SCOPES.to_owned()),
+            auto_provision,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, client_id: &str) -> bool {
+        match self {
+            Audience::One(audience) => audience == client_id,
+            Audience::Many(audiences) => audiences.iter().any(|audience| audience == client_id),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct IdTokenClaims {
+    iss: String,
+    sub: String,
+    aud: Audience,
+    exp: i64,
+    nonce: Option<String>,
+    preferred_username: Option<String>,
+    email: Option<String>,
+}
+
+/// OpenID Connect provider using the authorization code flow with PKCE.
+pub struct OidcProvider {
+    config: OidcConfig,
+    metadata: ProviderMetadata,
+    client: Client,
+}
+
+impl OidcProvider {
+    /// Fetches the provider metadata from its discovery document.
+    pub async fn discover(config: OidcConfig) -> Result<Self, IdentityError> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(REQUEST_TIMEOUT))
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            config.issuer.trim_end_matches('/')
+        );
+
+        let body = client.get(url).send().await?.error_for_status()?.bytes().await?;
+        let metadata = serde_json::from_slice::<ProviderMetadata>(&body)
+            .map_err(|err| IdentityError::InvalidResponse(err.to_string()))?;
+
+        if metadata.issuer != config.issuer {
+            return Err(IdentityError::InvalidResponse(format!(
+                "discovery document is for issuer {}",
+                metadata.issuer
+            )));
+        }
+
+        Ok(Self {
+            config,
+            metadata,
+            client,
+        })
+    }
+
+    /// Validates the claims of an ID token received straight from the token endpoint.
+    ///
+    /// The token comes over TLS from the provider itself, so its signature
+    /// doesn't have to be checked (OpenID Connect Core 3.1.3.7).
+    fn validate_id_token(
+        &self,
+        id_token: &str,
+        nonce: &str,
+        now: OffsetDateTime,
+    ) -> Result<ExternalIdentity, IdentityError> {
+        let payload = id_token
+            .split('.')
+            .nth(1)
+            .ok_or(IdentityError::InvalidToken("malformed token"))?;
+
+        let payload = BASE64URL_NOPAD
+            .decode(payload.trim_end_matches('=').as_bytes())
+            .map_err(|_| IdentityError::InvalidToken("malformed payload"))?;
+
+        let claims = serde_json::from_slice::<IdTokenClaims>(&payload)
+            .map_err(|_| IdentityError::InvalidToken("malformed claims"))?;
+
+        if claims.iss != self.metadata.issuer {
+            return Err(IdentityError::InvalidToken("wrong issuer"));
+        }
+
+        if !claims.aud.contains(&self.config.client_id) {
+            return Err(IdentityError::InvalidToken("wrong audience"));
+        }
+
+        if claims.exp <= now.unix_timestamp() {
+            return Err(IdentityError::InvalidToken("expired"));
+        }
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(IdentityError::InvalidToken("wrong nonce"));
+        }
+
+        Ok(ExternalIdentity {
+            subject: claims.sub,
+            preferred_username: claims.preferred_username,
+            email: claims.email,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl IdentityProvider for OidcProvider {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn auto_provision(&self) -> bool {
+        self.config.auto_provision
+    }
+
+    fn authorization_url(&self, state: &str, nonce: &str, code_challenge: &str) -> String {
+        let mut url = Url::parse(&self.metadata.authorization_endpoint)
+            .expect("authorization endpoint is not a valid URL");
+
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", &self.config.redirect_uri)
+            .append_pair("scope", &self.config.scopes)
+            .append_pair("state", state)
+            .append_pair("nonce", nonce)
+            .append_pair("code_challenge", code_challenge)
+            .append_pair("code_challenge_method", "S256");
+
+        url.into()
+    }
+
+    async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<ExternalIdentity, IdentityError> {
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.config.redirect_uri),
+            ("client_id", &self.config.client_id),
+            ("code_verifier", code_verifier),
+        ];
+
+        if let Some(secret) = &self.config.client_secret {
+            form.push(("client_secret", secret));
+        }
+
+        let body = self
+            .client
+            .post(&self.metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+
+        let tokens = serde_json::from_slice::<TokenResponse>(&body)
+            .map_err(|err| IdentityError::InvalidResponse(err.to_string()))?;
+
+        self.validate_id_token(&tokens.id_token, nonce, OffsetDateTime::now_utc())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::HashMap, sync::{Arc, Mutex}};
+    use axum::{Form, Json, Router, extract::State, routing::{get, post}};
+    use serde_json::json;
+    use tokio::net::TcpListener;
+    use crate::services::identity::code_challenge;
+
+    const CLIENT_ID: &str = "justice";
+    const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+
+    #[derive(Clone, Default)]
+    struct Issuer {
+        url: Arc<Mutex<String>>,
+        /// Nonce and code challenge of the issued authorization code.
+        grant: Arc<Mutex<Option<(String, String)>>>,
+    }
+
+    fn id_token(claims: serde_json::Value) -> String {
+        let header = BASE64URL_NOPAD.encode(br#"{"alg":"none"}"#);
+        let payload = BASE64URL_NOPAD.encode(claims.to_string().as_bytes());
+        format!("{header}.{payload}.")
+    }
+
+    /// Starts a minimal issuer serving discovery and a token endpoint that checks PKCE.
+    async fn start_issuer() -> Issuer {
+        async fn discovery(State(issuer): State<Issuer>) -> Json<serde_json::Value> {
+            let url = issuer.url.lock().unwrap().clone();
+            Json(json!({
+                "issuer": url,
+                "authorization_endpoint": format!("{url}/authorize"),
+                "token_endpoint": format!("{url}/token"),
+            }))
+        }
+
+        async fn token(
+            State(issuer): State<Issuer>,
+            Form(form): Form<HashMap<String, String>>,
+        ) -> Result<Json<serde_json::Value>, axum::http::StatusCode> {
+            let (nonce, challenge) = issuer
+                .grant
+                .lock()
+                .unwrap()
+                .take()
+                .ok_or(axum::http::StatusCode::BAD_REQUEST)?;
+
+            if form.get("code").map(String::as_str) != Some("code")
+                || form.get("code_verifier").map(|verifier| code_challenge(verifier)) != Some(challenge)
+            {
+                return Err(axum::http::StatusCode::BAD_REQUEST);
+            }
+
+            let url = issuer.url.lock().unwrap().clone();
+            Ok(Json(json!({
+                "access_token": "access",
+                "token_type": "Bearer",
+                "id_token": id_token(json!({
+                    "iss": url,
+                    "sub": "248289761001",
+                    "aud": CLIENT_ID,
+                    "exp": OffsetDateTime::now_utc().unix_timestamp() + 60,
+                    "nonce": nonce,
+                    "preferred_username": "alice",
+                })),
+            })))
+        }
+
+        let issuer = Issuer::default();
+        let app = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/token", post(token))
+            .with_state(issuer.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        *issuer.url.lock().unwrap() = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        issuer
+    }
+
+    fn config(issuer: &str) -> OidcConfig {
+        OidcConfig {
+            name: DEFAULT_NAME.to_owned(),
+            issuer: issuer.to_owned(),
+            client_id: CLIENT_ID.to_owned(),
+            client_secret: None,
+            redirect_uri: "http://localhost:4000/auth/oidc/callback".to_owned(),
+            scopes: DEFAULT_SCOPES.to_owned(),
+            auto_provision: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_authorization_url() {
+        let issuer = start_issuer().await;
+        let url = issuer.url.lock().unwrap().clone();
+        let provider = OidcProvider::discover(config(&url)).await.unwrap();
+
+        let authorization = Url::parse(&provider.authorization_url("state", "nonce", "challenge")).unwrap();
+        let params = authorization.query_pairs().into_owned().collect::<HashMap<_, _>>();
+
+        assert_eq!(authorization.path(), "/authorize");
+        assert_eq!(params["client_id"], CLIENT_ID);
+        assert_eq!(params["state"], "state");
+        assert_eq!(params["nonce"], "nonce");
+        assert_eq!(params["code_challenge"], "challenge");
+        assert_eq!(params["code_challenge_method"], "S256");
+    }
+
+    #[tokio::test]
+    async fn test_exchange_code() {
+        let issuer = start_issuer().await;
+        let url = issuer.url.lock().unwrap().clone();
+        let provider = OidcProvider::discover(config(&url)).await.unwrap();
+        *issuer.grant.lock().unwrap() = Some(("nonce".to_owned(), code_challenge(VERIFIER)));
+
+        let identity = provider.exchange_code("code", VERIFIER, "nonce").await.unwrap();
+
+        assert_eq!(identity.subject, "248289761001");
+        assert_eq!(identity.preferred_username.as_deref(), Some("alice"));
+    }
+
+    #[tokio::test]
+    async fn test_exchange_code_wrong_verifier() {
+        let issuer = start_issuer().await;
+        let url = issuer.url.lock().unwrap().clone();
+        let provider = OidcProvider::discover(config(&url)).await.unwrap();
+        *issuer.grant.lock().unwrap() = Some(("nonce".to_owned(), code_challenge(VERIFIER)));
+
+        let result = provider.exchange_code("code", "other-verifier", "nonce").await;
+
+        assert!(matches!(result, Err(IdentityError::Http(_))));
+    }
+
+    #[tokio::test]
+    async fn test_exchange_code_wrong_nonce() {
+        let issuer = start_issuer().await;
+        let url = issuer.url.lock().unwrap().clone();
+        let provider = OidcProvider::discover(config(&url)).await.unwrap();
+        *issuer.grant.lock().unwrap() = Some(("nonce".to_owned(), code_challenge(VERIFIER)));
+
+        let result = provider.exchange_code("code", VERIFIER, "replayed").await;
+
+        assert!(matches!(result, Err(IdentityError::InvalidToken("wrong nonce"))));
+    }
+
+    #[test]
+    fn test_validate_id_token_claims() {
+        let provider = OidcProvider {
+            config: config("https://issuer.example"),
+            metadata: ProviderMetadata {
+                issuer: "https://issuer.example".to_owned(),
+                authorization_endpoint: "https://issuer.example/authorize".to_owned(),
+                token_endpoint: "https://issuer.example/token".to_owned(),
+            },
+            client: Client::new(),
+        };
+        let now = OffsetDateTime::now_utc();
+        let token = |iss: &str, aud: serde_json::Value, exp: i64| {
+            id_token(json!({"iss": iss, "sub": "1", "aud": aud, "exp": exp, "nonce": "nonce"}))
+        };
+        let valid_exp = now.unix_timestamp() + 60;
+
+        assert!(provider
+            .validate_id_token(&token("https://issuer.example", json!(["other", CLIENT_ID]), valid_exp), "nonce", now)
+            .is_ok());
+        assert!(matches!(
+            provider.validate_id_token(&token("https://evil.example", json!(CLIENT_ID), valid_exp), "nonce", now),
+            Err(IdentityError::InvalidToken("wrong issuer"))
+        ));
+        assert!(matches!(
+            provider.validate_id_token(&token("https://issuer.example", json!("other"), valid_exp), "nonce", now),
+            Err(IdentityError::InvalidToken("wrong audience"))
+        ));
+        assert!(matches!(
+            provider.validate_id_token(&token("https://issuer.example", json!(CLIENT_ID), now.unix_timestamp()), "nonce", now),
+            Err(IdentityError::InvalidToken("expired"))
+        ));
+    }
+}
@@ -0,0 +1,177 @@
+use sha2::Digest;
+use subtle::ConstantTimeEq;
+use tokio::task::spawn_blocking;
+use argon2::{
+    Algorithm, Argon2, Params, Version,
+    password_hash::{self, PasswordHasher as _, PasswordVerifier, SaltString},
+};
+use crate::{error::PasswordError, models::users::PasswordHash};
+
+/// Outcome of checking a password against a stored hash.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verification {
+    Invalid,
+    Valid,
+    /// The password matches, but the stored hash is a legacy SHA-256 digest
+    /// or uses other Argon2 parameters and should be replaced.
+    Outdated,
+}
+
+/// Hashes and verifies passwords with Argon2id.
+///
+/// Hashes are stored as PHC strings, so every row carries its own parameters
+/// and the configured cost can be raised without touching existing rows.
+#[derive(Clone)]
+pub struct PasswordHasher {
+    params: Params,
+}
+
+impl PasswordHasher {
+    pub fn new(params: Params) -> Self {
+        Self { params }
+    }
+
+    /// Reads the Argon2 cost from `PASSWORD_MEMORY_COST` (KiB), `PASSWORD_TIME_COST`
+    /// and `PASSWORD_PARALLELISM`, falling back to the OWASP recommended defaults.
+    pub fn from_env() -> Self {
+        let params = Params::new(
+            env_or("PASSWORD_MEMORY_COST", Params::DEFAULT_M_COST),
+            env_or("PASSWORD_TIME_COST", Params::DEFAULT_T_COST),
+            env_or("PASSWORD_PARALLELISM", Params::DEFAULT_P_COST),
+            None,
+        )
+        .expect("invalid password hashing parameters");
+
+        Self::new(params)
+    }
+
+    /// Hashes `password` with the given salt on the blocking thread pool.
+    pub async fn hash(&self, password: &str, salt: &str) -> Result<PasswordHash, PasswordError> {
+        let hasher = self.clone();
+        let password = password.to_owned();
+        let salt = salt.to_owned();
+        spawn_blocking(move || hasher.hash_blocking(&password, &salt)).await?
+    }
+
+    /// Verifies `password` against an Argon2 PHC string on the blocking thread pool.
+    pub async fn verify(&self, password: &str, stored: &str) -> Verification {
+        let hasher = self.clone();
+        let password = password.to_owned();
+        let stored = stored.to_owned();
+        spawn_blocking(move || hasher.verify_blocking(&password, &stored))
+            .await
+            .unwrap_or_else(|err| {
+                tracing::error!("password verification task failed: {err}");
+                Verification::Invalid
+            })
+    }
+
+    fn argon2(&self) -> Argon2<'_> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+
+    fn hash_blocking(&self, password: &str, salt: &str) -> Result<PasswordHash, PasswordError> {
+        let salt = SaltString::from_b64(salt)?;
+        let hash = self.argon2().hash_password(password.as_bytes(), &salt)?;
+        Ok(PasswordHash::new(hash.to_string()))
+    }
+
+    fn verify_blocking(&self, password: &str, stored: &str) -> Verification {
+        let hash = match password_hash::PasswordHash::new(stored) {
+            Ok(hash) => hash,
+            Err(err) => {
+                tracing::error!("failed to parse password hash: {err}");
+                return Verification::Invalid;
+            }
+        };
+
+        if self.argon2().verify_password(password.as_bytes(), &hash).is_err() {
+            return Verification::Invalid;
+        }
+
+        if self.is_current(&hash) {
+            Verification::Valid
+        } else {
+            Verification::Outdated
+        }
+    }
+
+    fn is_current(&self, hash: &password_hash::PasswordHash) -> bool {
+        let Ok(params) = Params::try_from(hash) else {
+            return false;
+        };
+
+        hash.algorithm == Algorithm::Argon2id.ident()
+            && hash.version == Some(Version::V0x13.into())
+            && params.m_cost() == self.params.m_cost()
+            && params.t_cost() == self.params.t_cost()
+            && params.p_cost() == self.params.p_cost()
+    }
+}
+
+/// Returns `true` if the stored hash predates Argon2 and is a hex SHA-256 digest.
+pub fn is_legacy(stored: &str) -> bool {
+    !stored.starts_with('$')
+}
+
+/// Verifies `password` against a legacy `sha256(password + salt)` digest.
+pub fn verify_legacy(password: &str, stored: &str, salt: &str) -> Verification {
+    let hash = hex::encode(sha2::Sha256::digest(format!("{password}{salt}").as_bytes()));
+    if bool::from(hash.as_bytes().ct_eq(stored.trim().as_bytes())) {
+        Verification::Outdated
+    } else {
+        Verification::Invalid
+    }
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("{name} must be a number")),
+        Err(_) => default,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::test;
+
+    fn hasher(t_cost: u32) -> PasswordHasher {
+        PasswordHasher::new(Params::new(8, t_cost, 1, None).unwrap())
+    }
+
+    #[test]
+    async fn test_hash_and_verify_ok() {
+        let hasher = hasher(1);
+        let hash = hasher.hash("ValidPass123", "c2FsdHNhbHRzYWx0").await.unwrap();
+
+        assert!(hash.starts_with("$argon2id$"));
+        assert_eq!(hasher.verify("ValidPass123", &hash).await, Verification::Valid);
+    }
+
+    #[test]
+    async fn test_verify_wrong_password() {
+        let hasher = hasher(1);
+        let hash = hasher.hash("ValidPass123", "c2FsdHNhbHRzYWx0").await.unwrap();
+
+        assert_eq!(hasher.verify("WrongPass123", &hash).await, Verification::Invalid);
+    }
+
+    #[test]
+    async fn test_verify_outdated_params() {
+        let hash = hasher(1).hash("ValidPass123", "c2FsdHNhbHRzYWx0").await.unwrap();
+
+        assert_eq!(hasher(2).verify("ValidPass123", &hash).await, Verification::Outdated);
+    }
+
+    #[test]
+    async fn test_verify_legacy() {
+        let stored = hex::encode(sha2::Sha256::digest(b"ValidPass123salt"));
+
+        assert!(is_legacy(&stored));
+        assert_eq!(verify_legacy("ValidPass123", &stored, "salt"), Verification::Outdated);
+        assert_eq!(verify_legacy("WrongPass123", &stored, "salt"), Verification::Invalid);
+    }
+}
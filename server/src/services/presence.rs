@@ -0,0 +1,201 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use dashmap::DashMap;
+use crate::models::{presence::PresenceStatus, sessions::SessionId, users::UserId};
+
+/// Time a user stays online after the last event stream closed, so reloads and reconnects go unnoticed.
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Debug)]
+struct Connection {
+    id: u64,
+    session_id: Option<SessionId>,
+    idle: bool,
+}
+
+#[derive(Debug)]
+struct UserConnections {
+    connections: Vec<Connection>,
+    /// Bumped by every new connection, a grace period only expires if nothing connected since it started.
+    generation: u64,
+    /// Status kept during the grace period.
+    last_status: PresenceStatus,
+}
+
+impl UserConnections {
+    fn status(&self) -> PresenceStatus {
+        if self.connections.is_empty() {
+            self.last_status
+        } else if self.connections.iter().all(|connection| connection.idle) {
+            PresenceStatus::Idle
+        } else {
+            PresenceStatus::Online
+        }
+    }
+}
+
+/// Result of closing an event stream.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Disconnect {
+    /// Other streams are still open, the status changed if set.
+    Remaining(Option<PresenceStatus>),
+    /// That was the last stream, the user goes offline unless reconnected within the grace period.
+    Last { generation: u64 },
+}
+
+/// Live presence derived from open event streams, kept in process memory.
+pub struct PresenceTracker {
+    users: DashMap<UserId, UserConnections>,
+    next_id: AtomicU64,
+    pub grace_period: Duration,
+}
+
+impl Default for PresenceTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_GRACE_PERIOD)
+    }
+}
+
+impl PresenceTracker {
+    pub fn new(grace_period: Duration) -> Self {
+        Self {
+            users: DashMap::new(),
+            next_id: AtomicU64::new(0),
+            grace_period,
+        }
+    }
+
+    pub fn status(&self, user_id: UserId) -> PresenceStatus {
+        self.users
+            .get(&user_id)
+            .map_or(PresenceStatus::Offline, |user| user.status())
+    }
+
+    /// Registers an event stream, returns its id and the new status if it changed.
+    pub fn connect(&self, user_id: UserId, session_id: Option<SessionId>) -> (u64, Option<PresenceStatus>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut user = self.users.entry(user_id).or_insert(UserConnections {
+            connections: Vec::new(),
+            generation: 0,
+            last_status: PresenceStatus::Offline,
+        });
+
+        let before = user.status();
+        user.generation += 1;
+        user.connections.push(Connection {
+            id,
+            session_id,
+            idle: false,
+        });
+
+        let after = user.status();
+        (id, (before != after).then_some(after))
+    }
+
+    pub fn disconnect(&self, user_id: UserId, connection_id: u64) -> Disconnect {
+        let Some(mut user) = self.users.get_mut(&user_id) else {
+            return Disconnect::Remaining(None);
+        };
+
+        let before = user.status();
+        user.connections.retain(|connection| connection.id != connection_id);
+        if user.connections.is_empty() {
+            user.last_status = before;
+            return Disconnect::Last {
+                generation: user.generation,
+            };
+        }
+
+        let after = user.status();
+        Disconnect::Remaining((before != after).then_some(after))
+    }
+
+    /// Ends the grace period started by [`Disconnect::Last`], returns whether the user is offline now.
+    pub fn expire(&self, user_id: UserId, generation: u64) -> bool {
+        self.users
+            .remove_if(&user_id, |_, user| {
+                user.connections.is_empty() && user.generation == generation
+            })
+            .is_some()
+    }
+
+    /// Marks the streams of a session as idle or active, returns the new status if it changed.
+    pub fn set_idle(&self, user_id: UserId, session_id: SessionId, idle: bool) -> Option<PresenceStatus> {
+        let mut user = self.users.get_mut(&user_id)?;
+        let before = user.status();
+        for connection in user.connections.iter_mut() {
+            if connection.session_id == Some(session_id) {
+                connection.idle = idle;
+            }
+        }
+
+        let after = user.status();
+        (before != after).then_some(after)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user() -> UserId {
+        UserId::new(1)
+    }
+
+    #[test]
+    fn test_connect_and_disconnect() {
+        let tracker = PresenceTracker::default();
+        assert_eq!(tracker.status(user()), PresenceStatus::Offline);
+
+        let (first, change) = tracker.connect(user(), None);
+        assert_eq!(change, Some(PresenceStatus::Online));
+
+        let (second, change) = tracker.connect(user(), None);
+        assert_eq!(change, None);
+
+        assert_eq!(tracker.disconnect(user(), first), Disconnect::Remaining(None));
+        assert!(matches!(tracker.disconnect(user(), second), Disconnect::Last { .. }));
+        assert_eq!(tracker.status(user()), PresenceStatus::Online);
+    }
+
+    #[test]
+    fn test_reconnect_within_grace_period() {
+        let tracker = PresenceTracker::default();
+        let (id, _) = tracker.connect(user(), None);
+        let Disconnect::Last { generation } = tracker.disconnect(user(), id) else {
+            panic!("expected last connection");
+        };
+
+        let (_, change) = tracker.connect(user(), None);
+        assert_eq!(change, None);
+        assert!(!tracker.expire(user(), generation));
+        assert_eq!(tracker.status(user()), PresenceStatus::Online);
+    }
+
+    #[test]
+    fn test_expire_after_grace_period() {
+        let tracker = PresenceTracker::default();
+        let (id, _) = tracker.connect(user(), None);
+        let Disconnect::Last { generation } = tracker.disconnect(user(), id) else {
+            panic!("expected last connection");
+        };
+
+        assert!(tracker.expire(user(), generation));
+        assert_eq!(tracker.status(user()), PresenceStatus::Offline);
+    }
+
+    #[test]
+    fn test_idle_when_every_session_is_idle() {
+        let tracker = PresenceTracker::default();
+        let (laptop, phone) = (SessionId::new(1), SessionId::new(2));
+        tracker.connect(user(), Some(laptop));
+        tracker.connect(user(), Some(phone));
+
+        assert_eq!(tracker.set_idle(user(), laptop, true), None);
+        assert_eq!(tracker.set_idle(user(), phone, true), Some(PresenceStatus::Idle));
+        assert_eq!(tracker.set_idle(user(), laptop, false), Some(PresenceStatus::Online));
+        assert_eq!(tracker.set_idle(UserId::new(2), laptop, true), None);
+    }
+}
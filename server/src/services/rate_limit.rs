@@ -0,0 +1,349 @@
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use dashmap::DashMap;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, HeaderValue, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use crate::{
+    AppState,
+    error::ApiError,
+    services::{auth::Auth, trace::TraceId},
+};
+
+pub const LIMIT_HEADER: &str = "RateLimit-Limit";
+pub const REMAINING_HEADER: &str = "RateLimit-Remaining";
+pub const RESET_HEADER: &str = "RateLimit-Reset";
+
+/// Buckets kept before full ones are pruned.
+const MAX_BUCKETS: usize = 100_000;
+
+/// Requests allowed per period, `limit` of them may come in a single burst.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimitPolicy {
+    pub limit: u32,
+    pub period: Duration,
+}
+
+impl RateLimitPolicy {
+    pub const fn new(limit: u32, period_seconds: u64) -> Self {
+        Self {
+            limit,
+            period: Duration::from_secs(period_seconds),
+        }
+    }
+
+    fn refill_rate(&self) -> f64 {
+        self.limit as f64 / self.period.as_secs_f64()
+    }
+
+    /// Parses `limit/seconds`, e.g. `30/60`.
+    fn parse(value: &str) -> Option<Self> {
+        let (limit, seconds) = value.trim().split_once('/')?;
+        let limit = limit.trim().parse().ok()?;
+        let seconds = seconds.trim().parse().ok()?;
+        (limit > 0 && seconds > 0).then(|| Self::new(limit, seconds))
+    }
+}
+
+/// Policy applied to requests whose path starts with `path`, optionally only for one method.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimitRule {
+    pub method: Option<Method>,
+    pub path: String,
+    pub policy: RateLimitPolicy,
+}
+
+impl RateLimitRule {
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        if self.method.as_ref().is_some_and(|expected| expected != method) {
+            return false;
+        }
+
+        path.strip_prefix(self.path.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/') || self.path.ends_with('/'))
+    }
+
+    /// Parses `[METHOD ]/path=limit/seconds`, e.g. `GET /search/users=30/60`.
+    fn parse(value: &str) -> Option<Self> {
+        let (route, policy) = value.trim().rsplit_once('=')?;
+        let (method, path) = match route.trim().split_once(' ') {
+            Some((method, path)) => (Some(method.parse().ok()?), path.trim()),
+            None => (None, route.trim()),
+        };
+
+        if !path.starts_with('/') {
+            return None;
+        }
+
+        Some(Self {
+            method,
+            path: path.to_owned(),
+            policy: RateLimitPolicy::parse(policy)?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Outcome of taking a token from a bucket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the bucket is full again.
+    pub reset: u64,
+    /// Seconds until the next request is allowed, set when this one is rejected.
+    pub retry_after: Option<u64>,
+}
+
+impl Decision {
+    fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(LIMIT_HEADER, HeaderValue::from(self.limit));
+        headers.insert(REMAINING_HEADER, HeaderValue::from(self.remaining));
+        headers.insert(RESET_HEADER, HeaderValue::from(self.reset));
+        headers
+    }
+}
+
+/// Token bucket rate limiter with a bucket per route rule and client.
+pub struct RateLimiter {
+    rules: Vec<RateLimitRule>,
+    default: RateLimitPolicy,
+    buckets: DashMap<(usize, String), Bucket>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(
+            vec![
+                RateLimitRule {
+                    method: Some(Method::POST),
+                    path: "/users".to_owned(),
+                    policy: RateLimitPolicy::new(10, 60 * 10),
+                },
+                RateLimitRule {
+                    method: None,
+                    path: "/search/users".to_owned(),
+                    policy: RateLimitPolicy::new(30, 60),
+                },
+                RateLimitRule {
+                    method: Some(Method::POST),
+                    path: "/login".to_owned(),
+                    policy: RateLimitPolicy::new(30, 60),
+                },
+            ],
+            RateLimitPolicy::new(300, 60),
+        )
+    }
+}
+
+impl RateLimiter {
+    pub fn new(rules: Vec<RateLimitRule>, default: RateLimitPolicy) -> Self {
+        Self {
+            rules,
+            default,
+            buckets: DashMap::new(),
+        }
+    }
+
+    /// Reads `RATE_LIMIT_DEFAULT` (`limit/seconds`) and `RATE_LIMIT_ROUTES`, a comma separated
+    /// list of `[METHOD ]/path=limit/seconds` rules checked in order, falling back to the built-in limits.
+    pub fn from_env() -> Self {
+        let builtin = Self::default();
+        let default = match std::env::var("RATE_LIMIT_DEFAULT") {
+            Ok(value) => RateLimitPolicy::parse(&value).expect("RATE_LIMIT_DEFAULT must be limit/seconds"),
+            Err(_) => builtin.default,
+        };
+
+        let rules = match std::env::var("RATE_LIMIT_ROUTES") {
+            Ok(value) => value
+                .split(',')
+                .filter(|rule| !rule.trim().is_empty())
+                .map(|rule| {
+                    RateLimitRule::parse(rule)
+                        .unwrap_or_else(|| panic!("invalid RATE_LIMIT_ROUTES rule: {rule}"))
+                })
+                .collect(),
+            Err(_) => builtin.rules,
+        };
+
+        Self::new(rules, default)
+    }
+
+    /// Index of the first matching rule, `rules.len()` stands for the default policy.
+    fn rule(&self, method: &Method, path: &str) -> (usize, RateLimitPolicy) {
+        self.rules
+            .iter()
+            .position(|rule| rule.matches(method, path))
+            .map_or((self.rules.len(), self.default), |index| (index, self.rules[index].policy))
+    }
+
+    /// Takes a token from the bucket of `client` for the route.
+    pub fn acquire(&self, method: &Method, path: &str, client: &str, now: Instant) -> Decision {
+        let (rule, policy) = self.rule(method, path);
+        let rate = policy.refill_rate();
+        let capacity = policy.limit as f64;
+
+        if self.buckets.len() >= MAX_BUCKETS {
+            // a full bucket behaves exactly like a missing one
+            self.buckets.retain(|_, bucket| {
+                bucket.tokens + now.saturating_duration_since(bucket.updated_at).as_secs_f64() * rate < capacity
+            });
+        }
+
+        let mut bucket = self
+            .buckets
+            .entry((rule, client.to_owned()))
+            .or_insert(Bucket {
+                tokens: capacity,
+                updated_at: now,
+            });
+
+        let elapsed = now.saturating_duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+        bucket.updated_at = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        Decision {
+            allowed,
+            limit: policy.limit,
+            remaining: bucket.tokens.floor() as u32,
+            reset: ((capacity - bucket.tokens) / rate).ceil() as u64,
+            retry_after: (!allowed).then(|| ((1.0 - bucket.tokens) / rate).ceil().max(1.0) as u64),
+        }
+    }
+}
+
+/// Rate limits requests by authenticated user, or by client address when there is no `Auth`.
+///
+/// Must run inside `auth` on protected routes, so the user is known.
+pub async fn rate_limit(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let client = match req.extensions().get::<Arc<Auth>>() {
+        Some(auth) => format!("user:{}", auth.user.id),
+        None => match req.extensions().get::<ConnectInfo<SocketAddr>>() {
+            Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+            None => "ip:unknown".to_owned(),
+        },
+    };
+
+    let decision = state
+        .rate_limiter
+        .acquire(req.method(), req.uri().path(), &client, Instant::now());
+
+    if let Some(retry_after) = decision.retry_after {
+        tracing::warn!("rate limit exceeded by {client}");
+        let error = match req.extensions().get::<TraceId>() {
+            Some(trace_id) => ApiError::TooManyRequests {
+                retry_after,
+                trace_id: trace_id.clone(),
+            },
+            None => ApiError::Internal,
+        };
+
+        return (decision.headers(), error).into_response();
+    }
+
+    let mut response = next.run(req).await;
+    response.headers_mut().extend(decision.headers());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter() -> RateLimiter {
+        RateLimiter::new(
+            vec![RateLimitRule {
+                method: Some(Method::GET),
+                path: "/search/users".to_owned(),
+                policy: RateLimitPolicy::new(2, 10),
+            }],
+            RateLimitPolicy::new(100, 10),
+        )
+    }
+
+    #[test]
+    fn test_acquire_rejects_empty_bucket() {
+        let limiter = limiter();
+        let now = Instant::now();
+
+        let first = limiter.acquire(&Method::GET, "/search/users", "ip:1", now);
+        assert_eq!(first, Decision { allowed: true, limit: 2, remaining: 1, reset: 5, retry_after: None });
+
+        limiter.acquire(&Method::GET, "/search/users", "ip:1", now);
+        let third = limiter.acquire(&Method::GET, "/search/users", "ip:1", now);
+        assert!(!third.allowed);
+        assert_eq!(third.retry_after, Some(5));
+    }
+
+    #[test]
+    fn test_acquire_refills_over_time() {
+        let limiter = limiter();
+        let now = Instant::now();
+
+        for _ in 0..2 {
+            limiter.acquire(&Method::GET, "/search/users", "ip:1", now);
+        }
+
+        let later = limiter.acquire(&Method::GET, "/search/users", "ip:1", now + Duration::from_secs(5));
+        assert!(later.allowed);
+        assert_eq!(later.remaining, 0);
+    }
+
+    #[test]
+    fn test_acquire_separates_clients_and_rules() {
+        let limiter = limiter();
+        let now = Instant::now();
+
+        for _ in 0..2 {
+            limiter.acquire(&Method::GET, "/search/users", "ip:1", now);
+        }
+
+        assert!(limiter.acquire(&Method::GET, "/search/users", "ip:2", now).allowed);
+        assert!(limiter.acquire(&Method::GET, "/chats", "ip:1", now).allowed);
+        assert!(limiter.acquire(&Method::POST, "/search/users", "ip:1", now).allowed);
+    }
+
+    #[test]
+    fn test_rule_matches_path_segments() {
+        let rule = RateLimitRule::parse("POST /users=10/600").unwrap();
+
+        assert_eq!(rule.policy, RateLimitPolicy::new(10, 600));
+        assert!(rule.matches(&Method::POST, "/users"));
+        assert!(rule.matches(&Method::POST, "/users/1"));
+        assert!(!rule.matches(&Method::POST, "/usersearch"));
+        assert!(!rule.matches(&Method::GET, "/users"));
+    }
+
+    #[test]
+    fn test_rule_parse_rejects_invalid() {
+        assert_eq!(
+            RateLimitRule::parse("/search/users=30/60").map(|rule| rule.method),
+            Some(None)
+        );
+        assert!(RateLimitRule::parse("users=30/60").is_none());
+        assert!(RateLimitRule::parse("/users=0/60").is_none());
+        assert!(RateLimitRule::parse("/users=30").is_none());
+    }
+}
@@ -1,8 +1,14 @@
 use std::time::Duration;
+use dashmap::DashMap;
 use time::OffsetDateTime;
 use sqlx::{PgPool, query};
 use tracing::{info, error};
-use tokio::{spawn, time::sleep};
+use tokio::{spawn, sync::broadcast, time::sleep};
+use crate::models::{
+    events::SseEvent,
+    sessions::{Session, SessionId},
+    users::{SESSION_LIFETIME, SESSION_MAX_LIFETIME, SESSION_RENEWAL_INTERVAL, UserId},
+};
 
 const CLEANUP_INTERVAL: u64 = 60 * 60;
 
@@ -30,7 +36,173 @@ pub fn start_cleanup_task(db: PgPool) {
                 Err(err) => error!("failed to delete expired sessions: {}", err),
             }
 
+            if let Err(err) = query!(
+                "DELETE FROM PendingLogins WHERE ExpiresAt < $1",
+                OffsetDateTime::now_utc()
+            )
+            .execute(&db)
+            .await
+            {
+                error!("failed to delete expired pending logins: {}", err);
+            }
+
+            if let Err(err) = query!(
+                "DELETE FROM AuthRequests WHERE ExpiresAt < $1",
+                OffsetDateTime::now_utc()
+            )
+            .execute(&db)
+            .await
+            {
+                error!("failed to delete expired auth requests: {}", err);
+            }
+
             sleep(Duration::from_secs(CLEANUP_INTERVAL)).await;
         }
     });
-}
\ No newline at end of file
+}
+
+/// State of a session at the time it is used.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SessionState {
+    /// The session is past its expiration or its maximum lifetime.
+    Expired,
+    Active,
+    /// The session should be extended until the given time.
+    Renew(OffsetDateTime),
+}
+
+/// Decides whether a session is still valid and whether its expiration should slide forward.
+///
+/// A session is renewed at most once per `SESSION_RENEWAL_INTERVAL`
+/// and never beyond `SESSION_MAX_LIFETIME` after its creation.
+pub fn check_session(session: &Session, now: OffsetDateTime) -> SessionState {
+    let max_expires_at = session
+        .created_at
+        .saturating_add(time::Duration::seconds(SESSION_MAX_LIFETIME));
+
+    if now >= session.expires_at || now >= max_expires_at {
+        return SessionState::Expired;
+    }
+
+    let renewed_at = session.expires_at - time::Duration::seconds(SESSION_LIFETIME);
+    if now - renewed_at < time::Duration::seconds(SESSION_RENEWAL_INTERVAL) {
+        return SessionState::Active;
+    }
+
+    let expires_at = now
+        .saturating_add(time::Duration::seconds(SESSION_LIFETIME))
+        .min(max_expires_at);
+
+    if expires_at > session.expires_at {
+        SessionState::Renew(expires_at)
+    } else {
+        SessionState::Active
+    }
+}
+
+/// Notifies the user's event streams that sessions were revoked.
+///
+/// Streams opened with one of the revoked sessions are closed,
+/// the others receive a `SessionsRevoked` event so clients can refresh the device list.
+pub fn close_streams(
+    events: &DashMap<UserId, broadcast::Sender<SseEvent>>,
+    user_id: UserId,
+    sessions_ids: Vec<SessionId>,
+) {
+    if sessions_ids.is_empty() {
+        return;
+    }
+
+    if let Some(sender) = events.get(&user_id)
+        && let Err(err) = sender.send(SseEvent::sessions_revoked(sessions_ids)) {
+            tracing::trace!("no event streams to close: {err}");
+        }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::Duration;
+
+    fn session(created_ago: Duration, expires_in: Duration, now: OffsetDateTime) -> Session {
+        Session {
+            id: SessionId::new(1),
+            user_id: UserId::new(1),
+            user_agent: None,
+            ip: None,
+            created_at: now - created_ago,
+            last_seen_at: now,
+            expires_at: now + expires_in,
+            external_auth_at: None,
+        }
+    }
+
+    #[test]
+    fn test_check_session_active() {
+        let now = OffsetDateTime::now_utc();
+        let session = session(Duration::hours(1), Duration::days(7) - Duration::hours(1), now);
+
+        assert_eq!(check_session(&session, now), SessionState::Active);
+    }
+
+    #[test]
+    fn test_check_session_expired() {
+        let now = OffsetDateTime::now_utc();
+        let session = session(Duration::days(8), -Duration::days(1), now);
+
+        assert_eq!(check_session(&session, now), SessionState::Expired);
+    }
+
+    #[test]
+    fn test_check_session_renew() {
+        let now = OffsetDateTime::now_utc();
+        let session = session(Duration::days(3), Duration::days(4), now);
+
+        assert_eq!(
+            check_session(&session, now),
+            SessionState::Renew(now + Duration::days(7))
+        );
+    }
+
+    #[test]
+    fn test_check_session_renew_capped_by_max_lifetime() {
+        let now = OffsetDateTime::now_utc();
+        let session = session(Duration::days(27), Duration::days(2), now);
+
+        assert_eq!(
+            check_session(&session, now),
+            SessionState::Renew(now + Duration::days(3))
+        );
+    }
+
+    #[test]
+    fn test_check_session_max_lifetime_exceeded() {
+        let now = OffsetDateTime::now_utc();
+        let session = session(Duration::days(30), Duration::days(1), now);
+
+        assert_eq!(check_session(&session, now), SessionState::Expired);
+    }
+
+    #[test]
+    fn test_close_streams_sends_revoked_sessions() {
+        let events = DashMap::new();
+        let (sender, mut receiver) = broadcast::channel(16);
+        events.insert(UserId::new(1), sender);
+
+        close_streams(&events, UserId::new(1), vec![SessionId::new(2), SessionId::new(3)]);
+
+        let event = receiver.try_recv().expect("event not sent");
+        assert_eq!(event.closes, vec![SessionId::new(2), SessionId::new(3)]);
+    }
+
+    #[test]
+    fn test_close_streams_skips_empty() {
+        let events = DashMap::new();
+        let (sender, mut receiver) = broadcast::channel(16);
+        events.insert(UserId::new(1), sender);
+
+        close_streams(&events, UserId::new(1), Vec::new());
+
+        assert!(receiver.try_recv().is_err());
+    }
+}
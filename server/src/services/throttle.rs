@@ -0,0 +1,290 @@
+use std::sync::Arc;
+use dashmap::DashMap;
+use time::{Duration, OffsetDateTime};
+use crate::models::users::UserId;
+
+/// Tracked keys kept by [`MemoryAttemptsStore`] before stale entries are pruned.
+const MAX_TRACKED_KEYS: usize = 100_000;
+
+/// Failed attempts counted for one key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Attempts {
+    pub failures: u32,
+    pub last_failure: OffsetDateTime,
+}
+
+/// Storage of failed attempt counters.
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait AttemptsStore: Send + Sync {
+    async fn get(&self, key: &str) -> Option<Attempts>;
+    /// Counts a failure, starting over when the last one is older than `reset_after`.
+    async fn record(&self, key: &str, now: OffsetDateTime, reset_after: Duration) -> Attempts;
+    async fn reset(&self, key: &str);
+}
+
+/// Counters kept in process memory, enough for a single node.
+#[derive(Default)]
+pub struct MemoryAttemptsStore(DashMap<String, Attempts>);
+
+#[async_trait::async_trait]
+impl AttemptsStore for MemoryAttemptsStore {
+    async fn get(&self, key: &str) -> Option<Attempts> {
+        self.0.get(key).map(|attempts| *attempts)
+    }
+
+    async fn record(&self, key: &str, now: OffsetDateTime, reset_after: Duration) -> Attempts {
+        if self.0.len() >= MAX_TRACKED_KEYS {
+            self.0.retain(|_, attempts| now - attempts.last_failure < reset_after);
+        }
+
+        let mut entry = self.0.entry(key.to_owned()).or_insert(Attempts {
+            failures: 0,
+            last_failure: now,
+        });
+
+        if now - entry.last_failure >= reset_after {
+            entry.failures = 0;
+        }
+
+        entry.failures += 1;
+        entry.last_failure = now;
+        *entry
+    }
+
+    async fn reset(&self, key: &str) {
+        self.0.remove(key);
+    }
+}
+
+/// Backoff applied to a key once its free attempts are used up.
+#[derive(Clone, Copy, Debug)]
+pub struct ThrottlePolicy {
+    /// Failures allowed without any delay.
+    pub free_attempts: u32,
+    /// Delay after the first failure beyond the free ones, doubled by every next failure.
+    pub base_delay: Duration,
+    /// Longest delay, reaching it locks the key out for this long.
+    pub max_delay: Duration,
+    /// Quiet time after which the counter starts over.
+    pub reset_after: Duration,
+}
+
+impl ThrottlePolicy {
+    /// Time that must pass after the last failure before the next attempt.
+    pub fn delay(&self, failures: u32) -> Duration {
+        if failures < self.free_attempts {
+            return Duration::ZERO;
+        }
+
+        let exponent = (failures - self.free_attempts).min(31);
+        self.base_delay
+            .checked_mul(2i32.saturating_pow(exponent))
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+}
+
+/// Subject whose failed attempts are counted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ThrottleKey {
+    /// Logins into one account, whoever tries them.
+    Username(String),
+    /// Logins from one client, whatever account they target.
+    Ip(String),
+    /// Registrations from one client.
+    Registration(String),
+    /// Second factor codes of one account, kept apart from `Username` so that
+    /// a correct password does not clear it.
+    SecondFactor(UserId),
+}
+
+impl ThrottleKey {
+    fn key(&self) -> String {
+        match self {
+            ThrottleKey::Username(username) => format!("username:{username}"),
+            ThrottleKey::Ip(ip) => format!("ip:{ip}"),
+            ThrottleKey::Registration(ip) => format!("registration:{ip}"),
+            ThrottleKey::SecondFactor(user_id) => format!("second-factor:{user_id}"),
+        }
+    }
+}
+
+/// Progressive backoff and temporary lockout of repeated login and registration attempts.
+#[derive(Clone)]
+pub struct Throttle {
+    store: Arc<dyn AttemptsStore>,
+    username: ThrottlePolicy,
+    ip: ThrottlePolicy,
+    registration: ThrottlePolicy,
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Self::new(Arc::new(MemoryAttemptsStore::default()))
+    }
+}
+
+impl Throttle {
+    pub fn new(store: Arc<dyn AttemptsStore>) -> Self {
+        Self {
+            store,
+            username: ThrottlePolicy {
+                free_attempts: 5,
+                base_delay: Duration::seconds(1),
+                max_delay: Duration::minutes(15),
+                reset_after: Duration::hours(1),
+            },
+            // a single address may be shared by many users behind a NAT
+            ip: ThrottlePolicy {
+                free_attempts: 20,
+                base_delay: Duration::seconds(1),
+                max_delay: Duration::minutes(15),
+                reset_after: Duration::hours(1),
+            },
+            registration: ThrottlePolicy {
+                free_attempts: 5,
+                base_delay: Duration::minutes(1),
+                max_delay: Duration::hours(1),
+                reset_after: Duration::hours(1),
+            },
+        }
+    }
+
+    fn policy(&self, key: &ThrottleKey) -> &ThrottlePolicy {
+        match key {
+            ThrottleKey::Username(_) | ThrottleKey::SecondFactor(_) => &self.username,
+            ThrottleKey::Ip(_) => &self.ip,
+            ThrottleKey::Registration(_) => &self.registration,
+        }
+    }
+
+    /// Returns how long to wait before the next attempt is allowed, if any of the keys is throttled.
+    pub async fn check(&self, keys: &[ThrottleKey], now: OffsetDateTime) -> Option<Duration> {
+        let mut retry_after = Duration::ZERO;
+        for key in keys {
+            let Some(attempts) = self.store.get(&key.key()).await else {
+                continue;
+            };
+
+            let policy = self.policy(key);
+            if now - attempts.last_failure >= policy.reset_after {
+                continue;
+            }
+
+            let allowed_at = attempts.last_failure + policy.delay(attempts.failures);
+            retry_after = retry_after.max(allowed_at - now);
+        }
+
+        retry_after.is_positive().then_some(retry_after)
+    }
+
+    /// Counts a failed attempt for every key.
+    pub async fn record(&self, keys: &[ThrottleKey], now: OffsetDateTime) {
+        for key in keys {
+            let policy = self.policy(key);
+            let attempts = self.store.record(&key.key(), now, policy.reset_after).await;
+            if policy.delay(attempts.failures) >= policy.max_delay {
+                tracing::warn!("{} locked out after {} failures", key.key(), attempts.failures);
+            }
+        }
+    }
+
+    pub async fn reset(&self, key: &ThrottleKey) {
+        self.store.reset(&key.key()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> ThrottlePolicy {
+        ThrottlePolicy {
+            free_attempts: 3,
+            base_delay: Duration::seconds(1),
+            max_delay: Duration::seconds(10),
+            reset_after: Duration::minutes(5),
+        }
+    }
+
+    #[test]
+    fn test_policy_delay_doubles_up_to_max() {
+        let policy = policy();
+
+        assert_eq!(policy.delay(2), Duration::ZERO);
+        assert_eq!(policy.delay(3), Duration::seconds(1));
+        assert_eq!(policy.delay(4), Duration::seconds(2));
+        assert_eq!(policy.delay(6), Duration::seconds(8));
+        assert_eq!(policy.delay(7), Duration::seconds(10));
+        assert_eq!(policy.delay(u32::MAX), Duration::seconds(10));
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_starts_over_after_quiet_time() {
+        let store = MemoryAttemptsStore::default();
+        let now = OffsetDateTime::now_utc();
+
+        store.record("key", now, Duration::minutes(5)).await;
+        let attempts = store.record("key", now, Duration::minutes(5)).await;
+        assert_eq!(attempts.failures, 2);
+
+        let later = now + Duration::minutes(5);
+        let attempts = store.record("key", later, Duration::minutes(5)).await;
+        assert_eq!(attempts, Attempts { failures: 1, last_failure: later });
+
+        store.reset("key").await;
+        assert_eq!(store.get("key").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_throttle_backs_off_after_free_attempts() {
+        let throttle = Throttle::default();
+        let keys = [ThrottleKey::Username("alice".to_owned())];
+        let now = OffsetDateTime::now_utc();
+
+        for _ in 0..4 {
+            assert_eq!(throttle.check(&keys, now).await, None);
+            throttle.record(&keys, now).await;
+        }
+
+        assert_eq!(throttle.check(&keys, now).await, None);
+        throttle.record(&keys, now).await;
+        assert_eq!(throttle.check(&keys, now).await, Some(Duration::seconds(1)));
+        assert_eq!(throttle.check(&keys, now + Duration::seconds(1)).await, None);
+
+        throttle.reset(&keys[0]).await;
+        throttle.record(&keys, now).await;
+        assert_eq!(throttle.check(&keys, now).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_throttle_keys_are_independent() {
+        let throttle = Throttle::default();
+        let username = ThrottleKey::Username("alice".to_owned());
+        let ip = ThrottleKey::Ip("10.0.0.1".to_owned());
+        let now = OffsetDateTime::now_utc();
+
+        for _ in 0..10 {
+            throttle.record(std::slice::from_ref(&username), now).await;
+        }
+
+        assert!(throttle.check(std::slice::from_ref(&username), now).await.is_some());
+        assert_eq!(throttle.check(&[ip], now).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_throttle_uses_store() {
+        let now = OffsetDateTime::now_utc();
+        let mut store = MockAttemptsStore::new();
+        store
+            .expect_get()
+            .withf(|key| key == "ip:10.0.0.1")
+            .returning(move |_| Some(Attempts { failures: 100, last_failure: now }));
+
+        let throttle = Throttle::new(Arc::new(store));
+        let retry_after = throttle.check(&[ThrottleKey::Ip("10.0.0.1".to_owned())], now).await;
+
+        assert_eq!(retry_after, Some(Duration::minutes(15)));
+    }
+}
@@ -0,0 +1,181 @@
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::Digest;
+use time::OffsetDateTime;
+use data_encoding::BASE32_NOPAD;
+use crate::{
+    error::RepositoryError,
+    models::totp::Totp,
+    repositories::totp::TotpRepository,
+};
+
+pub const ISSUER: &str = "Justice";
+const DIGITS: u32 = 6;
+const STEP_SECONDS: i64 = 30;
+/// Accepted clock drift, in steps, on both sides of the current one.
+const WINDOW: i64 = 1;
+
+pub fn encode_secret(secret: &[u8]) -> String {
+    BASE32_NOPAD.encode(secret)
+}
+
+/// Builds the key URI understood by authenticator apps.
+///
+/// Usernames are restricted to URI unreserved characters, so no escaping is needed.
+pub fn otpauth_uri(account: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{ISSUER}:{account}?secret={secret}&issuer={ISSUER}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECONDS}"
+    )
+}
+
+/// HOTP value (RFC 4226) of `secret` for the given counter.
+pub fn hotp(secret: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([hash[offset], hash[offset + 1], hash[offset + 2], hash[offset + 3]])
+        & 0x7fff_ffff;
+
+    binary % 10u32.pow(DIGITS)
+}
+
+pub fn current_step(now: OffsetDateTime) -> i64 {
+    now.unix_timestamp().div_euclid(STEP_SECONDS)
+}
+
+/// Finds the time step within the drift window whose code equals `code`.
+pub fn matching_step(secret: &str, code: &str, now: OffsetDateTime) -> Option<i64> {
+    if code.len() != DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let secret = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    let code = code.parse::<u32>().ok()?;
+    let step = current_step(now);
+
+    (step - WINDOW..=step + WINDOW)
+        .filter(|step| *step >= 0)
+        .find(|step| hotp(&secret, *step as u64) == code)
+}
+
+/// Recovery codes are random, so a single SHA-256 is enough to store them.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized = code
+        .chars()
+        .filter(|ch| ch.is_ascii_alphanumeric())
+        .map(|ch| ch.to_ascii_lowercase())
+        .collect::<String>();
+
+    hex::encode(sha2::Sha256::digest(normalized.as_bytes()))
+}
+
+/// Checks a TOTP or recovery code of an enabled enrollment and consumes it.
+///
+/// TOTP steps can't be replayed and recovery codes work only once.
+pub async fn check_code(
+    repository: &dyn TotpRepository,
+    totp: &Totp,
+    code: &str,
+    now: OffsetDateTime,
+) -> Result<bool, RepositoryError> {
+    if code.len() == DIGITS as usize && code.bytes().all(|b| b.is_ascii_digit()) {
+        return match matching_step(&totp.secret, code, now) {
+            Some(step) if step > totp.last_step => repository.use_step(totp.user_id, step).await,
+            _ => Ok(false),
+        };
+    }
+
+    repository
+        .use_recovery_code(totp.user_id, &hash_recovery_code(code))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::users::UserId, repositories::totp::MockTotpRepository};
+
+    // RFC 6238 appendix B, SHA1 key
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    fn at(timestamp: i64) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(timestamp).unwrap()
+    }
+
+    fn totp(last_step: i64) -> Totp {
+        Totp {
+            user_id: UserId::new(1),
+            secret: encode_secret(RFC_SECRET),
+            enabled: true,
+            last_step,
+        }
+    }
+
+    #[test]
+    fn test_hotp_rfc_vectors() {
+        // the RFC lists 8 digit values, the last 6 digits are the 6 digit codes
+        assert_eq!(hotp(RFC_SECRET, current_step(at(59)) as u64), 287082);
+        assert_eq!(hotp(RFC_SECRET, current_step(at(1111111109)) as u64), 81804);
+        assert_eq!(hotp(RFC_SECRET, current_step(at(1234567890)) as u64), 5924);
+        assert_eq!(hotp(RFC_SECRET, current_step(at(2000000000)) as u64), 279037);
+    }
+
+    #[test]
+    fn test_matching_step_window() {
+        let secret = encode_secret(RFC_SECRET);
+        let step = current_step(at(59));
+
+        assert_eq!(matching_step(&secret, "287082", at(59)), Some(step));
+        assert_eq!(matching_step(&secret, "287082", at(59 + 30)), Some(step));
+        assert_eq!(matching_step(&secret, "287082", at(59 + 90)), None);
+        assert_eq!(matching_step(&secret, "28708", at(59)), None);
+        assert_eq!(matching_step(&secret, "abcdef", at(59)), None);
+    }
+
+    #[test]
+    fn test_otpauth_uri() {
+        let uri = otpauth_uri("alice", "GEZDGNBV");
+        assert_eq!(
+            uri,
+            "otpauth://totp/Justice:alice?secret=GEZDGNBV&issuer=Justice&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn test_hash_recovery_code_normalizes() {
+        assert_eq!(hash_recovery_code("ab12c-de34f"), hash_recovery_code("AB12CDE34F"));
+    }
+
+    #[tokio::test]
+    async fn test_check_code_consumes_step() {
+        let step = current_step(at(59));
+        let mut repository = MockTotpRepository::new();
+        repository
+            .expect_use_step()
+            .withf(move |_, s| *s == step)
+            .returning(|_, _| Ok(true));
+
+        assert!(check_code(&repository, &totp(0), "287082", at(59)).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_check_code_rejects_replay() {
+        let step = current_step(at(59));
+        let repository = MockTotpRepository::new();
+
+        assert!(!check_code(&repository, &totp(step), "287082", at(59)).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_check_code_recovery() {
+        let mut repository = MockTotpRepository::new();
+        repository
+            .expect_use_recovery_code()
+            .withf(|_, hash| hash == hash_recovery_code("ab12c-de34f"))
+            .returning(|_, _| Ok(true));
+
+        assert!(check_code(&repository, &totp(0), "ab12c-de34f", at(59)).await.unwrap());
+    }
+}
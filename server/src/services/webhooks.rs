@@ -0,0 +1,442 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+    time::Duration,
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use reqwest::{
+    Client,
+    StatusCode,
+    Url,
+    dns::{Addrs, Name, Resolve, Resolving},
+    header::CONTENT_TYPE,
+};
+use serde_json::{Value, json};
+use time::OffsetDateTime;
+use crate::{
+    models::{
+        bots::BotWebhook,
+        events::SseEvent,
+        users::UserId,
+    },
+    repositories::bots::BotsRepository,
+};
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_ATTEMPTS: u32 = 5;
+const BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Delivers chat events to bots as signed HTTP POST requests.
+///
+/// Every delivery runs in its own task and is retried with exponential backoff,
+/// so a slow or broken bot never delays the request that produced the event.
+///
+/// Webhooks on loopback, private, link-local and other internal addresses are refused,
+/// see `is_public_address`, unless insecure hosts are allowed for local development.
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    client: Client,
+    max_attempts: u32,
+    base_delay: Duration,
+    allow_insecure_hosts: bool,
+}
+
+impl WebhookDispatcher {
+    pub fn new(max_attempts: u32, base_delay: Duration, allow_insecure_hosts: bool) -> Self {
+        let mut builder = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            // a proxy would resolve the host itself, out of reach of the resolver below
+            .no_proxy();
+        if !allow_insecure_hosts {
+            builder = builder.dns_resolver(Arc::new(PublicResolver));
+        }
+
+        Self {
+            client: builder.build().expect("failed to build webhook client"),
+            max_attempts,
+            base_delay,
+            allow_insecure_hosts,
+        }
+    }
+
+    /// Reads `WEBHOOK_ALLOW_INSECURE_HOSTS` (`true`/`false`), which lets webhooks reach
+    /// internal addresses such as `localhost` and should only be enabled for local development.
+    pub fn from_env() -> Self {
+        let allow_insecure_hosts = match std::env::var("WEBHOOK_ALLOW_INSECURE_HOSTS") {
+            Ok(value) => value.parse().expect("WEBHOOK_ALLOW_INSECURE_HOSTS must be true or false"),
+            Err(_) => false,
+        };
+
+        if allow_insecure_hosts {
+            tracing::warn!("webhooks may reach internal addresses, only use this for local development");
+        }
+
+        Self::new(MAX_ATTEMPTS, BASE_DELAY, allow_insecure_hosts)
+    }
+
+    pub fn allows_insecure_hosts(&self) -> bool {
+        self.allow_insecure_hosts
+    }
+
+    /// Sends `event` to every bot among `recipients` that has a webhook.
+    pub async fn notify_bots(&self, bots: &dyn BotsRepository, recipients: &[UserId], event: &SseEvent) {
+        let webhooks = match bots.get_webhooks(recipients).await {
+            Ok(webhooks) => webhooks,
+            Err(err) => {
+                tracing::error!("failed to get bot webhooks: {err}");
+                return;
+            }
+        };
+
+        if webhooks.is_empty() {
+            return;
+        }
+
+        let data = serde_json::from_str::<Value>(&event.data).unwrap_or(Value::Null);
+        let body = json!({ "type": event.event_type.as_ref(), "data": data }).to_string();
+        for webhook in webhooks {
+            let dispatcher = self.clone();
+            let body = body.clone();
+            let event_type = event.event_type.as_ref().to_owned();
+            tokio::spawn(async move { dispatcher.deliver(webhook, event_type, body).await });
+        }
+    }
+
+    /// Posts `body` to the webhook until it is accepted or the attempts run out.
+    pub async fn deliver(&self, webhook: BotWebhook, event_type: String, body: String) -> bool {
+        // hosts given as names are checked by the resolver on every connection, addresses here
+        if !self.allow_insecure_hosts
+            && let Some(ip) = literal_address(&webhook.url)
+            && !is_public_address(ip)
+        {
+            tracing::warn!("webhook of bot {} points to the internal address {ip}", webhook.bot_id);
+            return false;
+        }
+
+        for attempt in 0..self.max_attempts {
+            if attempt > 0 {
+                tokio::time::sleep(backoff(self.base_delay, attempt)).await;
+            }
+
+            let timestamp = OffsetDateTime::now_utc().unix_timestamp();
+            let result = self
+                .client
+                .post(&webhook.url)
+                .header(CONTENT_TYPE, "application/json")
+                .header(EVENT_HEADER, &event_type)
+                .header(TIMESTAMP_HEADER, timestamp)
+                .header(SIGNATURE_HEADER, sign(&webhook.secret, timestamp, &body))
+                .body(body.clone())
+                .send()
+                .await;
+
+            match result {
+                Ok(response) if response.status().is_success() => {
+                    tracing::trace!("webhook of bot {} delivered", webhook.bot_id);
+                    return true;
+                }
+
+                Ok(response) if !is_retryable(response.status()) => {
+                    tracing::warn!(
+                        "webhook of bot {} rejected with {}",
+                        webhook.bot_id,
+                        response.status()
+                    );
+                    return false;
+                }
+
+                Ok(response) => {
+                    tracing::warn!(
+                        "webhook of bot {} failed with {}, attempt {}",
+                        webhook.bot_id,
+                        response.status(),
+                        attempt + 1
+                    );
+                }
+
+                Err(err) => {
+                    tracing::warn!(
+                        "webhook of bot {} failed: {err}, attempt {}",
+                        webhook.bot_id,
+                        attempt + 1
+                    );
+                }
+            }
+        }
+
+        tracing::error!("giving up on webhook of bot {}", webhook.bot_id);
+        false
+    }
+}
+
+impl Default for WebhookDispatcher {
+    fn default() -> Self {
+        Self::new(MAX_ATTEMPTS, BASE_DELAY, false)
+    }
+}
+
+/// Resolves webhook hosts and fails if any of their addresses is internal.
+///
+/// The client connects to exactly the returned addresses, so a host cannot pass the check
+/// and then resolve to an internal address for the connection.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((name.as_str(), 0)).await?.collect::<Vec<_>>();
+            if let Some(addr) = addrs.iter().find(|addr| !is_public_address(addr.ip())) {
+                return Err(format!("{} resolves to the internal address {}", name.as_str(), addr.ip()).into());
+            }
+
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Address of the url when its host is an IP address rather than a name.
+///
+/// The url parser already normalizes forms like `0x7f.1` into dotted IPv4 addresses.
+pub fn literal_address(url: &str) -> Option<IpAddr> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    host.trim_start_matches('[').trim_end_matches(']').parse().ok()
+}
+
+/// Whether the address is reachable on the public internet.
+///
+/// Loopback, private, shared, link-local (which includes cloud metadata endpoints such as
+/// `169.254.169.254`), unspecified, multicast, documentation and reserved ranges are not,
+/// nor are IPv6 addresses embedding one of them.
+pub fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => is_public_ipv6(ip),
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // "this network", shared address space, IETF protocol assignments, benchmarking, reserved
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (18..20).contains(&b))
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    if let Some(ipv4) = ip.to_ipv4_mapped() {
+        return is_public_ipv4(ipv4);
+    }
+
+    let segments = ip.segments();
+    let embedded = |high: u16, low: u16| Ipv4Addr::from((u32::from(high) << 16) | u32::from(low));
+    match segments {
+        // NAT64 and 6to4 reach the embedded IPv4 address
+        [0x64, 0xff9b, 0, 0, 0, 0, high, low] => return is_public_ipv4(embedded(high, low)),
+        [0x2002, high, low, ..] => return is_public_ipv4(embedded(high, low)),
+        // deprecated IPv4-compatible addresses
+        [0, 0, 0, 0, 0, 0, high, low] if !ip.is_loopback() && !ip.is_unspecified() => {
+            return is_public_ipv4(embedded(high, low));
+        }
+        _ => {}
+    }
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        || ip.is_unique_local()
+        || ip.is_unicast_link_local()
+        // documentation
+        || (segments[0] == 0x2001 && segments[1] == 0xdb8))
+}
+
+/// Signs `{timestamp}.{body}` with HMAC-SHA256, receivers should reject stale timestamps.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn backoff(base_delay: Duration, attempt: u32) -> Duration {
+    base_delay.saturating_mul(1 << (attempt - 1).min(10))
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    };
+    use axum::{Router, extract::State, http::HeaderMap, routing::post};
+
+    #[test]
+    fn test_sign() {
+        let signature = sign("secret", 1_700_000_000, "{}");
+
+        assert_eq!(
+            signature,
+            "sha256=b8569b78799ff9e3cbff0fc2d63a33a2b57f3282abd07c37ae5e8e7d79a5f163"
+        );
+        assert_ne!(signature, sign("other", 1_700_000_000, "{}"));
+        assert_ne!(signature, sign("secret", 1_700_000_001, "{}"));
+    }
+
+    #[test]
+    fn test_backoff() {
+        let base = Duration::from_secs(1);
+
+        assert_eq!(backoff(base, 1), Duration::from_secs(1));
+        assert_eq!(backoff(base, 2), Duration::from_secs(2));
+        assert_eq!(backoff(base, 4), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(is_retryable(StatusCode::BAD_GATEWAY));
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_retryable(StatusCode::NOT_FOUND));
+    }
+
+    async fn receiver(
+        State((calls, failures)): State<(Arc<AtomicU32>, u32)>,
+        headers: HeaderMap,
+        body: String,
+    ) -> StatusCode {
+        let timestamp = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+        assert_eq!(headers[SIGNATURE_HEADER], sign("secret", timestamp, &body));
+        assert_eq!(headers[EVENT_HEADER], "Message");
+
+        if calls.fetch_add(1, Ordering::SeqCst) < failures {
+            StatusCode::SERVICE_UNAVAILABLE
+        } else {
+            StatusCode::NO_CONTENT
+        }
+    }
+
+    async fn spawn_receiver(failures: u32) -> (String, Arc<AtomicU32>) {
+        let calls = Arc::new(AtomicU32::new(0));
+        let app = Router::new()
+            .route("/hook", post(receiver))
+            .with_state((calls.clone(), failures));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        (url, calls)
+    }
+
+    fn webhook(url: String) -> BotWebhook {
+        BotWebhook {
+            bot_id: UserId::new(7),
+            url,
+            secret: "secret".to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deliver_retries() {
+        let (url, calls) = spawn_receiver(2).await;
+        let dispatcher = WebhookDispatcher::new(3, Duration::from_millis(1), true);
+
+        assert!(dispatcher.deliver(webhook(url), "Message".to_owned(), "{}".to_owned()).await);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_deliver_gives_up() {
+        let (url, calls) = spawn_receiver(u32::MAX).await;
+        let dispatcher = WebhookDispatcher::new(2, Duration::from_millis(1), true);
+
+        assert!(!dispatcher.deliver(webhook(url), "Message".to_owned(), "{}".to_owned()).await);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_deliver_refuses_internal_hosts() {
+        let (url, calls) = spawn_receiver(0).await;
+        let dispatcher = WebhookDispatcher::new(2, Duration::from_millis(1), false);
+        let localhost = url.replace("127.0.0.1", "localhost");
+
+        assert!(!dispatcher.deliver(webhook(url), "Message".to_owned(), "{}".to_owned()).await);
+        assert!(!dispatcher.deliver(webhook(localhost), "Message".to_owned(), "{}".to_owned()).await);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_resolver_rejects_internal_names() {
+        let result = PublicResolver.resolve("localhost".parse().unwrap()).await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_is_public_address() {
+        for ip in [
+            "0.0.0.0",
+            "0.1.2.3",
+            "127.0.0.1",
+            "127.255.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "172.31.255.255",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "100.100.100.200",
+            "192.0.0.1",
+            "198.18.0.1",
+            "224.0.0.1",
+            "240.0.0.1",
+            "255.255.255.255",
+            "::",
+            "::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "::10.0.0.1",
+            "64:ff9b::a00:1",
+            "2002:7f00:1::",
+            "fc00::1",
+            "fd00:ec2::254",
+            "fe80::1",
+            "ff02::1",
+            "2001:db8::1",
+        ] {
+            assert!(!is_public_address(ip.parse().unwrap()), "{ip}");
+        }
+
+        for ip in ["1.1.1.1", "8.8.8.8", "172.32.0.1", "100.128.0.1", "2606:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(is_public_address(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn test_literal_address() {
+        assert_eq!(literal_address("http://10.0.0.1:8080/hook"), Some("10.0.0.1".parse().unwrap()));
+        assert_eq!(literal_address("http://[::1]/hook"), Some("::1".parse().unwrap()));
+        assert_eq!(literal_address("http://0x7f.1/hook"), Some("127.0.0.1".parse().unwrap()));
+        assert_eq!(literal_address("https://example.com/hook"), None);
+    }
+}
@@ -4,32 +4,91 @@ use tokio::sync::{Mutex, broadcast};
 use crate::{
     models::{events::SseEvent, users::UserId},
     rand::RandomGenerator,
+    services::{
+        identity::IdentityProviders,
+        password::PasswordHasher,
+        rate_limit::RateLimiter,
+        presence::PresenceTracker,
+        export::ExportTracker,
+        throttle::Throttle,
+        webhooks::WebhookDispatcher,
+    },
     repositories::{
         chats::{ChatsRepository, PgChatsRepository},
         users::{UsersRepository, PgUsersRepository},
         messages::{MessagesRepository, PgMessagesRepository},
         sessions::{SessionsRepository, PgSessionsRepository},
+        tokens::{TokensRepository, PgTokensRepository},
+        bots::{BotsRepository, PgBotsRepository},
+        totp::{TotpRepository, PgTotpRepository},
+        identities::{IdentitiesRepository, PgIdentitiesRepository},
+        profiles::{ProfilesRepository, PgProfilesRepository},
+        presence::{PresenceRepository, PgPresenceRepository},
+        blocks::{BlocksRepository, PgBlocksRepository},
+        admin::{AdminRepository, PgAdminRepository},
+        invites::{InvitesRepository, PgInvitesRepository},
+        receipts::{ReceiptsRepository, PgReceiptsRepository},
     },
 };
 
 pub struct AppState {
     pub random: Arc<Mutex<dyn RandomGenerator>>,
+    pub hasher: PasswordHasher,
+    pub throttle: Throttle,
+    pub rate_limiter: RateLimiter,
     pub events: Arc<DashMap<UserId, broadcast::Sender<SseEvent>>>,
+    pub presence: PresenceTracker,
+    pub exports: Arc<ExportTracker>,
+    pub webhooks: WebhookDispatcher,
+    pub identity_providers: IdentityProviders,
     pub users: Arc<dyn UsersRepository>,
     pub sessions: Arc<dyn SessionsRepository>,
+    pub tokens: Arc<dyn TokensRepository>,
+    pub bots: Arc<dyn BotsRepository>,
+    pub totp: Arc<dyn TotpRepository>,
+    pub identities: Arc<dyn IdentitiesRepository>,
+    pub profiles: Arc<dyn ProfilesRepository>,
+    pub presences: Arc<dyn PresenceRepository>,
+    pub blocks: Arc<dyn BlocksRepository>,
+    pub admin: Arc<dyn AdminRepository>,
     pub chats: Arc<dyn ChatsRepository>,
+    pub invites: Arc<dyn InvitesRepository>,
+    pub receipts: Arc<dyn ReceiptsRepository>,
     pub messages: Arc<dyn MessagesRepository>,
 }
 
 impl AppState {
-    pub fn new(random: Arc<Mutex<dyn RandomGenerator>>, pool: sqlx::PgPool) -> Self {
+    pub fn new(
+        random: Arc<Mutex<dyn RandomGenerator>>,
+        hasher: PasswordHasher,
+        rate_limiter: RateLimiter,
+        identity_providers: IdentityProviders,
+        pool: sqlx::PgPool,
+    ) -> Self {
         Self {
             users: Arc::new(PgUsersRepository::new(pool.clone())),
             sessions: Arc::new(PgSessionsRepository::new(pool.clone())),
+            tokens: Arc::new(PgTokensRepository::new(pool.clone())),
+            bots: Arc::new(PgBotsRepository::new(pool.clone())),
+            totp: Arc::new(PgTotpRepository::new(pool.clone())),
+            identities: Arc::new(PgIdentitiesRepository::new(pool.clone())),
+            profiles: Arc::new(PgProfilesRepository::new(pool.clone())),
+            presences: Arc::new(PgPresenceRepository::new(pool.clone())),
+            blocks: Arc::new(PgBlocksRepository::new(pool.clone())),
+            admin: Arc::new(PgAdminRepository::new(pool.clone())),
             chats: Arc::new(PgChatsRepository::new(pool.clone())),
+            invites: Arc::new(PgInvitesRepository::new(pool.clone())),
+            receipts: Arc::new(PgReceiptsRepository::new(pool.clone())),
             messages: Arc::new(PgMessagesRepository::new(pool)),
             events: Arc::new(DashMap::new()),
+            presence: PresenceTracker::default(),
+            exports: Arc::new(ExportTracker::from_env()),
+            webhooks: WebhookDispatcher::from_env(),
+            throttle: Throttle::default(),
+            identity_providers,
+            rate_limiter,
             random,
+            hasher,
         }
     }
 }
\ No newline at end of file
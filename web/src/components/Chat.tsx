@@ -3,6 +3,7 @@ import {
 	GetChatMessagesResponse,
 	Message,
 	SendMessageResponse,
+	markRead,
 } from "../models/chats";
 import {
 	Accessor,
@@ -54,6 +55,9 @@ export default function ChatView({
 		setMessages(body.messages.reverse());
 		setHasMore(body.has_more);
 
+		const latest = messages().at(-1);
+		if (latest) markRead(chatId(), latest.id);
+
 		requestAnimationFrame(() => {
 			if (container) container.scrollTop = container.scrollHeight;
 		});
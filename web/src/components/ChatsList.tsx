@@ -1,8 +1,8 @@
 import "./ChatList.tsx.css";
 import { createSignal, For, onMount } from "solid-js";
 import { createStore, SetStoreFunction } from "solid-js/store";
-import { Chat, ChatCreationResponse, Member } from "../models/chats";
-import { Button, Form, ListGroup, Modal } from "solid-bootstrap";
+import { Chat, ChatCreationResponse, GetChatsResponse, Member } from "../models/chats";
+import { Badge, Button, Form, ListGroup, Modal } from "solid-bootstrap";
 import MemberInput from "./MemberInput";
 import { useLocation } from "@solidjs/router";
 
@@ -66,8 +66,9 @@ export default function ChatsList({ chats, setChats }: ChatsListProps) {
 	onMount(async () => {
 		const res = await fetch("/chats");
 		if (res.ok) {
-			const chats: Chat[] = await res.json();
-			setChats(chats);
+			const page: GetChatsResponse = await res.json();
+			// the list shows the store reversed, so the most recent chat goes last
+			setChats(page.chats.reverse());
 		} else {
 			console.error(res.status);
 			console.error(await res.json());
@@ -83,8 +84,20 @@ export default function ChatsList({ chats, setChats }: ChatsListProps) {
 			<ListGroup defaultActiveKey={params.hash}>
 				<For each={chats.reverse()}>
 					{(chat) => (
-						<ListGroup.Item action href={"#" + chat.id}>
-							{chat.title}
+						<ListGroup.Item
+							action
+							href={"#" + chat.id}
+							class="d-flex justify-content-between align-items-start"
+						>
+							<div class="text-truncate">
+								<div>{chat.title}</div>
+								<small class="text-muted">{chat.last_message?.content}</small>
+							</div>
+							{chat.unread_count ? (
+								<Badge bg="primary" pill>
+									{chat.unread_count}
+								</Badge>
+							) : null}
 						</ListGroup.Item>
 					)}
 				</For>
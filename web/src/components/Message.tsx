@@ -2,7 +2,7 @@ import { createEffect, createSignal } from "solid-js";
 import { useUsers } from "../contexts/UserContext";
 import { Message } from "../models/chats";
 import { User } from "../models/users";
-import { Placeholder } from "solid-bootstrap";
+import { Badge, Placeholder } from "solid-bootstrap";
 
 export default function ChatMessage(msg: Message) {
 	const { users, setUser } = useUsers();
@@ -19,7 +19,7 @@ export default function ChatMessage(msg: Message) {
 				? users.currentUser
 				: users.users[msg.sender_id];
 		if (sender) {
-			setUsername(sender.username);
+			setUsername(sender.display_name ?? sender.username);
 			return;
 		}
 
@@ -33,20 +33,34 @@ export default function ChatMessage(msg: Message) {
 
 		const user: User = await res.json();
 		setUser("users", msg.sender_id, user);
-		setUsername(user.username);
+		setUsername(user.display_name ?? user.username);
 	});
 
 	return (
 		<div class="m-3 p-3 border d-flex flex-column">
 			<div class="d-flex flex-row justify-content-between">
 				{username() ? (
-					<span class="me-5 first-color">{username()}</span>
+					<span class="me-5 first-color">
+						{username()}
+						{msg.is_bot && (
+							<Badge bg="secondary" class="ms-2">
+								bot
+							</Badge>
+						)}
+					</span>
 				) : (
 					<Placeholder />
 				)}
 				<span>{new Date(msg.created_at).toLocaleString()}</span>
 			</div>
-			<span>{msg.content}</span>
+			{msg.sender_blocked ? (
+				<details>
+					<summary class="text-secondary">Message from a blocked user</summary>
+					<span>{msg.content}</span>
+				</details>
+			) : (
+				<span>{msg.content}</span>
+			)}
 		</div>
 	);
 }
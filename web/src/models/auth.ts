@@ -6,4 +6,6 @@ export interface ILoginErrorResponse extends IServerError {
 
 export interface ILoginResponse {
 	readonly user_id: number;
+	readonly second_factor_required: boolean;
+	readonly pending_token?: string;
 }
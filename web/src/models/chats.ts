@@ -1,6 +1,29 @@
 export interface Chat {
 	id: number;
+	kind?: "direct" | "group";
+	visibility?: "private" | "public";
 	title: string;
+	description?: string | null;
+	image_url?: string | null;
+	version?: number;
+	last_message?: MessagePreview | null;
+	last_read_message_id?: number | null;
+	unread_count?: number;
+	last_activity_at?: string;
+}
+
+export interface MessagePreview {
+	id: number;
+	sender_id: number | null;
+	sender_blocked: boolean;
+	content: string;
+	truncated: boolean;
+	created_at: string;
+}
+
+export interface GetChatsResponse {
+	chats: Chat[];
+	next_cursor: string | null;
 }
 
 export interface Member {
@@ -18,6 +41,8 @@ export interface Message {
 	created_at: string;
 	id: number;
 	sender_id: number | null;
+	is_bot: boolean;
+	sender_blocked: boolean;
 }
 
 export interface GetChatMessagesResponse {
@@ -25,6 +50,14 @@ export interface GetChatMessagesResponse {
 	messages: Message[];
 }
 
+export async function markRead(chatId: number | string, messageId: number) {
+	await fetch(`/chats/${chatId}/read`, {
+		method: "PUT",
+		headers: { "Content-Type": "application/json" },
+		body: JSON.stringify({ message_id: messageId }),
+	});
+}
+
 export interface SendMessageResponse {
 	message_id: number;
 }
@@ -1,4 +1,5 @@
-import { Message } from "./chats";
+import { Chat, Message } from "./chats";
+import { User } from "./users";
 
 export interface NewMessageEvent {
 	chat_id: number;
@@ -11,3 +12,31 @@ export interface NewChatEvent {
 	users_ids: number[];
 	chat_id: number;
 }
+
+export interface ChatMembersEvent {
+	chat_id: number;
+	title: string;
+	users_ids: number[];
+	added: number[];
+	removed: number[];
+}
+
+export interface ChatUpdatedEvent {
+	chat: Chat;
+}
+
+export interface ReadMarkerEvent {
+	chat_id: number;
+	user_id: number;
+	message_id: number;
+}
+
+export interface ProfileEvent {
+	user: User;
+}
+
+export interface PresenceEvent {
+	user_id: number;
+	status: "online" | "idle" | "offline";
+	last_seen_at: Date | null;
+}
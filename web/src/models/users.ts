@@ -1,7 +1,17 @@
+export interface UserStatus {
+	text: string;
+	expires_at: Date | null;
+}
+
 export interface User {
 	id: number;
 	username: string;
+	is_bot: boolean;
 	created_at: Date;
+	display_name: string | null;
+	bio: string | null;
+	avatar_url: string | null;
+	status: UserStatus | null;
 }
 
 export interface UsersContextModel {
@@ -1,11 +1,12 @@
-import { A, useLocation } from "@solidjs/router";
+import { useLocation } from "@solidjs/router";
 import { Button } from "solid-bootstrap";
 import ChatsList from "../components/ChatsList";
 import { createMemo, createSignal, onCleanup, onMount } from "solid-js";
-import { Chat, Message } from "../models/chats";
-import { NewChatEvent, NewMessageEvent } from "../models/events";
+import { Chat, Message, markRead } from "../models/chats";
+import { ChatMembersEvent, ChatUpdatedEvent, NewChatEvent, NewMessageEvent, ProfileEvent, ReadMarkerEvent } from "../models/events";
 import ChatView from "../components/Chat";
 import { createStore } from "solid-js/store";
+import { useUsers } from "../contexts/UserContext";
 
 export default function ChatPage() {
 	const params = useLocation();
@@ -13,6 +14,7 @@ export default function ChatPage() {
 	const [hasMore, setHasMore] = createSignal(false);
 	const [messages, setMessages] = createSignal<Message[]>([]);
 	const [chats, setChats] = createStore<Chat[]>([]);
+	const { users, setUser } = useUsers();
 	let chatContainer!: HTMLDivElement;
 
 	onMount(() => {
@@ -25,6 +27,17 @@ export default function ChatPage() {
 				requestAnimationFrame(() => {
 					if (chatContainer) chatContainer.scrollTop = chatContainer.scrollHeight;
 				});
+				markRead(eventData.chat_id, eventData.message.id);
+			} else {
+				setChats((chat) => chat.id === eventData.chat_id, "unread_count", (count) => (count ?? 0) + 1);
+			}
+		});
+
+		events.addEventListener("ReadMarker", (event) => {
+			const eventData: ReadMarkerEvent = JSON.parse(event.data);
+			// markers of other members are receipts, only our own clear the badge
+			if (eventData.user_id === users.currentUser?.id) {
+				setChats((chat) => chat.id === eventData.chat_id, "unread_count", 0);
 			}
 		});
 
@@ -37,7 +50,43 @@ export default function ChatPage() {
 			setChats(chats.length, chat);
 		});
 
+		events.addEventListener("ChatMembers", (event) => {
+			const eventData: ChatMembersEvent = JSON.parse(event.data);
+			const currentUserId = users.currentUser?.id;
+			if (currentUserId === undefined) return;
+
+			if (eventData.removed.includes(currentUserId)) {
+				setChats((chats) => chats.filter((chat) => chat.id !== eventData.chat_id));
+			} else if (eventData.added.includes(currentUserId)) {
+				setChats(chats.length, { id: eventData.chat_id, title: eventData.title });
+			}
+		});
+
+		events.addEventListener("ChatUpdated", (event) => {
+			const eventData: ChatUpdatedEvent = JSON.parse(event.data);
+			setChats((chat) => chat.id === eventData.chat.id, eventData.chat);
+		});
+
+		events.addEventListener("Profile", (event) => {
+			const eventData: ProfileEvent = JSON.parse(event.data);
+			if (eventData.user.id === users.currentUser?.id) {
+				setUser("currentUser", eventData.user);
+			} else {
+				setUser("users", eventData.user.id, eventData.user);
+			}
+		});
+
+		const reportActivity = () => {
+			fetch("/presence", {
+				method: "PUT",
+				headers: { "Content-Type": "application/json" },
+				body: JSON.stringify({ idle: document.hidden }),
+			});
+		};
+		document.addEventListener("visibilitychange", reportActivity);
+
 		onCleanup(() => {
+			document.removeEventListener("visibilitychange", reportActivity);
 			events.close();
 		});
 	});
@@ -45,9 +94,9 @@ export default function ChatPage() {
 	return (
 		<div class="w-100 h-100 d-flex flex-row">
 			<aside class="d-flex h-100 align-items-end fit p-3 border-end">
-				<A href="/logout" target="_self">
-					<Button variant="danger">Exit</Button>
-				</A>
+				<form method="post" action="/logout">
+					<Button type="submit" variant="danger">Exit</Button>
+				</form>
 			</aside>
 			<main class="d-flex flex-row w-100">
 				<ChatsList {...{ chats, setChats }} />
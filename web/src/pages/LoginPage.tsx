@@ -11,17 +11,21 @@ const LoginPage = () => {
 	const [showError, setShowError] = createSignal(false);
 	const [username, setUsername] = createSignal("");
 	const [password, setPassword] = createSignal("");
+	const [pendingToken, setPendingToken] = createSignal<string | null>(null);
+	const [code, setCode] = createSignal("");
+	const [retryAfter, setRetryAfter] = createSignal<number | null>(null);
 
 	const onSubmit = async (e: SubmitEvent) => {
 		e.preventDefault();
 		setShowError(false);
+		setRetryAfter(null);
 
-		const data = {
-			username: username(),
-			password: password(),
-		};
+		const token = pendingToken();
+		const [url, data] = token
+			? ["/login/totp", { pending_token: token, code: code() }]
+			: ["/login", { username: username(), password: password() }];
 
-		const res = await fetch("/login", {
+		const res = await fetch(url, {
 			method: "POST",
 			headers: {
 				"Content-Type": "application/json",
@@ -31,13 +35,23 @@ const LoginPage = () => {
 
 		if (res.ok) {
 			const body: ILoginResponse = await res.json();
+			if (body.second_factor_required && body.pending_token) {
+				setPendingToken(body.pending_token);
+				return;
+			}
+
 			setUser("currentUser", {
 				id: body.user_id,
 				username: username(),
 			});
 			navigate("/", { replace: true });
-		} else if (res.status === 404) {
+		} else if (res.status === 404 && token) {
+			setPendingToken(null);
+			setShowError(true);
+		} else if (res.status === 404 || (res.status === 400 && token)) {
 			setShowError(true);
+		} else if (res.status === 429) {
+			setRetryAfter(Number(res.headers.get("Retry-After")));
 		} else {
 			console.error(res.status);
 			console.error(await res.json());
@@ -57,15 +71,31 @@ const LoginPage = () => {
 						/>
 						<Show when={showError()}>
 							<Form.Text class="text-danger">
-								Incorrect username or password
+								{pendingToken() ? "Incorrect code" : "Incorrect username or password"}
+							</Form.Text>
+						</Show>
+						<Show when={retryAfter()}>
+							<Form.Text class="text-danger">
+								Too many attempts, try again in {retryAfter()} seconds
 							</Form.Text>
 						</Show>
 					</Form.Group>
 					<Form.Control
 						type="password"
 						placeholder="Password"
+						disabled={pendingToken() !== null}
 						onInput={(e) => setPassword(e.currentTarget.value)}
 					/>
+					<Show when={pendingToken()}>
+						<Form.Control
+							class="mt-1"
+							type="text"
+							inputMode="numeric"
+							autocomplete="one-time-code"
+							placeholder="Authentication or recovery code"
+							onInput={(e) => setCode(e.currentTarget.value)}
+						/>
+					</Show>
 
 					<div class="mt-3 d-flex w-100">
 						<div class="w-100">
@@ -62,6 +62,9 @@ const RegisterPage = () => {
 			}
 		} else if (res.status === 409) {
 			setUsernameErrors(["There is already a user with that name"]);
+		} else if (res.status === 429) {
+			const retryAfter = res.headers.get("Retry-After");
+			setUsernameErrors([`Too many registrations, try again in ${retryAfter} seconds`]);
 		} else {
 			console.error(res.status);
 			console.error(await res.json());
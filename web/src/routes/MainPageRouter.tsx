@@ -1,19 +1,23 @@
 import { RouteSectionProps } from "@solidjs/router";
-import { CookieJar } from "../utils/cookie";
 import MainPage from "../pages/MainPage";
-import { Component } from "solid-js";
+import { Component, createResource, Show } from "solid-js";
 
 const MainPageRouter: Component<RouteSectionProps> = (
 	props: RouteSectionProps,
 ) => {
-	const cookies = new CookieJar(document.cookie);
-	const session = cookies.getCookie("session");
+	// The session cookie is HttpOnly, so ask the server whether it is valid.
+	const [authorized] = createResource(async () => {
+		const res = await fetch("/users/0");
+		return res.ok;
+	});
 
-	if (session === undefined) {
-		return <MainPage />;
-	} else {
-		return props.children;
-	}
+	return (
+		<Show when={!authorized.loading}>
+			<Show when={authorized()} fallback={<MainPage />}>
+				{props.children}
+			</Show>
+		</Show>
+	);
 };
 
 export default MainPageRouter;